@@ -0,0 +1,45 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use mda::{Email, NormalizationOptions};
+
+/// Creates a large plain ASCII email, which is normalized by the fast path.
+fn plain_email() -> Vec<u8> {
+    let mut data = String::from(
+        "Return-Path: <me@source.com>\n\
+         To: Destination <someone.else@destination.com>\n\
+         Subject: A large plain\n folded subject\n\
+         Content-Type: text/plain; charset=us-ascii\n\n");
+    for i in 0..20000 {
+        data += &format!("Line {} of a large plain text message body.\n", i);
+    }
+    data.into_bytes()
+}
+
+fn normalize(c: &mut Criterion) {
+    let data = plain_email();
+
+    c.bench_function("normalize plain email", |b| {
+        b.iter(|| Email::from_vec(data.clone()).unwrap())
+    });
+
+    // A no-op part transform makes normalization use the full parser.
+    c.bench_function("normalize plain email with full parser", |b| {
+        b.iter(|| {
+            let options = NormalizationOptions{
+                part_transform: Some(Box::new(|_, _| {})),
+                ..Default::default()
+            };
+            Email::from_vec_with_options(data.clone(), options).unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, normalize);
+criterion_main!(benches);
@@ -0,0 +1,510 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Parsing of email addresses in header fields.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::BorrowedEmail;
+use crate::normalize::decode_encoded_words;
+use crate::util::find_ignore_ascii_case;
+
+/// An email address (mailbox), as found in address header fields like From
+/// and To.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    /// The display name of the mailbox, e.g., `John Doe` in
+    /// `John Doe <john@example.com>`, if any.
+    pub display_name: Option<String>,
+    /// The local part of the address, without any quoting.
+    pub local: String,
+    /// The domain part of the address.
+    pub domain: String,
+}
+
+impl Address {
+    /// Returns the bare address, e.g., `john@example.com`.
+    pub fn addr_spec(&self) -> String {
+        format!("{}@{}", self.local, self.domain)
+    }
+
+    /// Returns whether the bare address of this address is equal to the
+    /// specified bare address. The domain is compared case-insensitively.
+    pub fn has_addr_spec(&self, addr_spec: &str) -> bool {
+        match addr_spec.rsplit_once('@') {
+            Some((local, domain)) =>
+                local == self.local && domain.eq_ignore_ascii_case(&self.domain),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.display_name {
+            Some(name) => write!(f, "{} <{}>", name, self.addr_spec()),
+            None => write!(f, "{}", self.addr_spec()),
+        }
+    }
+}
+
+/// A lexical token of an address header field value.
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Comment(String),
+    Special(char),
+    Space,
+}
+
+fn is_special(c: char) -> bool {
+    matches!(c, ',' | ';' | ':' | '<' | '>' | '@')
+}
+
+/// Splits an address header field value into tokens.
+fn tokenize(value: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                tokens.push(Token::Space);
+            },
+            '"' => {
+                chars.next();
+                let mut quoted = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => quoted.extend(chars.next()),
+                        _ => quoted.push(c),
+                    }
+                }
+                tokens.push(Token::Quoted(quoted));
+            },
+            '(' => {
+                chars.next();
+                let mut comment = String::new();
+                let mut depth = 1;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        },
+                        '\\' => { comment.extend(chars.next()); continue; },
+                        _ => {},
+                    }
+                    comment.push(c);
+                }
+                tokens.push(Token::Comment(comment));
+            },
+            c if is_special(c) => {
+                chars.next();
+                tokens.push(Token::Special(c));
+            },
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || is_special(c) || c == '"' || c == '(' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            },
+        }
+    }
+
+    tokens
+}
+
+/// Decodes any MIME encoded-words in some text, using the specified
+/// charset aliases.
+fn decode_words(text: &str, charset_aliases: &HashMap<String, String>) -> String {
+    String::from_utf8_lossy(&decode_encoded_words(text.as_bytes(), charset_aliases).0).into_owned()
+}
+
+fn is_encoded_word(text: &str) -> bool {
+    text.starts_with("=?") && text.ends_with("?=")
+}
+
+/// Joins the words and quoted strings of a phrase (e.g., a display name),
+/// separating them with single spaces and decoding any MIME encoded-words.
+///
+/// Decoding happens after the field value has been split into tokens, so
+/// decoded text can't affect the structure of the field value.
+fn join_phrase(tokens: &[Token], charset_aliases: &HashMap<String, String>) -> Option<String> {
+    let mut phrase = String::new();
+    let mut pending_space = false;
+    let mut prev_encoded = false;
+
+    for token in tokens {
+        match token {
+            Token::Word(s) | Token::Quoted(s) => {
+                let encoded = is_encoded_word(s);
+                // Whitespace between consecutive encoded-words is ignored.
+                if pending_space && !phrase.is_empty() && !(encoded && prev_encoded) {
+                    phrase.push(' ');
+                }
+                phrase.push_str(&decode_words(s, charset_aliases));
+                pending_space = false;
+                prev_encoded = encoded;
+            },
+            Token::Space => pending_space = true,
+            _ => {},
+        }
+    }
+
+    if phrase.is_empty() { None } else { Some(phrase) }
+}
+
+/// Builds an address from the tokens of an addr-spec (`local@domain`),
+/// ignoring whitespace and comments.
+fn parse_addr_spec(tokens: &[Token], display_name: Option<String>) -> Option<Address> {
+    let at = tokens.iter().rposition(|t| *t == Token::Special('@'))?;
+    let concat = |tokens: &[Token]| -> String {
+        tokens.iter()
+            .filter_map(|t| match t {
+                Token::Word(s) | Token::Quoted(s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let local = concat(&tokens[..at]);
+    let domain = concat(&tokens[at + 1..]);
+    if local.is_empty() || domain.is_empty() {
+        return None;
+    }
+
+    Some(Address{display_name, local, domain})
+}
+
+/// Builds an address from the tokens of a single mailbox, either in the
+/// `Display Name <local@domain>` or the `local@domain (Comment)` form.
+fn parse_mailbox(tokens: &[Token], charset_aliases: &HashMap<String, String>) -> Option<Address> {
+    match tokens.iter().position(|t| *t == Token::Special('<')) {
+        Some(open) => {
+            let close = tokens[open..].iter()
+                .position(|t| *t == Token::Special('>'))
+                .map_or(tokens.len(), |i| open + i);
+            let mut addr_tokens = &tokens[open + 1..close];
+            // Ignore obsolete source routes, e.g., <@relay1,@relay2:user@host>.
+            if let Some(colon) = addr_tokens.iter().rposition(|t| *t == Token::Special(':')) {
+                addr_tokens = &addr_tokens[colon + 1..];
+            }
+            parse_addr_spec(addr_tokens, join_phrase(&tokens[..open], charset_aliases))
+        },
+        None => {
+            let comment = tokens.iter().rev().find_map(|t| match t {
+                Token::Comment(c) if !c.trim().is_empty() => Some(decode_words(c.trim(), charset_aliases)),
+                _ => None,
+            });
+            parse_addr_spec(tokens, comment)
+        },
+    }
+}
+
+/// Parses the addresses in a raw (not decoded) address header field value,
+/// e.g., the value of a From or To header field. MIME encoded-words in
+/// display names are decoded, using the specified charset aliases. Entries
+/// that aren't valid addresses are ignored. Group names are ignored, but the
+/// addresses of group members are included.
+pub fn parse_addresses(value: &str, charset_aliases: &HashMap<String, String>) -> Vec<Address> {
+    let tokens = tokenize(value);
+    let mut addresses = Vec::new();
+    let mut start = 0;
+    let mut in_angle = false;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Special('<') => in_angle = true,
+            Token::Special('>') => in_angle = false,
+            // Commas and colons within angle brackets are part of obsolete
+            // source routes.
+            Token::Special(',') | Token::Special(';') if !in_angle => {
+                addresses.extend(parse_mailbox(&tokens[start..i], charset_aliases));
+                start = i + 1;
+            },
+            // The tokens before a colon are the name of a group.
+            Token::Special(':') if !in_angle => start = i + 1,
+            _ => {},
+        }
+    }
+    addresses.extend(parse_mailbox(&tokens[start..], charset_aliases));
+
+    addresses
+}
+
+/// Returns the posting address of a mailing list from the value of a
+/// List-Post header field, e.g., `<mailto:list@example.com>`. The scheme
+/// is matched case-insensitively.
+fn list_post_address(value: &str) -> Option<&str> {
+    let start = find_ignore_ascii_case(value, "mailto:")? + "mailto:".len();
+    let rest = &value[start..];
+    let end = rest.find(['>', '?', ',']).unwrap_or(rest.len());
+    Some(rest[..end].trim())
+}
+
+lazy_static! {
+    /// Matches runs of characters that may form an address in free text.
+    /// The candidates are validated by `body_address`.
+    static ref BODY_ADDRESS_REGEX: Regex =
+        Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+").unwrap();
+}
+
+#[cfg(feature = "psl")]
+lazy_static! {
+    /// The system copy of the public suffix list, if available.
+    static ref PUBLIC_SUFFIX_LIST: Option<publicsuffix::List> =
+        std::fs::read_to_string("/usr/share/publicsuffix/public_suffix_list.dat")
+            .ok()
+            .and_then(|list| list.parse().ok());
+}
+
+/// Returns the address in a candidate match of `BODY_ADDRESS_REGEX`, if the
+/// candidate is a plausible address, i.e., it has a dot-atom local part and
+/// a domain with at least two labels and an alphabetic top-level label.
+/// Punctuation surrounding the address in the text is ignored.
+fn body_address(candidate: &str) -> Option<&str> {
+    let (local, domain) = candidate.split_once('@')?;
+    let domain_len = domain.len();
+    let local = local.trim_start_matches('.');
+    let domain = domain.trim_end_matches(['.', '-']);
+
+    let valid_local = !local.is_empty() && !local.ends_with('.') && !local.contains("..");
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_labels = labels.iter().all(|label| {
+        !label.is_empty() && !label.starts_with('-') && !label.ends_with('-')
+    });
+    let tld = labels.last().unwrap();
+    let valid_tld = tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic());
+
+    if !valid_local || labels.len() < 2 || !valid_labels || !valid_tld {
+        return None;
+    }
+
+    let at = candidate.len() - domain_len - 1;
+    Some(&candidate[at - local.len()..at + 1 + domain.len()])
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the address that replies to the email should be sent to.
+    ///
+    /// This is the first Reply-To address, if present, otherwise the first
+    /// From address. Addresses equal to the posting address of a mailing
+    /// list, as specified by the List-Post header field, are skipped, to
+    /// avoid replying to the whole list. If the email has a null
+    /// Return-Path (`<>`), e.g., because it's a bounce, `None` is returned,
+    /// since automatic replies must never be sent to the null sender.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if let Some(address) = email.reply_to_address() {
+    ///     println!("Replying to {}", address.addr_spec());
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn reply_to_address(&self) -> Option<Address> {
+        if let Some(return_path) = self.header_field("Return-Path") {
+            if return_path.chars().all(|c| c == '<' || c == '>' || c.is_whitespace()) {
+                return None;
+            }
+        }
+
+        let list_address = self.header_field("List-Post").and_then(list_post_address);
+
+        ["Reply-To", "From"]
+            .iter()
+            .filter_map(|name| self.raw_header_field(name))
+            .filter_map(|value| {
+                parse_addresses(&value, &self.normalization_settings.charset_aliases).into_iter().next()
+            })
+            .find(|address| !list_address.is_some_and(|list| address.has_addr_spec(list)))
+    }
+
+    /// Returns the Sender and From addresses of the email, in that order,
+    /// if the email was sent on behalf of the author by a different
+    /// mailbox, e.g., by an assistant or a mailing service.
+    ///
+    /// Returns `None` if either field is missing, or if the bare Sender
+    /// address is equal to the bare (first) From address. Note that a
+    /// differing Sender may also be a sign of spoofing.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if let Some((sender, from)) = email.on_behalf_of() {
+    ///     eprintln!("{} sent on behalf of {}", sender, from);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn on_behalf_of(&self) -> Option<(Address, Address)> {
+        let first_address = |name| self.header_field_addresses(name).into_iter().next();
+        let sender = first_address("Sender")?;
+        let from = first_address("From")?;
+
+        if from.has_addr_spec(&sender.addr_spec()) {
+            None
+        } else {
+            Some((sender, from))
+        }
+    }
+
+    /// Returns the addresses in the first occurrence of an address header
+    /// field, e.g., From, To or Cc. The list is empty if the header field
+    /// isn't present.
+    ///
+    /// The addresses are parsed from the raw field value, so that commas
+    /// and angle brackets in MIME encoded-words don't split or create
+    /// addresses, and the display names are then decoded, using the
+    /// [charset aliases](struct.NormalizationOptions.html#structfield.charset_aliases)
+    /// the email was created with. Quoted local parts and obsolete source
+    /// routes are supported. The names of groups, e.g.,
+    /// `undisclosed-recipients:;`, are ignored, but the addresses of group
+    /// members are included. Entries that aren't valid addresses are
+    /// ignored.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// for address in email.header_field_addresses("To") {
+    ///     eprintln!("To: {} at {}", address.local, address.domain);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn header_field_addresses(&self, name: &str) -> Vec<Address> {
+        self.raw_header_field(name)
+            .map(|value| parse_addresses(&value, &self.normalization_settings.charset_aliases))
+            .unwrap_or_default()
+    }
+
+    /// Returns the bare addresses found in the decoded text of the email
+    /// body, e.g., `john@example.com`, in the order they first appear.
+    /// Addresses that differ only in case are considered duplicates and are
+    /// returned only once.
+    ///
+    /// Since addresses in the body aren't in structured fields, they are
+    /// found with a conservative pattern: only dot-atom local parts and
+    /// domains with an alphabetic top-level label (e.g., `.com`) are
+    /// recognized, so quoted local parts and address literals are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// for address in email.body_addresses() {
+    ///     eprintln!("Contact: {}", address);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn body_addresses(&self) -> Vec<String> {
+        let body_text = self.body_text();
+        let mut addresses: Vec<String> = Vec::new();
+
+        for candidate in BODY_ADDRESS_REGEX.find_iter(&body_text) {
+            // Don't pick out the ASCII part of a word with other characters.
+            let is_separator = |c: Option<char>| {
+                c.is_none_or(|c| c.is_whitespace() || c.is_ascii_punctuation())
+            };
+            let before = body_text[..candidate.start()].chars().next_back();
+            let after = body_text[candidate.end()..].chars().next();
+            if !is_separator(before) || !is_separator(after) {
+                continue;
+            }
+
+            if let Some(address) = body_address(candidate.as_str()) {
+                if !addresses.iter().any(|a| a.eq_ignore_ascii_case(address)) {
+                    addresses.push(address.to_string());
+                }
+            }
+        }
+
+        addresses
+    }
+
+    /// Returns the lowercase domain of the first address in the From header
+    /// field, without any trailing dot, e.g., `mail.example.co.uk` for
+    /// `John <john@Mail.Example.co.uk>`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.from_domain().as_deref() == Some("example.com") {
+    ///     email.deliver_to_maildir("/my/maildir/example")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_domain(&self) -> Option<String> {
+        let address = self.header_field_addresses("From").into_iter().next()?;
+        let domain = address.domain.trim_end_matches('.').to_lowercase();
+        if domain.is_empty() { None } else { Some(domain) }
+    }
+
+    /// Returns the registrable domain (i.e., the public suffix plus one
+    /// label, also known as eTLD+1) of the [From domain](#method.from_domain),
+    /// e.g., `example.co.uk` for `mail.example.co.uk`. This identifies the
+    /// organization that sent the email, regardless of the host it was
+    /// sent from.
+    ///
+    /// The public suffix list is read from the system copy at
+    /// `/usr/share/publicsuffix/public_suffix_list.dat` the first time it's
+    /// needed. Returns `None` if the list isn't available, if the From
+    /// domain is an address literal, or if the From domain is itself a
+    /// public suffix.
+    ///
+    /// This method is available only if the `psl` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if let Some(organization) = email.from_registrable_domain() {
+    ///     email.deliver_to_maildir(format!("/my/maildir/orgs/{}", organization))?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "psl")]
+    pub fn from_registrable_domain(&self) -> Option<String> {
+        use publicsuffix::Psl;
+
+        let domain = self.from_domain()?;
+        if domain.starts_with('[') {
+            return None;
+        }
+
+        let list = PUBLIC_SUFFIX_LIST.as_ref()?;
+        let registrable = list.domain(domain.as_bytes())?;
+        String::from_utf8(registrable.as_bytes().to_vec()).ok()
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Information about the attachments of emails.
+
+use crate::{BorrowedEmail, EmailPart};
+use crate::decode::qp_decode_into_buf;
+use crate::params::parse_parameters;
+
+/// An attachment of an email, as returned by
+/// [Email::attachments](type.Email.html#method.attachments).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The filename of the attachment, from the `filename` parameter of the
+    /// Content-Disposition header field, or the `name` parameter of the
+    /// Content-Type header field, if any.
+    pub filename: Option<String>,
+    /// The lowercase content type of the attachment, e.g., `image/png`.
+    pub content_type: String,
+    /// The data of the attachment, without its content transfer encoding.
+    pub data: Vec<u8>,
+}
+
+/// Returns the data without the line terminator at its end. The final
+/// line terminator of a part belongs to the boundary line that follows it.
+pub(crate) fn trim_final_newline(data: &[u8]) -> &[u8] {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    data.strip_suffix(b"\r").unwrap_or(data)
+}
+
+/// Returns the length the data would have after being decoded with the
+/// specified content transfer encoding.
+///
+/// The length of base64 data is calculated from the number of base64
+/// characters, without decoding the data. Data that isn't encoded, or
+/// can't be decoded, is considered to be its own decoded form.
+pub(crate) fn decoded_len(data: &[u8], encoding: Option<&str>) -> usize {
+    match encoding {
+        Some("base64") => {
+            let chars = data.iter()
+                .filter(|&&b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+                .count();
+            chars * 3 / 4
+        },
+        Some("quoted-printable") => {
+            let mut decoded = Vec::new();
+            match qp_decode_into_buf(trim_final_newline(data), &mut decoded) {
+                Ok(()) => decoded.len(),
+                Err(_) => trim_final_newline(data).len(),
+            }
+        },
+        _ => trim_final_newline(data).len(),
+    }
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the Content-Disposition header field of a part, if the part
+    /// is an attachment, i.e., has non-text content or an `attachment`
+    /// disposition. Returns `Some(None)` for attachments without a
+    /// disposition, and `None` for parts that aren't attachments.
+    fn attachment_disposition(&self, part: &EmailPart) -> Option<Option<String>> {
+        let disposition = self.part_header_field(part.info(), "Content-Disposition");
+        let is_attachment = disposition.as_ref().is_some_and(|d| {
+            d.split(';').next().unwrap().trim().eq_ignore_ascii_case("attachment")
+        });
+        if part.is_text() && !is_attachment {
+            return None;
+        }
+        Some(disposition)
+    }
+
+    /// Returns whether the email has an attachment, as returned by
+    /// [attachments](#method.attachments), whose decoded size is larger
+    /// than the specified number of bytes. The decoded size is the size of
+    /// the attachment data after removing its content transfer encoding
+    /// (e.g., base64), and is calculated without decoding non-text data.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.has_attachment_larger_than(10 * 1024 * 1024) {
+    ///     email.deliver_to_maildir("/my/maildir/quarantine")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn has_attachment_larger_than(&self, bytes: usize) -> bool {
+        self.parts()
+            .iter()
+            .filter(|part| self.attachment_disposition(part).is_some())
+            .any(|part| part.decoded_len() > bytes)
+    }
+
+    /// Returns the attachments of the email, in the order they appear in
+    /// the email. Attachments are the parts with non-text content, and the
+    /// text parts with an `attachment` Content-Disposition.
+    ///
+    /// The data of the attachments is decoded from its content transfer
+    /// encoding (e.g., base64). Non-text data is never converted from its
+    /// charset. Filenames may be split or encoded as specified by RFC 2231
+    /// or RFC 2047.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// for (i, attachment) in email.attachments().into_iter().enumerate() {
+    ///     let filename = attachment.filename.unwrap_or_else(|| format!("attachment-{}", i));
+    ///     std::fs::write(format!("/my/archive/{}", filename), &attachment.data)?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn attachments(&self) -> Vec<Attachment> {
+        self.parts()
+            .into_iter()
+            .filter_map(|part| {
+                let disposition = self.attachment_disposition(&part)?;
+
+                let filename = disposition
+                    .and_then(|d| parse_parameters(&d).remove("filename"))
+                    .or_else(|| {
+                        self.part_header_field(part.info(), "Content-Type")
+                            .and_then(|ct| parse_parameters(&ct).remove("name"))
+                    });
+
+                Some(
+                    Attachment{
+                        filename,
+                        content_type: part.content_type().unwrap_or("text/plain").to_string(),
+                        data: part.decoded_bytes().into_owned(),
+                    }
+                )
+            })
+            .collect()
+    }
+}
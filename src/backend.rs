@@ -0,0 +1,380 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Pluggable storage backends for maildir deliveries.
+
+use std::io;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "test-util")]
+use std::sync::Mutex;
+
+use crate::deliver::flags_info;
+#[cfg(feature = "test-util")]
+use crate::deliver::{identity_filename, named_filename};
+use crate::{BorrowedEmail, Compression, MaildirFlag, MdaError, Result};
+
+/// Returns the error for a delivery operation a backend doesn't support.
+fn unsupported(operation: &str) -> MdaError {
+    let msg = format!("The maildir backend doesn't support {}", operation);
+    io::Error::new(io::ErrorKind::Unsupported, msg).into()
+}
+
+/// A backend that stores emails delivered to maildirs.
+///
+/// Emails deliver to maildirs on the filesystem by default. A different
+/// backend can be set with
+/// [Email::set_maildir_backend](type.Email.html#method.set_maildir_backend),
+/// e.g., to test routing logic without touching the filesystem. All the
+/// maildir delivery methods of the email deliver through the backend.
+///
+/// Only [deliver](#tymethod.deliver) must be implemented. The other
+/// operations fail with an `std::io::Error` of kind `Unsupported` by
+/// default, so that a backend never silently falls back to the filesystem.
+pub trait MaildirBackend: Send + Sync {
+    /// Delivers an email to the maildir at the specified path, returning
+    /// the path of the delivered email file. Used by
+    /// [Email::deliver_to_maildir](type.Email.html#method.deliver_to_maildir).
+    fn deliver(&self, email: &BorrowedEmail, maildir: &Path) -> Result<PathBuf>;
+
+    /// Delivers an email marked with the specified (non-empty) flags. Used
+    /// by [Email::deliver_to_maildir_with_flags](type.Email.html#method.deliver_to_maildir_with_flags).
+    fn deliver_with_flags(
+        &self,
+        _email: &BorrowedEmail,
+        _maildir: &Path,
+        _flags: &[MaildirFlag]
+    ) -> Result<PathBuf> {
+        Err(unsupported("flagged deliveries"))
+    }
+
+    /// Delivers an email tagged with the specified Dovecot keywords. Used
+    /// by [Email::deliver_to_maildir_with_keywords](type.Email.html#method.deliver_to_maildir_with_keywords).
+    fn deliver_with_keywords(
+        &self,
+        _email: &BorrowedEmail,
+        _maildir: &Path,
+        _keywords: &[&str]
+    ) -> Result<PathBuf> {
+        Err(unsupported("deliveries with keywords"))
+    }
+
+    /// Delivers an email using the specified base as its filename. Used by
+    /// [Email::deliver_to_maildir_named](type.Email.html#method.deliver_to_maildir_named).
+    fn deliver_named(&self, _email: &BorrowedEmail, _maildir: &Path, _base: &str) -> Result<PathBuf> {
+        Err(unsupported("named deliveries"))
+    }
+
+    /// Delivers an email at most once for the specified identity, returning
+    /// `None` if it was already delivered. Used by
+    /// [Email::deliver_to_maildir_once](type.Email.html#method.deliver_to_maildir_once).
+    fn deliver_once(
+        &self,
+        _email: &BorrowedEmail,
+        _maildir: &Path,
+        _identity: &str
+    ) -> Result<Option<PathBuf>> {
+        Err(unsupported("deliveries at most once"))
+    }
+
+    /// Delivers an email compressed with the specified algorithm. Used by
+    /// [Email::deliver_to_maildir_compressed](type.Email.html#method.deliver_to_maildir_compressed).
+    fn deliver_compressed(
+        &self,
+        _email: &BorrowedEmail,
+        _maildir: &Path,
+        _compression: Compression
+    ) -> Result<PathBuf> {
+        Err(unsupported("compressed deliveries"))
+    }
+
+    /// Stores the metadata of an email, which was delivered to the
+    /// specified path, in a sidecar file, returning the path of the sidecar
+    /// file. Used by
+    /// [Email::deliver_to_maildir_with_sidecar](type.Email.html#method.deliver_to_maildir_with_sidecar).
+    ///
+    /// This method is available only if the `serde` feature is enabled.
+    #[cfg(feature = "serde")]
+    fn write_sidecar(
+        &self,
+        _email: &BorrowedEmail,
+        _maildir: &Path,
+        _email_path: &Path,
+        _metadata: &[u8]
+    ) -> Result<PathBuf> {
+        Err(unsupported("sidecar files"))
+    }
+}
+
+/// The default backend, which delivers emails to maildirs on the
+/// filesystem, with the delivery settings of each email.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FilesystemMaildirBackend;
+
+impl MaildirBackend for FilesystemMaildirBackend {
+    fn deliver(&self, email: &BorrowedEmail, maildir: &Path) -> Result<PathBuf> {
+        email.deliver_to_maildir_fs(maildir).map(|result| result.path)
+    }
+
+    fn deliver_with_flags(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        flags: &[MaildirFlag]
+    ) -> Result<PathBuf> {
+        let maildir = email.open_maildir(maildir)?;
+        let email_path = maildir.deliver_with_info(&email.raw_data, &flags_info(flags), email.delivery_durability)?;
+        email.set_deliver_path_if_unset(&email_path);
+        Ok(email_path)
+    }
+
+    fn deliver_with_keywords(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        keywords: &[&str]
+    ) -> Result<PathBuf> {
+        let maildir = email.open_maildir(maildir)?;
+        let letters = maildir.register_dovecot_keywords(keywords)?;
+        let email_path = maildir.deliver_with_info(&email.raw_data, &letters, email.delivery_durability)?;
+        email.set_deliver_path_if_unset(&email_path);
+        Ok(email_path)
+    }
+
+    fn deliver_named(&self, email: &BorrowedEmail, maildir: &Path, base: &str) -> Result<PathBuf> {
+        let maildir = email.open_maildir(maildir)?;
+        let email_path = maildir.deliver_named(&email.raw_data, base, email.delivery_durability)?;
+        email.set_deliver_path_if_unset(&email_path);
+        Ok(email_path)
+    }
+
+    fn deliver_once(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        identity: &str
+    ) -> Result<Option<PathBuf>> {
+        let maildir = email.open_maildir(maildir)?;
+        maildir.deliver_once(&email.raw_data, identity, email.delivery_durability)
+    }
+
+    fn deliver_compressed(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        compression: Compression
+    ) -> Result<PathBuf> {
+        let maildir = email.open_maildir(maildir)?;
+        maildir.deliver_compressed(&email.raw_data, compression, email.delivery_durability)
+    }
+
+    #[cfg(feature = "serde")]
+    fn write_sidecar(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        email_path: &Path,
+        metadata: &[u8]
+    ) -> Result<PathBuf> {
+        let maildir = email.open_maildir(maildir)?;
+        maildir.write_sidecar(email_path, metadata, email.delivery_durability)
+    }
+}
+
+/// An email delivered to an [InMemoryMaildirBackend](struct.InMemoryMaildirBackend.html).
+///
+/// This struct is available only if the `test-util` feature is enabled.
+#[cfg(feature = "test-util")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InMemoryDelivery {
+    /// The path of the maildir the email was delivered to.
+    pub maildir: PathBuf,
+    /// The path returned for the delivered email, which reflects the kind
+    /// of delivery like a filesystem delivery would, e.g., flagged emails
+    /// are in the cur/ directory, with the flags in the `:2,` info section
+    /// of their filename.
+    pub path: PathBuf,
+    /// The raw data of the delivered email. Compressed deliveries record
+    /// the uncompressed data.
+    pub data: Vec<u8>,
+    /// The Dovecot keywords the email was delivered with.
+    pub keywords: Vec<String>,
+    /// The data of the sidecar file of the email, if one was written.
+    pub sidecar: Option<Vec<u8>>,
+}
+
+/// A backend that records delivered emails in memory, instead of storing
+/// them, so that tests can assert which emails were delivered where.
+///
+/// The returned path of each delivered email is in the target maildir, but
+/// no file is created. Emails are numbered in delivery order, e.g., the
+/// first email delivered with
+/// [deliver_to_maildir](type.Email.html#method.deliver_to_maildir) is at
+/// `new/1` in its maildir, unless the delivery method determines the
+/// filename, like
+/// [deliver_to_maildir_named](type.Email.html#method.deliver_to_maildir_named)
+/// does.
+///
+/// This struct is available only if the `test-util` feature is enabled.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+/// use mda::{Email, InMemoryMaildirBackend};
+/// let backend = Arc::new(InMemoryMaildirBackend::new());
+/// let mut email = Email::from_vec(b"Subject: [URGENT] Hi\n\nBody\n".to_vec())?;
+/// email.set_maildir_backend(backend.clone());
+///
+/// email
+///     .when(|e| e.header_field("Subject").unwrap_or("").contains("[URGENT]"))
+///     .deliver("/my/maildir/urgent")?
+///     .otherwise("/my/maildir/inbox")?;
+///
+/// assert_eq!(backend.delivered_to("/my/maildir/urgent").len(), 1);
+/// assert!(backend.delivered_to("/my/maildir/inbox").is_empty());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[cfg(feature = "test-util")]
+#[derive(Debug, Default)]
+pub struct InMemoryMaildirBackend {
+    deliveries: Mutex<Vec<InMemoryDelivery>>,
+}
+
+#[cfg(feature = "test-util")]
+impl InMemoryMaildirBackend {
+    /// Creates a backend without any deliveries.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns all the recorded deliveries, in the order they were made.
+    pub fn deliveries(&self) -> Vec<InMemoryDelivery> {
+        self.deliveries.lock().unwrap().clone()
+    }
+
+    /// Returns the data of the emails delivered to the specified maildir,
+    /// in the order they were delivered.
+    pub fn delivered_to(&self, maildir: impl AsRef<Path>) -> Vec<Vec<u8>> {
+        self.deliveries.lock().unwrap().iter()
+            .filter(|delivery| delivery.maildir == maildir.as_ref())
+            .map(|delivery| delivery.data.clone())
+            .collect()
+    }
+
+    /// Returns whether an email with the specified filename has been
+    /// delivered to the new/ directory of the specified maildir.
+    fn contains_new_email_named(deliveries: &[InMemoryDelivery], maildir: &Path, filename: &str) -> bool {
+        let path = maildir.join("new").join(filename);
+        deliveries.iter().any(|delivery| delivery.path == path)
+    }
+
+    /// Records a delivery to a subdirectory of a maildir, with a filename
+    /// built from the number of the delivery.
+    fn record(
+        deliveries: &mut Vec<InMemoryDelivery>,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        subdir: &str,
+        filename: impl FnOnce(usize) -> String,
+        keywords: &[&str]
+    ) -> PathBuf {
+        let path = maildir.join(subdir).join(filename(deliveries.len() + 1));
+        deliveries.push(
+            InMemoryDelivery{
+                maildir: maildir.to_path_buf(),
+                path: path.clone(),
+                data: email.raw_data().to_vec(),
+                keywords: keywords.iter().map(|keyword| keyword.to_string()).collect(),
+                sidecar: None,
+            }
+        );
+        path
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl MaildirBackend for InMemoryMaildirBackend {
+    fn deliver(&self, email: &BorrowedEmail, maildir: &Path) -> Result<PathBuf> {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        Ok(Self::record(&mut deliveries, email, maildir, "new", |n| n.to_string(), &[]))
+    }
+
+    fn deliver_with_flags(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        flags: &[MaildirFlag]
+    ) -> Result<PathBuf> {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        let info = flags_info(flags);
+        Ok(Self::record(&mut deliveries, email, maildir, "cur", |n| format!("{}:2,{}", n, info), &[]))
+    }
+
+    fn deliver_with_keywords(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        keywords: &[&str]
+    ) -> Result<PathBuf> {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        Ok(Self::record(&mut deliveries, email, maildir, "cur", |n| format!("{}:2,", n), keywords))
+    }
+
+    fn deliver_named(&self, email: &BorrowedEmail, maildir: &Path, base: &str) -> Result<PathBuf> {
+        let filename = named_filename(base)?;
+        let mut deliveries = self.deliveries.lock().unwrap();
+        if Self::contains_new_email_named(&deliveries, maildir, &filename) {
+            return Err(format!("An email named {:?} already exists in the maildir", filename).into());
+        }
+        Ok(Self::record(&mut deliveries, email, maildir, "new", |_| filename, &[]))
+    }
+
+    fn deliver_once(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        identity: &str
+    ) -> Result<Option<PathBuf>> {
+        let filename = identity_filename(identity);
+        let mut deliveries = self.deliveries.lock().unwrap();
+        if Self::contains_new_email_named(&deliveries, maildir, &filename) {
+            return Ok(None);
+        }
+        Ok(Some(Self::record(&mut deliveries, email, maildir, "new", |_| filename, &[])))
+    }
+
+    fn deliver_compressed(
+        &self,
+        email: &BorrowedEmail,
+        maildir: &Path,
+        compression: Compression
+    ) -> Result<PathBuf> {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        let suffix = compression.suffix();
+        Ok(Self::record(&mut deliveries, email, maildir, "new", |n| format!("{}{}", n, suffix), &[]))
+    }
+
+    #[cfg(feature = "serde")]
+    fn write_sidecar(
+        &self,
+        _email: &BorrowedEmail,
+        maildir: &Path,
+        email_path: &Path,
+        metadata: &[u8]
+    ) -> Result<PathBuf> {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        let delivery = deliveries.iter_mut()
+            .find(|delivery| delivery.maildir == maildir && delivery.path == email_path)
+            .ok_or("Invalid email path")?;
+        delivery.sidecar = Some(metadata.to_vec());
+
+        let mut sidecar = email_path.as_os_str().to_owned();
+        sidecar.push(".meta");
+        Ok(PathBuf::from(sidecar))
+    }
+}
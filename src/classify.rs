@@ -0,0 +1,226 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Classification of emails based on header fields added by other tools.
+
+use crate::BorrowedEmail;
+
+/// Parses the value of a `score=` (or the older `hits=`) token in an
+/// X-Spam-Status field value.
+fn parse_spam_status_score(value: &str) -> Option<f32> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .find_map(|token| token.strip_prefix("score=").or_else(|| token.strip_prefix("hits=")))
+        .and_then(|score| score.parse().ok())
+}
+
+/// Parses the leading number in an X-Spam-Score field value, ignoring any
+/// following data (e.g., `7.3 (+++++++)` or `7.3 / 15.0`).
+fn parse_spam_score(value: &str) -> Option<f32> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+/// The priority of an email, as declared by its sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// X-Priority 4 or 5, Importance `low`, or Priority `non-urgent`.
+    Low,
+    /// X-Priority 3, Importance `normal`, or Priority `normal`.
+    Normal,
+    /// X-Priority 1 or 2, Importance `high`, or Priority `urgent`.
+    High,
+}
+
+/// Parses an X-Priority field value, e.g., `1 (Highest)`, using its leading
+/// digit: 1 and 2 are high, 3 is normal, and 4 and 5 are low.
+fn parse_x_priority(value: &str) -> Option<Priority> {
+    match value.trim_start().chars().next()? {
+        '1' | '2' => Some(Priority::High),
+        '3' => Some(Priority::Normal),
+        '4' | '5' => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// Parses an Importance field value, i.e., `high`, `normal` or `low`.
+fn parse_importance(value: &str) -> Option<Priority> {
+    match value.trim().to_lowercase().as_str() {
+        "high" => Some(Priority::High),
+        "normal" => Some(Priority::Normal),
+        "low" => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// Parses a Priority field value (RFC 2156), i.e., `urgent`, `normal` or
+/// `non-urgent`.
+fn parse_priority(value: &str) -> Option<Priority> {
+    match value.trim().to_lowercase().as_str() {
+        "urgent" => Some(Priority::High),
+        "normal" => Some(Priority::Normal),
+        "non-urgent" => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+/// Lowercase subject prefixes that denote replies, in several languages.
+const REPLY_PREFIXES: &[&str] = &["re", "aw", "sv", "antw", "vs", "odp", "res", "回复", "答复"];
+
+/// Lowercase subject prefixes that denote forwards, in several languages.
+const FORWARD_PREFIXES: &[&str] = &["fwd", "fw", "wg", "tr", "rv", "vb", "enc", "doorst", "转发"];
+
+/// Returns the lowercase reply or forward prefix of a subject, e.g., `re`
+/// for `Re: Hello` or `Re[2]: Hello`, if any.
+fn subject_prefix(subject: &str) -> Option<String> {
+    let colon = subject.find([':', '：'])?;
+    let prefix = subject[..colon].trim();
+    // Strip any reply counter, e.g., "Re[2]" or "Re^2".
+    let prefix = prefix.split(['[', '^', '(']).next().unwrap().trim_end();
+    Some(prefix.to_lowercase())
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the spam score assigned to the email by an upstream spam
+    /// filter (e.g., SpamAssassin), if any.
+    ///
+    /// The score is parsed from the `score=` token of the X-Spam-Status
+    /// header field, falling back to the numeric value of the X-Spam-Score
+    /// header field.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.spam_score().is_some_and(|score| score > 5.0) {
+    ///     email.deliver_to_maildir("/my/spam/path")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn spam_score(&self) -> Option<f32> {
+        self.header_field("X-Spam-Status")
+            .and_then(parse_spam_status_score)
+            .or_else(|| self.header_field("X-Spam-Score").and_then(parse_spam_score))
+    }
+
+    /// Returns whether the email is a bounce, i.e., a delivery status
+    /// notification or other report about a failed delivery.
+    ///
+    /// An email is considered a bounce if it has a null Return-Path (`<>`),
+    /// a `multipart/report` content type with a `delivery-status` report
+    /// type, or a From header field with a `MAILER-DAEMON` address.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.is_bounce() {
+    ///     email.deliver_to_maildir("/my/maildir/bounces")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_bounce(&self) -> bool {
+        let null_return_path = self.header_field("Return-Path").is_some_and(|rp| {
+            rp.chars().filter(|c| !c.is_whitespace()).eq("<>".chars())
+        });
+
+        let delivery_status_report = self.header_field("Content-Type").is_some_and(|ct| {
+            ct.trim().to_lowercase().starts_with("multipart/report") &&
+            self.content_type_params()
+                .get("report-type")
+                .is_some_and(|rt| rt.eq_ignore_ascii_case("delivery-status"))
+        });
+
+        let from_mailer_daemon = self.header_field("From").is_some_and(|from| {
+            from.to_lowercase().contains("mailer-daemon")
+        });
+
+        null_return_path || delivery_status_report || from_mailer_daemon
+    }
+
+    /// Returns the priority of the email, as declared in its header.
+    ///
+    /// The priority is taken from the first of the following header fields
+    /// that is present and has a recognized value:
+    ///
+    /// 1. X-Priority, where 1 and 2 are high, 3 is normal, and 4 and 5 are
+    ///    low priority, ignoring any trailing comment, e.g., `1 (Highest)`.
+    /// 2. Importance, with a value of `high`, `normal` or `low`.
+    /// 3. Priority, with a value of `urgent`, `normal` or `non-urgent`.
+    ///
+    /// Values are compared case-insensitively. If none of these fields
+    /// declares a priority, the priority is normal.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::{Email, Priority};
+    /// let email = Email::from_stdin()?;
+    /// if email.priority() == Priority::High {
+    ///     email.deliver_to_maildir("/my/maildir/urgent")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn priority(&self) -> Priority {
+        self.header_field("X-Priority").and_then(parse_x_priority)
+            .or_else(|| self.header_field("Importance").and_then(parse_importance))
+            .or_else(|| self.header_field("Priority").and_then(parse_priority))
+            .unwrap_or(Priority::Normal)
+    }
+
+    /// Returns the lowercase reply or forward prefix of the subject, after
+    /// any leading bracketed tags, if any.
+    fn subject_prefix(&self) -> Option<String> {
+        self.subject_without_tags().and_then(subject_prefix)
+    }
+
+    /// Returns whether the email is a reply to another email.
+    ///
+    /// An email is considered a reply if it has an In-Reply-To or
+    /// References header field, or if its subject (after any leading
+    /// bracketed tags) starts with a reply prefix, e.g., `Re:`, `Aw:` or
+    /// `Sv:`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.is_reply() {
+    ///     email.deliver_to_maildir("/my/maildir/threads")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_reply(&self) -> bool {
+        self.header_field("In-Reply-To").is_some() ||
+            self.header_field("References").is_some() ||
+            self.subject_prefix().is_some_and(|prefix| REPLY_PREFIXES.contains(&prefix.as_str()))
+    }
+
+    /// Returns whether the email forwards another email.
+    ///
+    /// An email is considered a forward if its subject (after any leading
+    /// bracketed tags) starts with a forward prefix, e.g., `Fwd:`, `Fw:` or
+    /// `Wg:`, or if it contains a `message/rfc822` part.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.is_forward() {
+    ///     email.deliver_to_maildir("/my/maildir/forwards")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_forward(&self) -> bool {
+        self.subject_prefix().is_some_and(|prefix| FORWARD_PREFIXES.contains(&prefix.as_str())) ||
+            self.parts.iter().any(|part| part.content_type.as_deref() == Some("message/rfc822"))
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Comparison of emails.
+
+use std::collections::BTreeMap;
+
+use crate::BorrowedEmail;
+
+impl BorrowedEmail<'_> {
+    /// Returns the header fields of the email as a map from lowercase field
+    /// names to sorted field values, excluding the fields with the specified
+    /// names.
+    fn comparable_fields(&self, ignore_headers: &[String]) -> BTreeMap<&str, Vec<&str>> {
+        self.fields
+            .iter()
+            .filter(|(name, _)| !ignore_headers.contains(name))
+            .map(|(name, values)| {
+                let mut values: Vec<&str> = values.iter().map(|v| v.trim()).collect();
+                values.sort_unstable();
+                (name.as_str(), values)
+            })
+            .collect()
+    }
+
+    /// Returns whether this email is semantically equal to another email,
+    /// i.e., whether they have the same normalized header fields and
+    /// normalized body data. The order of header fields and header fields
+    /// with the specified (case-insensitive) names are ignored.
+    ///
+    /// This is useful for detecting duplicates of an email that arrived via
+    /// different paths, and thus have different trace header fields.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let other = Email::from_vec(std::fs::read("/my/maildir/cur/1546300800.1234_0.host:2,")?)?;
+    /// if email.semantically_equal(&other, &["Received", "Delivered-To", "Return-Path"]) {
+    ///     return Ok(());
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn semantically_equal(&self, other: &BorrowedEmail, ignore_headers: &[&str]) -> bool {
+        let ignore_headers: Vec<String> =
+            ignore_headers.iter().map(|name| name.to_lowercase()).collect();
+
+        self.body() == other.body() &&
+            self.comparable_fields(&ignore_headers) == other.comparable_fields(&ignore_headers)
+    }
+}
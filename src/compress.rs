@@ -0,0 +1,64 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Compression of email data for delivery.
+
+use std::borrow::Cow;
+#[cfg(feature = "gzip")]
+use std::io::Write;
+
+use crate::Result;
+
+/// The compression algorithm to use when delivering emails with
+/// [Email::deliver_to_maildir_compressed](type.Email.html#method.deliver_to_maildir_compressed).
+///
+/// Each algorithm is available only if the feature with the same
+/// (lowercase) name is enabled.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Don't compress the email data.
+    None,
+    /// Compress with gzip, as supported, e.g., by the Dovecot `zlib`
+    /// plugin. Emails are stored with the `.gz` filename suffix.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Compress with Zstandard. Emails are stored with the `.zst` filename
+    /// suffix.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the filename suffix of data compressed with this algorithm.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => ".gz",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Compresses data with this algorithm.
+    pub(crate) fn compress<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        match self {
+            Compression::None => Ok(Cow::Borrowed(data)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(Cow::Owned(encoder.finish()?))
+            },
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Cow::Owned(zstd::encode_all(data, 0)?)),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Parsing and conversion of email dates.
+
+use crate::BorrowedEmail;
+
+/// The abbreviated English month names.
+pub const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun",
+    "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A date and time with a timezone offset, as found in email header fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u32,
+    /// The month, in the range 1-12.
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// The timezone offset from UTC in minutes.
+    pub offset: i32,
+}
+
+impl DateTime {
+    /// Converts a Unix timestamp to a UTC date and time.
+    pub fn from_unix_time(secs: u64) -> Self {
+        let days = (secs / 86400) as i64;
+        let secs_of_day = (secs % 86400) as u32;
+
+        // Convert days since the epoch to a civil date, see
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as u32;
+
+        DateTime{
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day / 60 % 60,
+            second: secs_of_day % 60,
+            offset: 0,
+        }
+    }
+
+    /// Converts the date and time to a Unix timestamp, taking the timezone
+    /// offset into account.
+    pub fn to_unix_time(self) -> i64 {
+        // Convert the civil date to days since the epoch, see
+        // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = self.year as i64 - if self.month <= 2 { 1 } else { 0 };
+        let era = if year >= 0 { year } else { year - 399 } / 400;
+        let yoe = year - era * 400;
+        let month = self.month as i64;
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - 719468;
+
+        days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64 -
+            self.offset as i64 * 60
+    }
+}
+
+/// Parses a timezone, either numeric (e.g., `+0200`) or one of the obsolete
+/// names allowed by RFC 5322, into an offset from UTC in minutes. Unknown
+/// zone names are treated as UTC.
+fn parse_zone(zone: &str) -> Option<i32> {
+    if let Some(digits) = zone.strip_prefix(['+', '-']) {
+        if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let hours: i32 = digits[..2].parse().ok()?;
+        let minutes: i32 = digits[2..].parse().ok()?;
+        let offset = hours * 60 + minutes;
+        return Some(if zone.starts_with('-') { -offset } else { offset });
+    }
+
+    if zone.is_empty() || !zone.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let hours = match zone.to_ascii_uppercase().as_str() {
+        "EDT" => -4,
+        "EST" | "CDT" => -5,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" => -8,
+        _ => 0,
+    };
+    Some(hours * 60)
+}
+
+/// Parses an RFC 5322 date (e.g., `Mon, 7 Jan 2019 10:00:00 +0200`), as
+/// found in the Date header field. The day of week and any trailing
+/// comments are ignored.
+pub fn parse_rfc5322_date(s: &str) -> Option<DateTime> {
+    let s = s.split('(').next()?;
+    let s = match s.find(',') {
+        Some(i) => &s[i + 1..],
+        None => s,
+    };
+    let mut tokens = s.split_whitespace();
+
+    let day: u32 = tokens.next()?.parse().ok()?;
+    let month_str = tokens.next()?.get(..3)?;
+    let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month_str))? as u32 + 1;
+    let year: u32 = match tokens.next()? {
+        y if y.len() <= 2 => {
+            let y: u32 = y.parse().ok()?;
+            if y < 50 { 2000 + y } else { 1900 + y }
+        },
+        y if y.len() == 3 => 1900 + y.parse::<u32>().ok()?,
+        y => y.parse().ok()?,
+    };
+
+    let mut time = tokens.next()?.split(':');
+    let hour: u32 = time.next()?.parse().ok()?;
+    let minute: u32 = time.next()?.parse().ok()?;
+    let second: u32 = match time.next() {
+        Some(sec) => sec.parse().ok()?,
+        None => 0,
+    };
+
+    let offset = match tokens.next() {
+        Some(zone) => parse_zone(zone)?,
+        None => 0,
+    };
+
+    if day == 0 || day > 31 || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    Some(DateTime{year, month, day, hour, minute, second, offset})
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the timezone offset from UTC in minutes of the Date header
+    /// field, without parsing the rest of the date.
+    ///
+    /// Both numeric zones (e.g., `+0200`) and the obsolete zone names
+    /// allowed by RFC 5322 (e.g., `GMT`, `EST`) are supported. Unknown zone
+    /// names are treated as UTC, as RFC 5322 recommends. Returns `None` if
+    /// the email has no Date header field or the field doesn't end with a
+    /// valid zone.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.date_offset().is_some_and(|offset| offset >= 8 * 60) {
+    ///     email.deliver_to_maildir("/my/maildir/apac")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn date_offset(&self) -> Option<i32> {
+        let date = self.header_field("Date")?;
+        // Ignore any trailing comments, e.g., "+0200 (EET)".
+        let zone = date.split('(').next()?.split_whitespace().last()?;
+        parse_zone(zone)
+    }
+
+    /// Returns the date of the Date header field as a Unix timestamp.
+    ///
+    /// The date is parsed as specified by RFC 5322, including the obsolete
+    /// zone names (e.g., `GMT`, `UT`, `EST`) and two-digit years. Missing
+    /// seconds and single-digit days are tolerated. Returns `None` if the
+    /// email has no Date header field or the date can't be parsed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let year = email.date().map(|date| 1970 + date / (365 * 86400));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn date(&self) -> Option<i64> {
+        self.header_field("Date")
+            .and_then(parse_rfc5322_date)
+            .map(|date| date.to_unix_time())
+    }
+}
@@ -8,12 +8,12 @@
 
 //! Base64 and quoted-printable decoding.
 
-use crate::Result;
+use crate::{MdaError, Result};
 
 const PAD: u8 = 64; // The pseudo-index of the PAD character.
 const INV: u8 = 99; // An invalid index.
 
-static BASE64_INDICES: &'static [u8] = &[
+static BASE64_INDICES: &[u8] = &[
      //   0    1    2    3    4    5    6    7    8    9    A    B    C    D    E    F
 /* 0 */ INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV,
 /* 1 */ INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV, INV,
@@ -47,7 +47,7 @@ enum Base64Value {
 /// characters (rfc2045: All line breaks or other characters not
 /// found in Table 1 must be ignored by decoding software).
 fn next_valid_base64_value(iter: &mut dyn Iterator<Item=&u8>) -> Base64Value {
-    while let Some(c) = iter.next() {
+    for c in &mut *iter {
         let b = BASE64_INDICES[*c as usize];
         if b < PAD {
             return Base64Value::Some(b);
@@ -56,7 +56,7 @@ fn next_valid_base64_value(iter: &mut dyn Iterator<Item=&u8>) -> Base64Value {
             return Base64Value::Pad;
         }
     }
-    return Base64Value::None;
+    Base64Value::None
 }
 
 /// Decodes base64 encoded data, appending the decoded data to a Vec<u8>.
@@ -73,44 +73,44 @@ pub fn base64_decode_into_buf(input: &[u8], output: &mut Vec<u8>) -> Result<()>
         loop {
             let c0 = match next_valid_base64_value(&mut iter) {
                 Base64Value::Some(c) => c,
-                Base64Value::Pad => return Err("Invalid base64 padding".into()),
+                Base64Value::Pad => return Err(MdaError::Decode("Invalid base64 padding".into())),
                 Base64Value::None => return Ok(()),
             };
 
             let c1 = match next_valid_base64_value(&mut iter) {
                 Base64Value::Some(c) => { output.push((c0 << 2) | ((c & 0x3f) >> 4)); c }
-                Base64Value::Pad => return Err("Invalid base64 padding".into()),
-                Base64Value::None => return Err("Invalid base64 encoding".into()),
+                Base64Value::Pad => return Err(MdaError::Decode("Invalid base64 padding".into())),
+                Base64Value::None => return Err(MdaError::Decode("Invalid base64 encoding".into())),
             };
 
             let c2 = match next_valid_base64_value(&mut iter) {
                 Base64Value::Some(c) => { output.push((c1 << 4) | ((c & 0x3f) >> 2)); c }
                 Base64Value::Pad => break 1,
-                Base64Value::None => return Err("Invalid base64 padding".into()),
+                Base64Value::None => return Err(MdaError::Decode("Invalid base64 padding".into())),
             };
 
             match next_valid_base64_value(&mut iter) {
-                Base64Value::Some(c) => { output.push((c2 << 6) | ((c & 0x3f))); }
+                Base64Value::Some(c) => { output.push((c2 << 6) | (c & 0x3f)); }
                 Base64Value::Pad => break 0,
-                Base64Value::None => return Err("Invalid base64 padding".into()),
+                Base64Value::None => return Err(MdaError::Decode("Invalid base64 padding".into())),
             };
         };
 
     let mut found_paddings = 0;
 
-    while let Some(c) = iter.next() {
+    for c in iter {
         if *c == b'=' {
             found_paddings += 1;
             continue;
         }
         let b = BASE64_INDICES[*c as usize];
         if b < PAD {
-            return Err("Unexpected characters after base64 padding".into());
+            return Err(MdaError::Decode("Unexpected characters after base64 padding".into()));
         }
     }
 
     if found_paddings != expected_paddings {
-        return Err("Invalid base64 padding".into());
+        return Err(MdaError::Decode("Invalid base64 padding".into()));
     }
 
     Ok(())
@@ -124,7 +124,7 @@ fn hexdigit_to_num(mut a: u8) -> Option<u8> {
 
     a.make_ascii_lowercase();
 
-    if a >= b'a' && a <= b'f' {
+    if (b'a'..=b'f').contains(&a) {
         return Some(a - b'a' + 10);
     }
 
@@ -194,28 +194,28 @@ mod test_base64 {
     fn decodes_full_length() {
         let mut decoded = Vec::new();
         assert!(base64_decode_into_buf("YWJj".as_bytes(), &mut decoded).is_ok());
-        assert_eq!(decoded, &[b'a', b'b', b'c']);
+        assert_eq!(decoded, b"abc");
     }
 
     #[test]
     fn decodes_with_two_padding() {
         let mut decoded = Vec::new();
         assert!(base64_decode_into_buf("YWJjZA==".as_bytes(), &mut decoded).is_ok());
-        assert_eq!(decoded, &[b'a', b'b', b'c', b'd']);
+        assert_eq!(decoded, b"abcd");
     }
 
     #[test]
     fn decodes_with_one_padding() {
         let mut decoded = Vec::new();
         assert!(base64_decode_into_buf("YWJjZGU=".as_bytes(), &mut decoded).is_ok());
-        assert_eq!(decoded, &[b'a', b'b', b'c', b'd', b'e']);
+        assert_eq!(decoded, b"abcde");
     }
 
     #[test]
     fn decodes_with_ignored_characters() {
         let mut decoded = Vec::new();
         assert!(base64_decode_into_buf(" Y\t WJ\njZA=\r\n = ".as_bytes(), &mut decoded).is_ok());
-        assert_eq!(decoded, &[b'a', b'b', b'c', b'd']);
+        assert_eq!(decoded, b"abcd");
     }
 
     #[test]
@@ -248,14 +248,14 @@ mod test_qp {
     fn decodes_byte() {
         let mut decoded = Vec::new();
         assert!(qp_decode_into_buf("a=62c=64".as_bytes(), &mut decoded).is_ok());
-        assert_eq!(decoded, &[b'a', b'b', b'c', b'd']);
+        assert_eq!(decoded, b"abcd");
     }
 
     #[test]
     fn decodes_soft_break() {
         let mut decoded = Vec::new();
         assert!(qp_decode_into_buf("a=\r\nb=\nc".as_bytes(), &mut decoded).is_ok());
-        assert_eq!(decoded, &[b'a', b'b', b'c']);
+        assert_eq!(decoded, b"abc");
     }
 
     #[test]
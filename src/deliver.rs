@@ -8,19 +8,21 @@
 
 //! Email delivery functionality.
 
+use std::fmt;
 use std::fs::{self, File};
-use std::io::ErrorKind;
+use std::io::{self, BufWriter, ErrorKind};
 use std::io::prelude::*;
 use std::os::unix::prelude::*;
 use std::path::{PathBuf, Path};
 use std::process;
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::{DeliveryDurability, Result};
+use crate::{Compression, DeliveryDurability, MdaError, Result};
+use crate::quota;
 
 use gethostname::gethostname;
-use libc;
+use sha2::{Digest, Sha256};
 
 /// A generator for likely unique maildir email filenames.
 ///
@@ -33,6 +35,7 @@ pub struct EmailFilenameGenerator {
 }
 
 impl EmailFilenameGenerator {
+    /// Creates a new generator for the current host.
     pub fn new() -> Self {
         // From https://cr.yp.to/proto/maildir.html:
         // "To deal with invalid host names, replace / with \057 and : with \072"
@@ -46,11 +49,17 @@ impl EmailFilenameGenerator {
         EmailFilenameGenerator{
             count: 0,
             max_seen_unix_time: 0,
-            hostname: hostname,
+            hostname,
         }
     }
 }
 
+impl Default for EmailFilenameGenerator {
+    fn default() -> Self {
+        EmailFilenameGenerator::new()
+    }
+}
+
 impl Iterator for EmailFilenameGenerator {
     type Item = String;
 
@@ -69,10 +78,149 @@ impl Iterator for EmailFilenameGenerator {
     }
 }
 
+/// Detailed information about a completed delivery.
+///
+/// More fields may be added in the future, so this struct can't be
+/// constructed or exhaustively matched outside this crate.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryResult {
+    /// The path of the delivered email file.
+    pub path: PathBuf,
+    /// The size of the delivered email file in bytes.
+    pub size: u64,
+    /// The durability method used for the delivery.
+    pub durability: DeliveryDurability,
+    /// Whether the email data was written and synced (with `O_SYNC`) by
+    /// this delivery. This is false when the email was delivered by
+    /// hard-linking to a previous delivery, whose data was already synced.
+    pub file_synced: bool,
+    /// Whether the affected maildir directories were synced.
+    pub dirs_synced: bool,
+}
+
+impl DeliveryResult {
+    pub(crate) fn new(
+        path: PathBuf,
+        size: u64,
+        durability: DeliveryDurability,
+        file_synced: bool,
+        dirs_synced: bool
+    ) -> Self {
+        DeliveryResult{path, size, durability, file_synced, dirs_synced}
+    }
+}
+
+/// An error that prevented the delivery of an email, and that callers may
+/// want to handle specially, e.g., by temporarily rejecting the email.
+///
+/// Delivery methods return such errors as
+/// [MdaError::Delivery](enum.MdaError.html#variant.Delivery).
+///
+/// # Example
+///
+/// ```no_run
+/// # use mda::{DeliveryError, Email, MdaError};
+/// let email = Email::from_stdin()?;
+/// if let Err(err) = email.deliver_to_maildir("/my/maildir") {
+///     if let MdaError::Delivery(DeliveryError::QuotaExceeded) = err {
+///         std::process::exit(75); // EX_TEMPFAIL
+///     }
+///     return Err(err.into());
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryError {
+    /// Delivering the email would exceed the Maildir++ quota of the maildir,
+    /// as defined in its `maildirsize` file.
+    QuotaExceeded,
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryError::QuotaExceeded => write!(f, "Maildir quota exceeded"),
+        }
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+/// A standard maildir flag, stored in the `:2,` info section of the
+/// filename of an email.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MaildirFlag {
+    /// The email is a draft (`D`).
+    Draft,
+    /// The email is flagged for urgent or special attention (`F`).
+    Flagged,
+    /// The email has been replied to (`R`).
+    Replied,
+    /// The email has been seen (`S`).
+    Seen,
+    /// The email has been trashed (`T`).
+    Trashed,
+}
+
+impl MaildirFlag {
+    /// Returns the letter that represents the flag in maildir filenames.
+    pub fn letter(self) -> char {
+        match self {
+            MaildirFlag::Draft => 'D',
+            MaildirFlag::Flagged => 'F',
+            MaildirFlag::Replied => 'R',
+            MaildirFlag::Seen => 'S',
+            MaildirFlag::Trashed => 'T',
+        }
+    }
+}
+
+/// Returns the maildir info for the specified flags, i.e., their letters in
+/// ASCII order, without duplicates.
+pub(crate) fn flags_info(flags: &[MaildirFlag]) -> String {
+    let mut letters: Vec<char> = flags.iter().map(|flag| flag.letter()).collect();
+    letters.sort_unstable();
+    letters.dedup();
+    letters.into_iter().collect()
+}
+
 /// A representation of a maildir.
+///
+/// A `Maildir` can be used to deliver byte data directly, without creating
+/// an [Email](type.Email.html). Multiple maildirs can share the same
+/// [EmailFilenameGenerator](struct.EmailFilenameGenerator.html), which is
+/// recommended when delivering from a single process, to avoid filename
+/// collisions. After the first delivery of some data, further deliveries of
+/// the same data can use `deliver_with_hard_link` to avoid rewriting it.
+///
+/// If the maildir has a Maildir++ `maildirsize` file, deliveries that would
+/// exceed the quota defined in it fail with
+/// [DeliveryError::QuotaExceeded](enum.DeliveryError.html), and successful
+/// deliveries are recorded in it. Maildirs without such a file have no
+/// quota.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::path::Path;
+/// use std::sync::{Arc, Mutex};
+/// use mda::{DeliveryDurability, EmailFilenameGenerator, Maildir};
+/// let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+/// let inbox = Maildir::open_or_create(Path::new("/my/maildir/inbox"), gen.clone())?;
+/// let archive = Maildir::open_or_create(Path::new("/my/maildir/archive"), gen)?;
+///
+/// let data = std::fs::read("/my/email")?;
+/// let path = inbox.deliver(&data, DeliveryDurability::FileAndDirSync)?;
+/// archive.deliver_with_hard_link(&path, DeliveryDurability::FileAndDirSync)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
 pub struct Maildir {
     root: PathBuf,
     email_filename_gen: Arc<Mutex<EmailFilenameGenerator>>,
+    retries: u32,
+    retry_base_delay: Duration,
 }
 
 impl Maildir {
@@ -84,43 +232,334 @@ impl Maildir {
     ) -> Result<Self> {
         let root = PathBuf::from(mailbox);
         for s in &["tmp", "new", "cur"] {
-            let path = root.join(&s);
+            let path = root.join(s);
             fs::create_dir_all(&path)?;
         }
 
-        Ok(Maildir{root, email_filename_gen})
+        Ok(Maildir{root, email_filename_gen, retries: 0, retry_base_delay: Duration::ZERO})
+    }
+
+    /// Sets the number of times failed steps of deliveries to the maildir,
+    /// i.e., writing the email file and hard-linking it in place, are
+    /// retried, if they fail with errors that may be transient, like
+    /// `EINTR`, `EAGAIN`, `EIO` or `ENOSPC`. Other errors, e.g., `EACCES`,
+    /// are returned immediately. Retries are delayed with exponential
+    /// backoff, starting with `base_delay`.
+    ///
+    /// By default failed steps aren't retried.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// use mda::{EmailFilenameGenerator, Maildir};
+    /// let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    /// let mut maildir = Maildir::open_or_create(Path::new("/my/maildir"), gen)?;
+    /// // Retry after 100ms, 200ms and 400ms.
+    /// maildir.set_delivery_retries(3, Duration::from_millis(100));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_delivery_retries(&mut self, count: u32, base_delay: Duration) {
+        self.retries = count;
+        self.retry_base_delay = base_delay;
     }
 
     /// Delivers an email to the maildir by creating a new file with the email data,
-    /// and using the specified DeliveryDurability method.
+    /// and using the specified DeliveryDurability method. Returns the path of
+    /// the delivered email file in the new/ directory.
     pub fn deliver(
         &self,
         data: &[u8],
         delivery_durability: DeliveryDurability
     ) -> Result<PathBuf> {
-        loop {
-            let tmp_dir = self.root.join("tmp");
-            let new_dir = self.root.join("new");
+        self.deliver_to_subdir(data, data.len(), "new", "", delivery_durability)
+    }
 
-            let tmp_email = self.write_email_to_dir(data, &tmp_dir)?;
-            let new_email = new_dir.join(
-                tmp_email.file_name().ok_or("")?.to_str().ok_or("")?);
+    /// Delivers an email to the cur/ directory of the maildir, with the
+    /// specified info (e.g., `FS`, or Dovecot keyword letters) in the `:2,`
+    /// section of the filename, and using the specified DeliveryDurability
+    /// method.
+    pub fn deliver_with_info(
+        &self,
+        data: &[u8],
+        info: &str,
+        delivery_durability: DeliveryDurability
+    ) -> Result<PathBuf> {
+        self.deliver_to_subdir(data, data.len(), "cur", &format!(":2,{}", info), delivery_durability)
+    }
 
-            let result = fs::hard_link(&tmp_email, &new_email);
-            fs::remove_file(&tmp_email)?;
+    /// Delivers an email to the maildir like `deliver`, compressing the
+    /// email data with the specified algorithm.
+    ///
+    /// The unique part of the filename of the delivered email is followed by
+    /// the suffix of the compression algorithm, e.g., `.gz`, and by the
+    /// `S=<size>` extension with the uncompressed size of the email, e.g.,
+    /// `1234.host.gz,S=2048`. Readers like Dovecot use the size extension to
+    /// report the size of compressed emails without decompressing them.
+    /// The uncompressed size is also what counts against the quota of the
+    /// maildir.
+    pub fn deliver_compressed(
+        &self,
+        data: &[u8],
+        compression: Compression,
+        delivery_durability: DeliveryDurability
+    ) -> Result<PathBuf> {
+        let compressed = compression.compress(data)?;
+        let suffix = format!("{},S={}", compression.suffix(), data.len());
+        self.deliver_to_subdir(&compressed, data.len(), "new", &suffix, delivery_durability)
+    }
 
-            match result {
-                Ok(_) => {
-                    if delivery_durability == DeliveryDurability::FileAndDirSync {
-                        File::open(&new_dir)?.sync_all()?;
-                        File::open(&tmp_dir)?.sync_all()?;
-                    }
-                    return Ok(new_email);
+    /// Delivers an email to the maildir like `deliver`, with the email data
+    /// written to the delivered file by a callback instead of being passed
+    /// in memory. This is useful for emails that are produced while being
+    /// delivered, e.g., streamed from another source.
+    ///
+    /// The callback is called exactly once. If it fails, the partially
+    /// written file is removed and the delivery fails with the error of the
+    /// callback.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    /// use mda::{DeliveryDurability, EmailFilenameGenerator, Maildir};
+    /// let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    /// let maildir = Maildir::open_or_create(Path::new("/my/maildir"), gen)?;
+    /// maildir.deliver_with_writer(
+    ///     |writer| {
+    ///         let mut file = std::fs::File::open("/my/email")?;
+    ///         std::io::copy(&mut file, writer)?;
+    ///         Ok(())
+    ///     },
+    ///     DeliveryDurability::FileAndDirSync
+    /// )?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_with_writer<F>(
+        &self,
+        write: F,
+        delivery_durability: DeliveryDurability
+    ) -> Result<PathBuf>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<()>
+    {
+        let tmp_dir = self.root.join("tmp");
+        let new_dir = self.root.join("new");
+        let (tmp_email, file) = self.retry(|| self.create_email_file_in_dir(&tmp_dir))?;
+
+        let mut writer = BufWriter::new(file);
+        // The size of the email is known only after it has been written, so
+        // the quota is checked then. Don't leave partially written or over
+        // quota files behind.
+        let result = write(&mut writer)
+            .and_then(|_| Ok(writer.flush()?))
+            .and_then(|_| Ok(fs::metadata(&tmp_email)?.len()))
+            .and_then(|size| self.check_quota(size).map(|_| size));
+        let size = match result {
+            Ok(size) => size,
+            Err(err) => {
+                let _ = fs::remove_file(&tmp_email);
+                return Err(err);
+            },
+        };
+
+        // The data can't be written again, so on name collisions only pick
+        // a new name for the delivered file.
+        let new_email = loop {
+            let new_email = new_dir.join(self.next_email_filename_candidate()?);
+            match self.retry(|| Ok(fs::hard_link(&tmp_email, &new_email)?)) {
+                Ok(_) => break new_email,
+                Err(MdaError::Io(ref err)) if err.kind() == ErrorKind::AlreadyExists => {},
+                Err(err) => {
+                    let _ = fs::remove_file(&tmp_email);
+                    return Err(err);
                 },
-                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {},
-                Err(err)  => return Err(err.into()),
             }
+        };
+        fs::remove_file(&tmp_email)?;
+        self.record_delivery(size);
+
+        if delivery_durability == DeliveryDurability::FileAndDirSync {
+            File::open(&new_dir)?.sync_all()?;
+            File::open(&tmp_dir)?.sync_all()?;
         }
+
+        Ok(new_email)
+    }
+
+    /// Registers keywords in the `dovecot-keywords` file of the maildir, if
+    /// they are not already registered, and returns the sorted letters that
+    /// reference them in the `:2,` info section of email filenames.
+    ///
+    /// Dovecot supports up to 26 keywords per maildir, referenced by the
+    /// letters `a` to `z`. Updates to the keywords file are serialized
+    /// using a `dovecot-keywords.lock` dotlock, like Dovecot itself does.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    /// use mda::{DeliveryDurability, EmailFilenameGenerator, Maildir};
+    /// let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    /// let maildir = Maildir::open_or_create(Path::new("/my/maildir"), gen)?;
+    /// let letters = maildir.register_dovecot_keywords(&["$Label1", "work"])?;
+    /// maildir.deliver_with_info(b"Subject: Hi\n\n", &letters, DeliveryDurability::FileAndDirSync)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn register_dovecot_keywords(&self, keywords: &[&str]) -> Result<String> {
+        for keyword in keywords {
+            if keyword.is_empty() ||
+               keyword.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                return Err(format!("Invalid keyword: {:?}", keyword).into());
+            }
+        }
+
+        let keywords_path = self.root.join("dovecot-keywords");
+        let mut lock = DotLock::acquire(self.root.join("dovecot-keywords.lock"))?;
+
+        let mut registered = match fs::read_to_string(&keywords_path) {
+            Ok(contents) => parse_dovecot_keywords(&contents),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut indices = Vec::new();
+        let mut changed = false;
+
+        for keyword in keywords {
+            let index = match registered.iter().find(|(_, k)| k == keyword) {
+                Some((index, _)) => *index,
+                None => {
+                    let index = (0..26).find(|i| registered.iter().all(|(j, _)| j != i))
+                        .ok_or("No free Dovecot keyword slots in maildir")?;
+                    registered.push((index, keyword.to_string()));
+                    changed = true;
+                    index
+                },
+            };
+            indices.push(index);
+        }
+
+        if changed {
+            registered.sort();
+            for (index, keyword) in &registered {
+                writeln!(lock.file, "{} {}", index, keyword)?;
+            }
+            lock.replace(&keywords_path)?;
+            File::open(&self.root)?.sync_all()?;
+        }
+
+        indices.sort_unstable();
+        indices.dedup();
+        Ok(indices.into_iter().map(|i| (b'a' + i) as char).collect())
+    }
+
+    /// Delivers an email to the maildir at most once for the specified
+    /// identity (e.g., a Message-ID or an envelope id), using the specified
+    /// DeliveryDurability method.
+    ///
+    /// The filename of the delivered email deterministically encodes the
+    /// identity, so if an email with the same identity has already been
+    /// delivered to the maildir, even by a previous process that crashed
+    /// before reporting success, the delivery is skipped, before the quota
+    /// is checked. This holds even if the email has since been moved to the
+    /// cur/ directory. An identity whose email has been removed from the
+    /// maildir can be delivered again.
+    ///
+    /// The email is first durably linked into new/, which fails atomically
+    /// if a concurrent delivery got there first. Only then is a marker,
+    /// named after the identity and hard-linked to the delivered file, added
+    /// to the `mda-once` directory of the maildir, so that later deliveries
+    /// find the email without scanning cur/. Markers of removed emails are
+    /// cleaned up by [clean_tmp](#method.clean_tmp).
+    ///
+    /// Returns the path of the delivered email file in the new/ directory,
+    /// or `None` if an email with the same identity was already delivered.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    /// use mda::{DeliveryDurability, EmailFilenameGenerator, Maildir};
+    /// let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    /// let maildir = Maildir::open_or_create(Path::new("/my/maildir"), gen)?;
+    /// let data = std::fs::read("/my/email")?;
+    /// if maildir.deliver_once(&data, "<1234@example.com>", DeliveryDurability::FileAndDirSync)?.is_none() {
+    ///     eprintln!("Already delivered");
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_once(
+        &self,
+        data: &[u8],
+        identity: &str,
+        delivery_durability: DeliveryDurability
+    ) -> Result<Option<PathBuf>> {
+        let filename = identity_filename(identity);
+        let once_dir = self.root.join("mda-once");
+        let marker = once_dir.join(&filename);
+        if once_marker_is_linked(&marker)? || self.contains_email_named(&filename)? {
+            return Ok(None);
+        }
+
+        let new_email = match self.deliver_to_new_named(data, &filename, delivery_durability)? {
+            Some(new_email) => new_email,
+            None => return Ok(None),
+        };
+
+        // The email has already been delivered, and without the marker a
+        // retry still finds it in new/ or cur/, so failing to add the
+        // marker doesn't fail the delivery.
+        let _ = add_once_marker(&new_email, &once_dir, &marker, delivery_durability);
+
+        Ok(Some(new_email))
+    }
+
+    /// Delivers an email to the maildir using the specified base as the
+    /// filename, instead of a generated unique name, and using the
+    /// specified DeliveryDurability method. This is useful for correlating
+    /// maildir filenames with identifiers assigned by external systems.
+    ///
+    /// The base is sanitized with
+    /// [sanitize_maildir_component](fn.sanitize_maildir_component.html), and
+    /// the `:` character, which isn't allowed in maildir filenames, is also
+    /// escaped (as `\072`). Fails if the base is empty or starts with `.`, or
+    /// if an email with the same filename already exists in the new/ or cur/
+    /// directory.
+    ///
+    /// Returns the path of the delivered email file in the new/ directory.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    /// use mda::{DeliveryDurability, EmailFilenameGenerator, Maildir};
+    /// let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    /// let maildir = Maildir::open_or_create(Path::new("/my/maildir"), gen)?;
+    /// let data = std::fs::read("/my/email")?;
+    /// maildir.deliver_named(&data, "ticket-1234", DeliveryDurability::FileAndDirSync)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_named(
+        &self,
+        data: &[u8],
+        base: &str,
+        delivery_durability: DeliveryDurability
+    ) -> Result<PathBuf> {
+        let filename = named_filename(base)?;
+        let exists_err = || format!("An email named {:?} already exists in the maildir", filename);
+        if self.contains_email_named(&filename)? {
+            return Err(exists_err().into());
+        }
+
+        self.deliver_to_new_named(data, &filename, delivery_durability)?
+            .ok_or_else(|| exists_err().into())
     }
 
     /// Delivers an email to the maildir by hard-linking with an existing file,
@@ -130,25 +569,299 @@ impl Maildir {
         src: &Path,
         delivery_durability: DeliveryDurability
     ) -> Result<PathBuf> {
+        let size = fs::metadata(src)?.len();
+        self.check_quota(size)?;
+
         loop {
             let new_dir = self.root.join("new");
             let new_email = new_dir.join(self.next_email_filename_candidate()?);
 
-            match fs::hard_link(&src, &new_email) {
+            match self.retry(|| Ok(fs::hard_link(src, &new_email)?)) {
                 Ok(_) => {
+                    self.record_delivery(size);
                     if delivery_durability == DeliveryDurability::FileAndDirSync {
                         File::open(&new_dir)?.sync_all()?;
                     }
                     return Ok(new_email);
                 },
-                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {},
-                Err(err)  => return Err(err.into()),
+                Err(MdaError::Io(ref err)) if err.kind() == ErrorKind::AlreadyExists => {},
+                Err(err)  => return Err(err),
             }
         }
     }
 
+    /// Removes files that were last modified more than `older_than` ago from
+    /// the tmp/ directory of the maildir, returning the number of removed
+    /// files.
+    ///
+    /// Such files are typically left behind by interrupted deliveries. The
+    /// maildir specification recommends removing files that are older than
+    /// 36 hours.
+    ///
+    /// Markers left by [deliver_once](#method.deliver_once) for emails that
+    /// have since been removed from the maildir are also removed, regardless
+    /// of their age, since they share the data of the removed email, and are
+    /// included in the returned number.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// use mda::{EmailFilenameGenerator, Maildir};
+    /// let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    /// let maildir = Maildir::open_or_create(Path::new("/my/maildir"), gen)?;
+    /// maildir.clean_tmp(Duration::from_secs(36 * 60 * 60))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn clean_tmp(&self, older_than: Duration) -> Result<usize> {
+        let now = SystemTime::now();
+        let mut removed = 0;
+
+        for entry in fs::read_dir(self.root.join("tmp"))? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let age = now.duration_since(metadata.modified()?).unwrap_or_default();
+            if age <= older_than {
+                continue;
+            }
+
+            removed += remove_unless_missing(&entry.path())? as usize;
+        }
+
+        let once_entries = match fs::read_dir(self.root.join("mda-once")) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(removed),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in once_entries {
+            let entry = entry?;
+            if entry.metadata()?.nlink() <= 1 {
+                removed += remove_unless_missing(&entry.path())? as usize;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Moves an email file from a maildir to the cur/ directory of the
+    /// maildir at `dest_root`, creating it if needed, and returns the new
+    /// path of the email file.
+    ///
+    /// The `:2,` info suffix of the source filename, which holds the email
+    /// flags (e.g., seen, replied), is preserved. The email is moved by
+    /// hard-linking, or, if the source is on a different filesystem, by
+    /// copying the email data. The source file is removed after the move.
+    ///
+    /// Moves don't check or update the Maildir++ quota of any maildir. This
+    /// is accurate for moves between the folders of a single Maildir++
+    /// maildir (e.g., from the maildir to its `.Archive` subfolder), which
+    /// share the quota of the maildir, but moves between different
+    /// maildirs aren't reflected in their `maildirsize` files.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use mda::Maildir;
+    /// let moved = Maildir::move_message(
+    ///     Path::new("/my/maildir/cur/1546300800.1234_0.host:2,FS"),
+    ///     Path::new("/my/maildir/.Archive"),
+    /// )?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn move_message(src: &Path, dest_root: &Path) -> Result<PathBuf> {
+        let filename = src.file_name().and_then(|f| f.to_str()).ok_or("Invalid email filename")?;
+        let (mut unique, info) = match filename.rfind(":2,") {
+            Some(i) => (filename[..i].to_string(), &filename[i..]),
+            None => (filename.to_string(), ":2,"),
+        };
+
+        let dest = Maildir::open_or_create(
+            dest_root,
+            Arc::new(Mutex::new(EmailFilenameGenerator::new()))
+        )?;
+        let cur_dir = dest.root.join("cur");
+        let mut link_src = src.to_path_buf();
+        let mut tmp_copy = None;
+
+        let dest_email = loop {
+            let dest_email = cur_dir.join(format!("{}{}", unique, info));
+
+            match fs::hard_link(&link_src, &dest_email) {
+                Ok(_) => break dest_email,
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {
+                    unique = dest.next_email_filename_candidate()?;
+                },
+                // Hard links can't cross filesystems, so copy the email data
+                // to the tmp/ directory of the destination and link from there.
+                Err(ref err) if err.raw_os_error() == Some(libc::EXDEV) && tmp_copy.is_none() => {
+                    let tmp_email = dest.write_email_to_dir(&fs::read(src)?, &dest.root.join("tmp"))?;
+                    link_src = tmp_email.clone();
+                    tmp_copy = Some(tmp_email);
+                },
+                Err(err) => {
+                    if let Some(tmp_email) = tmp_copy {
+                        let _ = fs::remove_file(tmp_email);
+                    }
+                    return Err(err.into());
+                },
+            }
+        };
+
+        if let Some(tmp_email) = tmp_copy {
+            fs::remove_file(tmp_email)?;
+        }
+        File::open(&cur_dir)?.sync_all()?;
+        fs::remove_file(src)?;
+
+        Ok(dest_email)
+    }
+
+    /// Atomically writes a sidecar file with the specified data next to a
+    /// delivered email, using the specified DeliveryDurability method. The
+    /// sidecar file has the filename of the email with a `.meta` suffix.
+    /// Returns the path of the sidecar file.
+    #[cfg(feature = "serde")]
+    pub(crate) fn write_sidecar(
+        &self,
+        email: &Path,
+        data: &[u8],
+        delivery_durability: DeliveryDurability
+    ) -> Result<PathBuf> {
+        let tmp_dir = self.root.join("tmp");
+        let dest_dir = email.parent().ok_or("Invalid email path")?;
+        let mut sidecar = email.as_os_str().to_owned();
+        sidecar.push(".meta");
+        let sidecar = PathBuf::from(sidecar);
+
+        let tmp_sidecar = self.write_email_to_dir(data, &tmp_dir)?;
+        if let Err(err) = fs::rename(&tmp_sidecar, &sidecar) {
+            let _ = fs::remove_file(&tmp_sidecar);
+            return Err(err.into());
+        }
+
+        if delivery_durability == DeliveryDurability::FileAndDirSync {
+            File::open(dest_dir)?.sync_all()?;
+            File::open(&tmp_dir)?.sync_all()?;
+        }
+
+        Ok(sidecar)
+    }
+
+    /// Delivers an email to the specified subdirectory of the maildir, using
+    /// a filename consisting of a unique name followed by `suffix`. The
+    /// email counts as `size` bytes against the quota of the maildir.
+    fn deliver_to_subdir(
+        &self,
+        data: &[u8],
+        size: usize,
+        subdir: &str,
+        suffix: &str,
+        delivery_durability: DeliveryDurability
+    ) -> Result<PathBuf> {
+        self.check_quota(size as u64)?;
+
+        loop {
+            let tmp_dir = self.root.join("tmp");
+            let dest_dir = self.root.join(subdir);
+
+            let tmp_email = self.write_email_to_dir(data, &tmp_dir)?;
+            let dest_email = dest_dir.join(format!(
+                "{}{}", tmp_email.file_name().ok_or("")?.to_str().ok_or("")?, suffix));
+
+            let result = self.retry(|| Ok(fs::hard_link(&tmp_email, &dest_email)?));
+            fs::remove_file(&tmp_email)?;
+
+            match result {
+                Ok(_) => {
+                    self.record_delivery(size as u64);
+                    if delivery_durability == DeliveryDurability::FileAndDirSync {
+                        File::open(&dest_dir)?.sync_all()?;
+                        File::open(&tmp_dir)?.sync_all()?;
+                    }
+                    return Ok(dest_email);
+                },
+                Err(MdaError::Io(ref err)) if err.kind() == ErrorKind::AlreadyExists => {},
+                Err(err)  => return Err(err),
+            }
+        }
+    }
+
+    /// Delivers an email to the new/ directory of the maildir with the
+    /// specified filename. Returns `None` if a file with that name already
+    /// exists, e.g., because it was delivered concurrently by another
+    /// process.
+    fn deliver_to_new_named(
+        &self,
+        data: &[u8],
+        filename: &str,
+        delivery_durability: DeliveryDurability
+    ) -> Result<Option<PathBuf>> {
+        self.check_quota(data.len() as u64)?;
+
+        let tmp_dir = self.root.join("tmp");
+        let new_dir = self.root.join("new");
+        let tmp_email = self.write_email_to_dir(data, &tmp_dir)?;
+        let new_email = new_dir.join(filename);
+
+        let result = self.retry(|| Ok(fs::hard_link(&tmp_email, &new_email)?));
+        fs::remove_file(&tmp_email)?;
+
+        match result {
+            Ok(_) => {
+                self.record_delivery(data.len() as u64);
+                if delivery_durability == DeliveryDurability::FileAndDirSync {
+                    File::open(&new_dir)?.sync_all()?;
+                    File::open(&tmp_dir)?.sync_all()?;
+                }
+                Ok(Some(new_email))
+            },
+            Err(MdaError::Io(ref err)) if err.kind() == ErrorKind::AlreadyExists => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns whether the new/ or cur/ directory contains an email with
+    /// the specified filename, ignoring any `:2,` info suffix.
+    fn contains_email_named(&self, filename: &str) -> Result<bool> {
+        if self.root.join("new").join(filename).exists() {
+            return Ok(true);
+        }
+
+        let info_prefix = format!("{}:", filename);
+        for entry in fs::read_dir(self.root.join("cur"))? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            if name == filename || name.starts_with(&info_prefix) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
     /// Writes email data to a new file in the specified directory.
     fn write_email_to_dir(&self, data: &[u8], dir: &Path) -> Result<PathBuf> {
+        self.retry(|| {
+            let (email, mut f) = self.create_email_file_in_dir(dir)?;
+            // Don't leave partially written files behind.
+            if let Err(err) = f.write_all(data) {
+                let _ = fs::remove_file(&email);
+                return Err(err.into());
+            }
+            Ok(email)
+        })
+    }
+
+    /// Creates a new, empty email file with a unique name in the specified
+    /// directory, opened for synchronous writing.
+    fn create_email_file_in_dir(&self, dir: &Path) -> Result<(PathBuf, File)> {
         loop {
             let email = dir.join(self.next_email_filename_candidate()?);
             let result = fs::OpenOptions::new()
@@ -158,19 +871,313 @@ impl Maildir {
                         .open(&email);
 
             match result {
-                Ok(mut f) => {
-                    f.write_all(&data)?;
-                    return Ok(email);
-                },
+                Ok(f) => return Ok((email, f)),
                 Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {},
                 Err(err)  => return Err(err.into()),
             }
         }
     }
 
+    /// Fails with `DeliveryError::QuotaExceeded` if delivering an email of
+    /// the specified size would exceed the Maildir++ quota of the maildir.
+    fn check_quota(&self, size: u64) -> Result<()> {
+        quota::check_quota(&self.root, size, || {
+            Ok(self.root.join("tmp").join(self.next_email_filename_candidate()?))
+        })
+    }
+
+    /// Records a delivery in the Maildir++ quota usage of the maildir. The
+    /// email has already been delivered at this point, so failures are
+    /// ignored; the usage is recalculated eventually anyway.
+    fn record_delivery(&self, size: u64) {
+        let _ = quota::record_delivery(&self.root, size);
+    }
+
+    /// Runs a delivery step, retrying it with exponential backoff while it
+    /// fails with transient errors, up to the configured number of retries.
+    fn retry<T, F>(&self, mut step: F) -> Result<T>
+    where
+        F: FnMut() -> Result<T>
+    {
+        let mut delay = self.retry_base_delay;
+        for _ in 0..self.retries {
+            match step() {
+                Err(MdaError::Io(ref err)) if is_transient_error(err) => {
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                },
+                result => return result,
+            }
+        }
+        step()
+    }
+
     /// Gets the next email filename candidate from the EmailFilenameGenerator.
     fn next_email_filename_candidate(&self) -> Result<String> {
         let mut gen = self.email_filename_gen.lock().map_err(|_| "")?;
         gen.next().ok_or("".into())
     }
 }
+
+/// Returns whether an IO error may be transient, i.e., whether the failed
+/// operation may succeed if retried, e.g., after an interruption, or after
+/// space has been freed on a full disk.
+fn is_transient_error(err: &io::Error) -> bool {
+    err.kind() == ErrorKind::Interrupted ||
+        matches!(err.raw_os_error(), Some(libc::EINTR | libc::EAGAIN | libc::EIO | libc::ENOSPC))
+}
+
+/// Sanitizes a string for use as a single component of a maildir path,
+/// e.g., the name of a maildir folder.
+///
+/// Folder names derived from the content of emails (e.g., the List-Id, the
+/// detail part of a plus address, or regex captures) or supplied by users
+/// **must** pass through this function before being joined to a path, to
+/// prevent directory traversal. The `/` and `\` characters, control
+/// characters and a leading `.` are escaped in the maildir style (e.g., `/`
+/// becomes `\057`), so the result can't be `.` or `..`, can't be hidden,
+/// and can't span multiple path components. An empty string results in
+/// `_`.
+///
+/// # Example
+///
+/// ```
+/// use mda::sanitize_maildir_component;
+/// assert_eq!(sanitize_maildir_component("lists"), "lists");
+/// assert_eq!(sanitize_maildir_component("../etc"), r"\056.\057etc");
+/// ```
+pub fn sanitize_maildir_component(s: &str) -> String {
+    if s.is_empty() {
+        return "_".to_string();
+    }
+
+    let mut sanitized = String::with_capacity(s.len());
+    for (i, c) in s.chars().enumerate() {
+        if c == '/' || c == '\\' || c.is_control() || (i == 0 && c == '.') {
+            for b in c.to_string().bytes() {
+                sanitized.push_str(&format!("\\{:03o}", b));
+            }
+        } else {
+            sanitized.push(c);
+        }
+    }
+    sanitized
+}
+
+/// Removes a file, returning whether it was removed, or `false` if it
+/// didn't exist, e.g., because another process cleaned up concurrently.
+fn remove_unless_missing(path: &Path) -> Result<bool> {
+    match fs::remove_file(path) {
+        Ok(_) => Ok(true),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns whether a `deliver_once` marker exists and is still linked to
+/// a delivered email, i.e., whether the file has other links besides the
+/// marker. Markers whose email has been removed are ignored.
+fn once_marker_is_linked(marker: &Path) -> Result<bool> {
+    match fs::metadata(marker) {
+        Ok(metadata) => Ok(metadata.nlink() > 1),
+        Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Adds a `deliver_once` marker for a delivered email, by hard-linking it
+/// to the email file, replacing any marker whose email has been removed.
+fn add_once_marker(
+    email: &Path,
+    once_dir: &Path,
+    marker: &Path,
+    delivery_durability: DeliveryDurability
+) -> Result<()> {
+    fs::create_dir_all(once_dir)?;
+    match fs::hard_link(email, marker) {
+        Ok(_) => {},
+        Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {
+            fs::remove_file(marker)?;
+            fs::hard_link(email, marker)?;
+        },
+        Err(err) => return Err(err.into()),
+    }
+
+    if delivery_durability == DeliveryDurability::FileAndDirSync {
+        File::open(once_dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Returns the maildir filename for emails delivered with the specified
+/// identity.
+///
+/// Characters that aren't safe in filenames are escaped in the maildir
+/// style (e.g., `/` becomes `\057`). Long identities are truncated and
+/// followed by the SHA-256 hash of the whole identity, to keep the filename
+/// within filesystem limits, leaving room for the `:2,` info suffix.
+pub(crate) fn identity_filename(identity: &str) -> String {
+    const MAX_ESCAPED_LEN: usize = 160;
+
+    let mut escaped = String::new();
+    for b in identity.bytes() {
+        if b.is_ascii_alphanumeric() || b"<>@._+=-".contains(&b) {
+            escaped.push(b as char);
+        } else {
+            escaped.push_str(&format!("\\{:03o}", b));
+        }
+    }
+
+    if escaped.len() > MAX_ESCAPED_LEN {
+        let hash: String = Sha256::digest(identity.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        escaped.truncate(MAX_ESCAPED_LEN);
+        escaped.push('.');
+        escaped.push_str(&hash);
+    }
+
+    format!("{}.once", escaped)
+}
+
+/// Returns the maildir filename for emails delivered with the specified
+/// base as their name, as described in
+/// [Maildir::deliver_named](struct.Maildir.html#method.deliver_named).
+pub(crate) fn named_filename(base: &str) -> Result<String> {
+    if base.is_empty() || base.starts_with('.') {
+        return Err(format!("Invalid maildir filename base: {:?}", base).into());
+    }
+
+    Ok(sanitize_maildir_component(base).replace(":", r"\072"))
+}
+
+/// Parses the contents of a Dovecot `dovecot-keywords` file into (index,
+/// keyword) pairs. Invalid lines are ignored.
+fn parse_dovecot_keywords(contents: &str) -> Vec<(u8, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ' ');
+            let index: u8 = parts.next()?.parse().ok()?;
+            let keyword = parts.next()?.trim();
+            if index < 26 && !keyword.is_empty() {
+                Some((index, keyword.to_string()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// An exclusive lock held by creating a `.lock` file, which is removed
+/// when the lock is dropped.
+struct DotLock {
+    path: PathBuf,
+    file: File,
+    held: bool,
+}
+
+impl DotLock {
+    /// Locks older than this are considered left behind by a crashed
+    /// process and are overridden.
+    const STALE_AGE: Duration = Duration::from_secs(120);
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn acquire(path: PathBuf) -> Result<Self> {
+        let start = SystemTime::now();
+
+        loop {
+            match fs::OpenOptions::new().create_new(true).write(true).open(&path) {
+                Ok(file) => return Ok(DotLock{path, file, held: true}),
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => {},
+                Err(err) => return Err(err.into()),
+            }
+
+            let stale = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .map(|modified| modified.elapsed().unwrap_or_default() > Self::STALE_AGE)
+                .unwrap_or(false);
+            if stale {
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+
+            if start.elapsed().unwrap_or_default() > Self::TIMEOUT {
+                let msg = format!("Timed out waiting for lock {}", path.display());
+                return Err(io::Error::new(ErrorKind::TimedOut, msg).into());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Atomically replaces `target` with the contents written to the lock
+    /// file, releasing the lock.
+    fn replace(mut self, target: &Path) -> Result<()> {
+        self.file.sync_all()?;
+        fs::rename(&self.path, target)?;
+        self.held = false;
+        Ok(())
+    }
+}
+
+impl Drop for DotLock {
+    fn drop(&mut self) {
+        if self.held {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn maildir_with_retries(dir: &Path, count: u32) -> Maildir {
+        let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+        let mut maildir = Maildir::open_or_create(dir, gen).unwrap();
+        maildir.set_delivery_retries(count, Duration::from_millis(1));
+        maildir
+    }
+
+    fn failing_step(failures: u32, errno: i32) -> impl FnMut() -> Result<u32> {
+        let mut calls = 0;
+        move || {
+            calls += 1;
+            if calls <= failures {
+                Err(io::Error::from_raw_os_error(errno).into())
+            } else {
+                Ok(calls)
+            }
+        }
+    }
+
+    #[test]
+    fn transient_errors_are_retried() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let maildir = maildir_with_retries(tmpdir.path(), 3);
+
+        assert_eq!(maildir.retry(failing_step(3, libc::EIO)).unwrap(), 4);
+        assert_eq!(maildir.retry(failing_step(1, libc::ENOSPC)).unwrap(), 2);
+        assert!(maildir.retry(failing_step(4, libc::EINTR)).is_err());
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let maildir = maildir_with_retries(tmpdir.path(), 3);
+
+        let err = maildir.retry(failing_step(1, libc::EACCES)).unwrap_err();
+
+        assert!(matches!(err, MdaError::Io(ref err) if err.raw_os_error() == Some(libc::EACCES)));
+    }
+
+    #[test]
+    fn errors_are_not_retried_by_default() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let maildir = maildir_with_retries(tmpdir.path(), 0);
+
+        assert!(maildir.retry(failing_step(1, libc::EIO)).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! DKIM (RFC 6376) canonicalization and hashing.
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use crate::BorrowedEmail;
+use crate::util::SliceLines;
+
+/// A DKIM canonicalization algorithm, as specified in the `c=` tag of a
+/// DKIM-Signature header field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Canon {
+    /// The `simple` algorithm, which tolerates almost no modification.
+    Simple,
+    /// The `relaxed` algorithm, which tolerates common whitespace
+    /// modifications.
+    Relaxed,
+}
+
+/// A DKIM hash algorithm, as specified in the `a=` tag of a
+/// DKIM-Signature header field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgo {
+    /// SHA-1 (`rsa-sha1`). It is obsolete for signing (RFC 8301), and is
+    /// accepted only for verifying old signatures.
+    Sha1,
+    /// SHA-256 (`rsa-sha256`).
+    Sha256,
+}
+
+fn is_wsp(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
+/// Canonicalizes a single body line, without its line terminator, with the
+/// relaxed body canonicalization algorithm, i.e., reducing whitespace
+/// sequences to a single space and removing trailing whitespace.
+fn relax_line(line: &[u8], out: &mut Vec<u8>) {
+    let mut pending_wsp = false;
+    for &c in line {
+        if is_wsp(c) {
+            pending_wsp = true;
+        } else {
+            if pending_wsp {
+                out.push(b' ');
+                pending_wsp = false;
+            }
+            out.push(c);
+        }
+    }
+}
+
+/// Canonicalizes body data with the specified DKIM body canonicalization
+/// algorithm. All lines are terminated with CRLF, and empty lines at the
+/// end of the body are removed.
+fn canonicalize_body(body: &[u8], canon: Canon) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len() + body.len() / 32);
+    // The length of the output up to the end of the last non-empty line.
+    let mut content_len = 0;
+
+    for line in SliceLines::new(body) {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        let line_start = out.len();
+        match canon {
+            Canon::Simple => out.extend(line),
+            Canon::Relaxed => relax_line(line, &mut out),
+        }
+        let is_empty = out.len() == line_start;
+        out.extend(b"\r\n");
+        if !is_empty {
+            content_len = out.len();
+        }
+    }
+
+    out.truncate(content_len);
+
+    // The simple algorithm represents an empty body as a single CRLF.
+    if canon == Canon::Simple && out.is_empty() {
+        out.extend(b"\r\n");
+    }
+
+    out
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the DKIM body hash of the email, i.e., the value of the
+    /// `bh=` tag of a DKIM-Signature header field (before base64 encoding),
+    /// as specified in RFC 6376.
+    ///
+    /// The raw body data is canonicalized with the specified algorithm,
+    /// treating bare LF line endings as CRLF, truncated to `limit` bytes if
+    /// a length limit (the `l=` tag) is specified, and hashed with the
+    /// specified hash algorithm.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Canon, Email, HashAlgo};
+    /// let email = Email::from_stdin()?;
+    /// let body_hash = email.dkim_body_hash(Canon::Relaxed, None, HashAlgo::Sha256);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn dkim_body_hash(&self, canon: Canon, limit: Option<usize>, algo: HashAlgo) -> Vec<u8> {
+        let body = canonicalize_body(self.raw_body(), canon);
+        let body = match limit {
+            Some(limit) => &body[..limit.min(body.len())],
+            None => &body[..],
+        };
+
+        match algo {
+            HashAlgo::Sha1 => Sha1::digest(body).to_vec(),
+            HashAlgo::Sha256 => Sha256::digest(body).to_vec(),
+        }
+    }
+}
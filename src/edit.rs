@@ -0,0 +1,160 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Editing of the header fields of emails.
+
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+use crate::{body_index_of, BorrowedEmail, Result};
+use crate::encode::{encode_header_value, fold_header_field};
+use crate::normalize::normalize_email;
+
+/// Fails if a header field with the specified name and value can't be
+/// written without corrupting the header, e.g., because the value contains
+/// line terminators.
+fn check_field(name: &str, value: &str) -> Result<()> {
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic() && b != b':') {
+        return Err(format!("Invalid header field name: {:?}", name).into());
+    }
+    if value.contains(['\r', '\n']) {
+        return Err(format!("Invalid header field value: {:?}", value).into());
+    }
+    Ok(())
+}
+
+/// Returns whether a raw header field, including any continuation lines,
+/// has the specified name. Names are compared case-insensitively.
+fn field_has_name(field: &[u8], name: &str) -> bool {
+    field.iter()
+        .position(|&b| b == b':')
+        .is_some_and(|colon| field[..colon].trim_ascii().eq_ignore_ascii_case(name.as_bytes()))
+}
+
+/// Appends a header field to a raw header, terminating the last line of the
+/// header first, if needed.
+fn append_field(header: &mut Vec<u8>, field: &[u8]) {
+    if !header.is_empty() && !header.ends_with(b"\n") {
+        let newline = if field.ends_with(b"\r\n") { &b"\r\n"[..] } else { &b"\n"[..] };
+        header.extend(newline);
+    }
+    header.extend(field);
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns a header field with the specified name and value, as it
+    /// should be written in the raw data. Non-ASCII text is encoded as MIME
+    /// encoded-words, and the field is folded to fit in 78 columns where
+    /// possible, using the line terminators of the raw header.
+    fn raw_header_field_line(&self, name: &str, value: &str) -> Vec<u8> {
+        let newline = match self.raw_header_lines().next() {
+            Some(line) if line.ends_with(b"\r\n") => "\r\n",
+            _ => "\n",
+        };
+        let field = format!("{}:{}", name, encode_header_value(&format!(" {}", value)));
+        fold_header_field(&field, newline).into_bytes()
+    }
+
+    /// Replaces the raw header of the email, and updates the normalized
+    /// data and the parsed information about the email accordingly. The
+    /// email is normalized with the options it was originally created with,
+    /// apart from any part transform and deadline, which apply only to the
+    /// creation of the email.
+    fn replace_raw_header(&mut self, mut header: Vec<u8>) -> Result<()> {
+        header.extend(&self.raw_data[self.raw_header_end()..]);
+        let normalized = normalize_email(&header, &mut self.normalization_settings.to_options())?;
+
+        self.raw_data = Cow::Owned(header);
+        self.raw_hash = OnceLock::new();
+        self.body_index = body_index_of(&normalized.data);
+        self.normalized_data = normalized.data;
+        self.fields = normalized.fields;
+        self.lossy_fields = normalized.lossy_fields;
+        self.body_parts = normalized.body_parts;
+        self.parts = normalized.parts;
+        self.issues = normalized.issues;
+        self.longest_field = normalized.longest_field;
+        // Previous deliveries have different data, so they can't be reused.
+        *self.deliver_path.get_mut().unwrap() = None;
+
+        Ok(())
+    }
+
+    /// Sets the value of a header field, replacing all existing
+    /// occurrences of the field. The field takes the place of its first
+    /// occurrence, or is added at the end of the header if the email
+    /// doesn't have such a field.
+    ///
+    /// The raw data of the email, which is what gets delivered, is updated,
+    /// and the email is normalized again, so the new value is reflected by
+    /// all methods, e.g., `header_field` and `header`. The email is
+    /// normalized with the options it was created with, apart from any part
+    /// transform and deadline. Any non-ASCII text in the value is encoded
+    /// as MIME encoded-words, and long values are folded.
+    ///
+    /// Fails if the name isn't a valid header field name, or if the value
+    /// contains line terminators.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let mut email = Email::from_stdin()?;
+    /// email.set_header_field("X-Bogosity", "Spam, tests=bogofilter")?;
+    /// email.deliver_to_maildir("/my/maildir/spam")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_header_field(&mut self, name: &str, value: &str) -> Result<()> {
+        check_field(name, value)?;
+        let field = self.raw_header_field_line(name, value);
+
+        let mut header = Vec::new();
+        let mut replaced = false;
+        for line in self.raw_header_lines() {
+            if !field_has_name(line, name) {
+                header.extend(line);
+            } else if !replaced {
+                header.extend(&field);
+                replaced = true;
+            }
+        }
+
+        if !replaced {
+            append_field(&mut header, &field);
+        }
+
+        self.replace_raw_header(header)
+    }
+
+    /// Adds a header field at the end of the header, keeping any existing
+    /// occurrences of the field.
+    ///
+    /// Like [set_header_field](#method.set_header_field), the raw data of
+    /// the email is updated and the email is normalized again, and the
+    /// value is encoded and folded as needed. Fails under the same
+    /// conditions as [set_header_field](#method.set_header_field).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let mut email = Email::from_stdin()?;
+    /// email.add_header_field("X-Spam-Score", "7.5")?;
+    /// email.deliver_to_maildir("/my/maildir/spam")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn add_header_field(&mut self, name: &str, value: &str) -> Result<()> {
+        check_field(name, value)?;
+        let field = self.raw_header_field_line(name, value);
+
+        let mut header = self.raw_data[..self.raw_header_end()].to_vec();
+        append_field(&mut header, &field);
+
+        self.replace_raw_header(header)
+    }
+}
@@ -0,0 +1,382 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Base64, quoted-printable and MIME encoded-word encoding.
+
+use encoding_rs::{Encoding, UTF_8};
+
+static BASE64_CHARS: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The maximum length of an encoded line allowed by MIME, excluding the
+/// line terminator.
+pub(crate) const MAX_ENCODED_LINE_LEN: usize = 76;
+
+/// The maximum length of a MIME encoded-word, as specified by RFC 2047.
+const MAX_ENCODED_WORD_LEN: usize = 75;
+
+/// The maximum length of a folded header field line.
+const MAX_HEADER_LINE_LEN: usize = 78;
+
+/// Encodes the input as base64, appending to the output.
+///
+/// If a maximum line length is specified, the encoded data is split into
+/// lines of at most that many characters (rounded down to a multiple of 4,
+/// but at least 4), separated with the specified newline sequence. MIME
+/// requires lines of at most 76 characters. No newline is appended after
+/// the last line.
+///
+/// # Example
+///
+/// ```
+/// use mda::base64_encode_into_buf;
+/// let mut encoded = Vec::new();
+/// base64_encode_into_buf(b"abcde", Some(76), b"\r\n", &mut encoded);
+/// assert_eq!(encoded, b"YWJjZGU=");
+/// ```
+pub fn base64_encode_into_buf(
+    input: &[u8],
+    max_line_len: Option<usize>,
+    newline: &[u8],
+    output: &mut Vec<u8>
+) {
+    // Each group of 3 input bytes is encoded as 4 characters.
+    let line_input_len = match max_line_len {
+        Some(max_line_len) => (max_line_len / 4).max(1) * 3,
+        None => input.len().max(1),
+    };
+
+    for (i, line) in input.chunks(line_input_len).enumerate() {
+        if i > 0 {
+            output.extend(newline);
+        }
+        for group in line.chunks(3) {
+            let n = (group[0] as u32) << 16 |
+                    (*group.get(1).unwrap_or(&0) as u32) << 8 |
+                    *group.get(2).unwrap_or(&0) as u32;
+            for j in 0..4 {
+                if j <= group.len() {
+                    output.push(BASE64_CHARS[(n >> (18 - 6 * j)) as usize & 0x3f]);
+                } else {
+                    output.push(b'=');
+                }
+            }
+        }
+    }
+}
+
+/// Encodes the input as quoted-printable, appending to the output.
+///
+/// Line breaks (`\n` or `\r\n`) in the input are emitted as the specified
+/// newline sequence. If a maximum line length is specified (at least 4),
+/// soft line breaks are inserted to keep encoded lines within that many
+/// characters. MIME requires lines of at most 76 characters. Whitespace at
+/// the end of lines is encoded, so that it's not lost in transport.
+///
+/// # Example
+///
+/// ```
+/// use mda::qp_encode_into_buf;
+/// let mut encoded = Vec::new();
+/// qp_encode_into_buf("Καλημέρα = hi".as_bytes(), Some(76), b"\n", &mut encoded);
+/// assert_eq!(encoded, b"=CE=9A=CE=B1=CE=BB=CE=B7=CE=BC=CE=AD=CF=81=CE=B1 =3D hi");
+/// ```
+pub fn qp_encode_into_buf(
+    input: &[u8],
+    max_line_len: Option<usize>,
+    newline: &[u8],
+    output: &mut Vec<u8>
+) {
+    // An encoded byte and the '=' of a soft line break must fit in a line.
+    let max_line_len = max_line_len.map_or(usize::MAX, |len| len.max(4));
+    let mut lines = input.split(|&b| b == b'\n').peekable();
+
+    while let Some(line) = lines.next() {
+        let is_last = lines.peek().is_none();
+        let line = if is_last { line } else { line.strip_suffix(b"\r").unwrap_or(line) };
+        let mut line_len = 0;
+
+        for (i, &b) in line.iter().enumerate() {
+            let at_end = i + 1 == line.len();
+            let literal = match b {
+                b' ' | b'\t' => !at_end,
+                b'=' => false,
+                33..=126 => true,
+                _ => false,
+            };
+            let len = if literal { 1 } else { 3 };
+
+            // Leave room for the '=' of the soft line break.
+            if line_len + len > max_line_len - 1 {
+                output.push(b'=');
+                output.extend(newline);
+                line_len = 0;
+            }
+
+            if literal {
+                output.push(b);
+            } else {
+                output.extend(format!("={:02X}", b).as_bytes());
+            }
+            line_len += len;
+        }
+
+        if !is_last {
+            output.extend(newline);
+        }
+    }
+}
+
+/// Encodes some text as a sequence of base64 MIME encoded-words in the
+/// specified encoding, labeled with the specified charset and separated by
+/// spaces. Each encoded-word is at most 75 characters long, as required by
+/// RFC 2047, unless the charset label is too long to fit a single
+/// character, and never splits a character.
+fn encoded_words(charset: &str, encoding: &'static Encoding, text: &str) -> String {
+    let prefix = format!("=?{}?b?", charset);
+    // Each group of 3 bytes is encoded as 4 characters, and the prefix and
+    // the "?=" suffix must fit in the encoded-word, too.
+    let max_chunk_len = MAX_ENCODED_WORD_LEN.saturating_sub(prefix.len() + 2) / 4 * 3;
+
+    let encoded_word = |chunk: &[u8]| {
+        let mut encoded = prefix.as_bytes().to_vec();
+        base64_encode_into_buf(chunk, None, b"", &mut encoded);
+        encoded.extend(b"?=");
+        String::from_utf8(encoded).unwrap()
+    };
+
+    let mut words = Vec::new();
+    let mut chunk_start = 0;
+    let mut chunk = Vec::new();
+    for (i, c) in text.char_indices() {
+        // Encode each chunk on its own, so that the encoded-words are self
+        // contained, even for stateful encodings.
+        let (encoded, _, _) = encoding.encode(&text[chunk_start..i + c.len_utf8()]);
+        if encoded.len() > max_chunk_len && i > chunk_start {
+            words.push(encoded_word(&chunk));
+            chunk_start = i;
+            chunk = encoding.encode(&text[i..i + c.len_utf8()]).0.into_owned();
+        } else {
+            chunk = encoded.into_owned();
+        }
+    }
+    words.push(encoded_word(&chunk));
+
+    words.join(" ")
+}
+
+/// Encodes some text as a sequence of base64 MIME encoded-words in the
+/// specified charset, separated by spaces, e.g., for use in a header field
+/// value. Each encoded-word is at most 75 characters long, as required by
+/// RFC 2047, and never splits a character.
+///
+/// If the charset isn't known, or it can't represent all the characters of
+/// the text, the text is encoded as UTF-8 instead.
+///
+/// # Example
+///
+/// ```
+/// use mda::encode_word;
+/// assert_eq!(encode_word("iso-8859-7", "Γειά"), "=?iso-8859-7?b?w+Xp3A==?=");
+/// assert_eq!(encode_word("us-ascii", "Γειά"), "=?utf-8?b?zpPOtc65zqw=?=");
+/// ```
+pub fn encode_word(charset: &str, text: &str) -> String {
+    let charset = charset.trim();
+    // ASCII labels are mapped to windows-1252, which is a superset of ASCII.
+    let is_ascii_label = ["us-ascii", "ascii"].iter().any(|l| charset.eq_ignore_ascii_case(l));
+
+    match Encoding::for_label(charset.as_bytes()) {
+        // Encodings like UTF-16 are never used as output encodings.
+        Some(encoding) if encoding.output_encoding() == encoding &&
+                          !encoding.encode(text).2 &&
+                          (!is_ascii_label || text.is_ascii()) => {
+            encoded_words(charset, encoding, text)
+        },
+        _ => encoded_words("utf-8", UTF_8, text),
+    }
+}
+
+/// Encodes the non-ASCII words of a header field value as MIME
+/// encoded-words. Consecutive non-ASCII words are encoded together, so
+/// that the spaces between them are preserved when decoding. ASCII words
+/// are left unchanged, so that the structure of field values, e.g., the
+/// addresses in address fields, is preserved.
+pub fn encode_header_value(value: &str) -> String {
+    let words: Vec<&str> = value.split(' ').collect();
+    let mut encoded = Vec::new();
+    let mut i = 0;
+
+    while i < words.len() {
+        if words[i].is_ascii() {
+            encoded.push(words[i].to_string());
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < words.len() && !words[i].is_ascii() {
+            i += 1;
+        }
+        encoded.push(encoded_words("utf-8", UTF_8, &words[start..i].join(" ")));
+    }
+
+    encoded.join(" ")
+}
+
+/// Folds a header field at spaces, so that its lines are at most 78
+/// characters long where possible, and terminates it with the specified
+/// newline sequence. Words longer than a line are never split.
+pub(crate) fn fold_header_field(field: &str, newline: &str) -> String {
+    let mut folded = String::new();
+    let mut line_len = 0;
+    for (i, word) in field.split(' ').enumerate() {
+        if i > 0 {
+            if line_len > 0 && line_len + 1 + word.len() > MAX_HEADER_LINE_LEN {
+                folded.push_str(newline);
+                line_len = 0;
+            }
+            folded.push(' ');
+            line_len += 1;
+        }
+        folded.push_str(word);
+        line_len += word.len();
+    }
+    folded.push_str(newline);
+
+    folded
+}
+
+#[cfg(test)]
+mod test_base64 {
+    use crate::encode::base64_encode_into_buf;
+    use crate::decode::base64_decode_into_buf;
+
+    #[test]
+    fn encodes_with_padding() {
+        let mut encoded = Vec::new();
+        base64_encode_into_buf(b"abcd", Some(76), b"\n", &mut encoded);
+        assert_eq!(encoded, b"YWJjZA==");
+        encoded.clear();
+        base64_encode_into_buf(b"abcde", Some(76), b"\n", &mut encoded);
+        assert_eq!(encoded, b"YWJjZGU=");
+    }
+
+    #[test]
+    fn wraps_long_lines() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut encoded = Vec::new();
+        base64_encode_into_buf(&data, Some(76), b"\r\n", &mut encoded);
+
+        let lines: Vec<&[u8]> = encoded.split(|&b| b == b'\n').collect();
+        assert!(lines[..lines.len() - 1].iter().all(|l| l.len() == 77 && l.ends_with(b"\r")));
+
+        let mut decoded = Vec::new();
+        base64_decode_into_buf(&encoded, &mut decoded).unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn wraps_at_configured_line_length() {
+        let data: Vec<u8> = (0..=255).collect();
+        for max_line_len in &[None, Some(1), Some(10), Some(64)] {
+            let mut encoded = Vec::new();
+            base64_encode_into_buf(&data, *max_line_len, b"\n", &mut encoded);
+
+            let max = max_line_len.map_or(usize::MAX, |len| (len / 4).max(1) * 4);
+            assert!(encoded.split(|&b| b == b'\n').all(|l| l.len() <= max));
+
+            let mut decoded = Vec::new();
+            base64_decode_into_buf(&encoded, &mut decoded).unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_qp {
+    use crate::encode::qp_encode_into_buf;
+    use crate::decode::qp_decode_into_buf;
+
+    #[test]
+    fn encodes_special_bytes_and_trailing_whitespace() {
+        let mut encoded = Vec::new();
+        qp_encode_into_buf("a=b \r\nΑ \n".as_bytes(), Some(76), b"\n", &mut encoded);
+        assert_eq!(encoded, b"a=3Db=20\n=CE=91=20\n");
+    }
+
+    #[test]
+    fn inserts_soft_line_breaks() {
+        let data = "Α".repeat(40);
+        let mut encoded = Vec::new();
+        qp_encode_into_buf(data.as_bytes(), Some(76), b"\n", &mut encoded);
+
+        assert!(encoded.split(|&b| b == b'\n').all(|l| l.len() <= 76));
+        let mut decoded = Vec::new();
+        qp_decode_into_buf(&encoded, &mut decoded).unwrap();
+        assert_eq!(decoded, data.as_bytes());
+    }
+
+    #[test]
+    fn inserts_soft_line_breaks_at_configured_line_length() {
+        let data = "Αβγ = δ\nplain ascii text that is long enough to be folded \n".repeat(3);
+        for max_line_len in &[None, Some(1), Some(20), Some(40)] {
+            let mut encoded = Vec::new();
+            qp_encode_into_buf(data.as_bytes(), *max_line_len, b"\n", &mut encoded);
+
+            let max = max_line_len.map_or(usize::MAX, |len| len.max(4));
+            assert!(encoded.split(|&b| b == b'\n').all(|l| l.len() <= max));
+
+            let mut decoded = Vec::new();
+            qp_decode_into_buf(&encoded, &mut decoded).unwrap();
+            assert_eq!(decoded, data.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_encoded_words {
+    use std::collections::HashMap;
+
+    use crate::encode::encode_word;
+    use crate::normalize::decode_encoded_words;
+
+    fn decode(encoded: &str) -> String {
+        let (decoded, lossless) = decode_encoded_words(encoded.as_bytes(), &HashMap::new());
+        assert!(lossless);
+        String::from_utf8(decoded.into_owned()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_in_charset() {
+        let text = "Καλημέρα κόσμε, τι κάνεις σήμερα; Όλα καλά εδώ στην Αθήνα!";
+        let encoded = encode_word("iso-8859-7", text);
+
+        assert!(encoded.starts_with("=?iso-8859-7?b?"));
+        assert!(encoded.split(' ').all(|word| word.len() <= 75));
+        assert_eq!(decode(&encoded), text);
+    }
+
+    #[test]
+    fn long_text_is_split_without_splitting_characters() {
+        let text = "日本語のテキスト".repeat(10);
+        let encoded = encode_word("utf-8", &text);
+
+        assert!(encoded.split(' ').count() > 1);
+        assert!(encoded.split(' ').all(|word| word.len() <= 75));
+        assert_eq!(decode(&encoded), text);
+    }
+
+    #[test]
+    fn falls_back_to_utf8() {
+        for charset in &["unknown-charset", "iso-8859-1", "us-ascii", "utf-16le"] {
+            let encoded = encode_word(charset, "Γειά");
+            assert!(encoded.starts_with("=?utf-8?b?"), "{}", charset);
+            assert_eq!(decode(&encoded), "Γειά");
+        }
+    }
+}
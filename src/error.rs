@@ -0,0 +1,106 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! The error type of the crate.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::DeliveryError;
+
+/// An error returned by the fallible functions of this crate.
+///
+/// The variants describe the kind of failure, so that callers can decide
+/// how to handle it, e.g., whether to retry a delivery later or to give up.
+/// More variants may be added in the future, so this enum can't be
+/// exhaustively matched outside this crate.
+///
+/// # Example
+///
+/// ```no_run
+/// # use mda::{Email, MdaError};
+/// let email = Email::from_stdin()?;
+/// match email.deliver_to_maildir("/my/maildir") {
+///     Ok(_) => {},
+///     // Let the MTA retry the delivery later.
+///     Err(MdaError::Io(_)) | Err(MdaError::Delivery(_)) => std::process::exit(75),
+///     Err(err) => return Err(err.into()),
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum MdaError {
+    /// An IO error, e.g., while reading an email or writing a delivered
+    /// email file.
+    Io(io::Error),
+    /// A delivery that was refused, e.g., because it would exceed the
+    /// quota of the maildir.
+    Delivery(DeliveryError),
+    /// An invalid regular expression.
+    Regex(regex::Error),
+    /// Data that couldn't be decoded, e.g., invalid base64 or
+    /// quoted-printable data.
+    Decode(String),
+    /// Invalid or unparsable input, e.g., an invalid header field name or
+    /// maildir filename, or an unexpected server response.
+    Parse(String),
+}
+
+impl fmt::Display for MdaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MdaError::Io(err) => err.fmt(f),
+            MdaError::Delivery(err) => err.fmt(f),
+            MdaError::Regex(err) => err.fmt(f),
+            MdaError::Decode(msg) | MdaError::Parse(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Error for MdaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MdaError::Io(err) => Some(err),
+            MdaError::Delivery(err) => Some(err),
+            MdaError::Regex(err) => Some(err),
+            MdaError::Decode(_) | MdaError::Parse(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for MdaError {
+    fn from(err: io::Error) -> Self {
+        MdaError::Io(err)
+    }
+}
+
+impl From<DeliveryError> for MdaError {
+    fn from(err: DeliveryError) -> Self {
+        MdaError::Delivery(err)
+    }
+}
+
+impl From<regex::Error> for MdaError {
+    fn from(err: regex::Error) -> Self {
+        MdaError::Regex(err)
+    }
+}
+
+impl From<String> for MdaError {
+    fn from(msg: String) -> Self {
+        MdaError::Parse(msg)
+    }
+}
+
+impl From<&str> for MdaError {
+    fn from(msg: &str) -> Self {
+        MdaError::Parse(msg.to_string())
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Conversion of HTML to plain text.
+
+use crate::util::find_ignore_ascii_case;
+
+/// Elements that start a new line of text.
+static LINE_BREAK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "br", "dd", "div", "dl", "dt",
+    "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li",
+    "main", "nav", "ol", "p", "pre", "section", "table", "tr", "ul",
+];
+
+/// Elements whose contents aren't text.
+static SKIPPED_ELEMENTS: &[&str] = &["head", "script", "style"];
+
+/// Returns the character referenced by an HTML character reference, without
+/// the leading `&` and trailing `;`, e.g., `amp` or `#x41`. Only numeric
+/// references and the most common named references are supported.
+fn character_reference(name: &str) -> Option<char> {
+    if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    if let Some(dec) = name.strip_prefix('#') {
+        return dec.parse().ok().and_then(char::from_u32);
+    }
+
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "euro" => '€',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// Appends HTML text data to the output, decoding character references.
+/// Line breaks in the data are ordinary whitespace in HTML, so they are
+/// replaced with spaces.
+fn push_text(text: &mut String, mut data: &str) {
+    const MAX_REFERENCE_LEN: usize = 10;
+
+    let start = text.len();
+    while let Some(amp) = data.find('&') {
+        text.push_str(&data[..amp]);
+        data = &data[amp..];
+
+        // Only look for the end of the reference within the maximum length,
+        // to avoid scanning the rest of the data for every `&`.
+        let reference = data[1..].bytes()
+            .take(MAX_REFERENCE_LEN + 1)
+            .position(|b| b == b';')
+            .and_then(|semicolon| Some((character_reference(&data[1..1 + semicolon])?, semicolon)));
+        match reference {
+            Some((c, semicolon)) => {
+                text.push(c);
+                data = &data[semicolon + 2..];
+            },
+            None => {
+                text.push('&');
+                data = &data[1..];
+            },
+        }
+    }
+    text.push_str(data);
+
+    let decoded = text.split_off(start);
+    text.push_str(&decoded.replace(['\r', '\n'], " "));
+}
+
+/// Returns the lowercase name of the element in a tag, e.g., `p` for
+/// `<P class="x">`, and whether the tag is an end tag.
+fn tag_name(tag: &str) -> (String, bool) {
+    let (tag, is_end) = match tag.strip_prefix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let end = tag.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(tag.len());
+    (tag[..end].to_ascii_lowercase(), is_end)
+}
+
+/// Collapses whitespace in text to single spaces within each line, trims
+/// the lines and removes empty lines.
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::new();
+
+    for line in text.lines() {
+        let mut words = line.split_whitespace().peekable();
+        if words.peek().is_none() {
+            continue;
+        }
+        for (i, word) in words.enumerate() {
+            if i > 0 {
+                collapsed.push(' ');
+            }
+            collapsed.push_str(word);
+        }
+        collapsed.push('\n');
+    }
+
+    collapsed
+}
+
+/// Converts HTML to plain text, with a minimal pass that is good enough for
+/// inspecting the text: tags, comments and the contents of non-text
+/// elements (e.g., scripts) are removed, character references are decoded,
+/// block elements and line breaks start new lines, and whitespace is
+/// collapsed.
+pub(crate) fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        push_text(&mut text, &rest[..lt]);
+        rest = &rest[lt..];
+
+        // A `<` that doesn't start a tag is text.
+        let starts_tag = rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        if !starts_tag {
+            text.push('<');
+            rest = &rest[1..];
+            continue;
+        }
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        let Some(gt) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let (name, is_end) = tag_name(&rest[1..gt]);
+        rest = &rest[gt + 1..];
+
+        if !is_end && SKIPPED_ELEMENTS.contains(&name.as_str()) {
+            let end_tag = format!("</{}", name);
+            rest = find_ignore_ascii_case(rest, &end_tag).map_or("", |end| {
+                let after = &rest[end..];
+                after.find('>').map_or("", |gt| &after[gt + 1..])
+            });
+        } else if LINE_BREAK_ELEMENTS.contains(&name.as_str()) {
+            text.push('\n');
+        } else if name == "td" || name == "th" {
+            text.push(' ');
+        }
+    }
+    push_text(&mut text, rest);
+
+    collapse_whitespace(&text)
+}
@@ -0,0 +1,214 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Email delivery to IMAP servers.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use native_tls::TlsConnector;
+
+use crate::date::{parse_rfc5322_date, DateTime, MONTHS};
+use crate::{BorrowedEmail, Result};
+use crate::util::to_crlf;
+
+/// The configuration for delivering emails to an IMAP server.
+///
+/// # Example
+///
+/// ```no_run
+/// use mda::ImapConfig;
+/// let mut config = ImapConfig::new("imap.example.com", "me", "secret");
+/// config.flags = vec![r"\Seen".to_string()];
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImapConfig {
+    /// The host name of the IMAP server.
+    pub host: String,
+    /// The port of the IMAP server. Defaults to 993.
+    pub port: u16,
+    /// Whether to connect using TLS. Defaults to true.
+    pub tls: bool,
+    /// The user name to log in with.
+    pub username: String,
+    /// The password to log in with.
+    pub password: String,
+    /// The flags to set on appended emails, e.g., `\Seen`.
+    pub flags: Vec<String>,
+}
+
+impl ImapConfig {
+    /// Creates a configuration for delivering to the specified host using
+    /// TLS on the default port.
+    pub fn new(host: &str, username: &str, password: &str) -> Self {
+        ImapConfig{
+            host: host.to_string(),
+            port: 993,
+            tls: true,
+            username: username.to_string(),
+            password: password.to_string(),
+            flags: Vec::new(),
+        }
+    }
+}
+
+/// An IMAP client session.
+struct ImapSession<S: Read + Write> {
+    stream: BufReader<S>,
+    next_tag: u32,
+}
+
+impl<S: Read + Write> ImapSession<S> {
+    fn new(stream: S) -> Result<Self> {
+        let mut session = ImapSession{stream: BufReader::new(stream), next_tag: 1};
+        let greeting = session.read_line()?;
+        if !greeting.starts_with("* OK") && !greeting.starts_with("* PREAUTH") {
+            return Err(format!("IMAP server rejected connection: {}", greeting).into());
+        }
+        Ok(session)
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line)? == 0 {
+            let msg = "IMAP server closed the connection";
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg).into());
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let stream = self.stream.get_mut();
+        stream.write_all(data)?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn send_command(&mut self, command: &str) -> Result<String> {
+        let tag = format!("a{}", self.next_tag);
+        self.next_tag += 1;
+        self.write(format!("{} {}\r\n", tag, command).as_bytes())?;
+        Ok(tag)
+    }
+
+    /// Reads responses until the tagged completion response, failing if it
+    /// doesn't report success.
+    fn read_completion(&mut self, tag: &str, what: &str) -> Result<()> {
+        loop {
+            let line = self.read_line()?;
+            if let Some(status) = line.strip_prefix(tag).and_then(|s| s.strip_prefix(' ')) {
+                if status.starts_with("OK") {
+                    return Ok(());
+                }
+                return Err(format!("IMAP {} failed: {}", what, status).into());
+            }
+        }
+    }
+
+    fn login(&mut self, username: &str, password: &str) -> Result<()> {
+        let tag = self.send_command(&format!("LOGIN {} {}", quote(username), quote(password)))?;
+        self.read_completion(&tag, "LOGIN")
+    }
+
+    fn append(
+        &mut self,
+        mailbox: &str,
+        flags: &[String],
+        date: Option<DateTime>,
+        data: &[u8]
+    ) -> Result<()> {
+        let mut command = format!("APPEND {}", quote(mailbox));
+        if !flags.is_empty() {
+            command += &format!(" ({})", flags.join(" "));
+        }
+        if let Some(date) = date {
+            command += &format!(" \"{}\"", imap_date_time(&date));
+        }
+        command += &format!(" {{{}}}", data.len());
+
+        let tag = self.send_command(&command)?;
+
+        // Wait for the server to accept the literal.
+        loop {
+            let line = self.read_line()?;
+            if line.starts_with('+') {
+                break;
+            }
+            if let Some(status) = line.strip_prefix(&tag).and_then(|s| s.strip_prefix(' ')) {
+                return Err(format!("IMAP APPEND failed: {}", status).into());
+            }
+        }
+
+        self.write(data)?;
+        self.write(b"\r\n")?;
+        self.read_completion(&tag, "APPEND")
+    }
+
+    fn logout(&mut self) -> Result<()> {
+        let tag = self.send_command("LOGOUT")?;
+        self.read_completion(&tag, "LOGOUT")
+    }
+}
+
+/// Quotes a string for use in an IMAP command.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', r"\\").replace('"', "\\\""))
+}
+
+/// Formats a date in the IMAP date-time format, e.g.,
+/// `07-Jan-2019 10:00:00 +0200`.
+fn imap_date_time(date: &DateTime) -> String {
+    let sign = if date.offset < 0 { '-' } else { '+' };
+    let offset = date.offset.abs();
+    format!(
+        "{:02}-{}-{:04} {:02}:{:02}:{:02} {}{:02}{:02}",
+        date.day, MONTHS[date.month as usize - 1], date.year,
+        date.hour, date.minute, date.second,
+        sign, offset / 60, offset % 60
+    )
+}
+
+fn deliver<S: Read + Write>(stream: S, email: &BorrowedEmail, config: &ImapConfig, mailbox: &str) -> Result<()> {
+    let mut session = ImapSession::new(stream)?;
+    let date = email.header_field("Date").and_then(parse_rfc5322_date);
+
+    session.login(&config.username, &config.password)?;
+    session.append(mailbox, &config.flags, date, &to_crlf(email.raw_data()))?;
+    session.logout()
+}
+
+impl BorrowedEmail<'_> {
+    /// Delivers the email to a mailbox on an IMAP server, using the IMAP
+    /// APPEND command. The original (non-normalized) email data is
+    /// delivered, and the internal date of the appended email is set from
+    /// the Date header field, if present and valid.
+    ///
+    /// This method is available only if the `imap` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, ImapConfig};
+    /// let email = Email::from_stdin()?;
+    /// let config = ImapConfig::new("imap.example.com", "me", "secret");
+    /// email.deliver_to_imap(&config, "INBOX")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_imap(&self, config: &ImapConfig, mailbox: &str) -> Result<()> {
+        let stream = TcpStream::connect((config.host.as_str(), config.port))?;
+
+        if config.tls {
+            // TLS failures are connection failures, like those of the TCP stream.
+            let connector = TlsConnector::new().map_err(io::Error::other)?;
+            let stream = connector.connect(&config.host, stream).map_err(io::Error::other)?;
+            deliver(stream, self, config, mailbox)
+        } else {
+            deliver(stream, self, config, mailbox)
+        }
+    }
+}
@@ -20,10 +20,10 @@
 //!
 //! # Email construction
 //!
-//! The [Email struct](struct.Email.html) is the basic abstraction of the `mda`
+//! The [Email struct](type.Email.html) is the basic abstraction of the `mda`
 //! crate. To construct an Email use the
-//! [Email::from_stdin](struct.Email.html#method.from_stdin) or
-//! [Email::from_vec](struct.Email.html#method.from_vec) method.
+//! [Email::from_stdin](type.Email.html#method.from_stdin) or
+//! [Email::from_vec](type.Email.html#method.from_vec) method.
 //!
 //! ```no_run
 //! use mda::Email;
@@ -35,7 +35,7 @@
 //! # Email delivery
 //!
 //! Use the
-//! [Email::deliver_to_maildir](struct.Email.html#method.deliver_to_maildir)
+//! [Email::deliver_to_maildir](type.Email.html#method.deliver_to_maildir)
 //! method to deliver the email to local maildir directories. Note that
 //! the original (non-normalized) email data is used during delivery.
 //!
@@ -49,8 +49,8 @@
 //!
 //! # Accessing email header fields
 //!
-//! Use the [Email::header_field](struct.Email.html#method.header_field) and
-//! [Email::header_field_all_occurrences](struct.Email.html#method.header_field_all_occurrences)
+//! Use the [Email::header_field](type.Email.html#method.header_field) and
+//! [Email::header_field_all_occurrences](type.Email.html#method.header_field_all_occurrences)
 //! methods to access the email header fields. Any MIME encoded words in the
 //! header field values are decoded and the field value is converted to UTF-8.
 //!
@@ -84,8 +84,8 @@
 //!
 //! # Processing and filtering the email with external programs
 //!
-//! Use the [Email::filter](struct.Email.html#method.filter) and
-//! [Email::from_stdin_filtered](struct.Email.html#method.from_stdin_filtered)
+//! Use the [Email::filter](type.Email.html#method.filter) and
+//! [Email::from_stdin_filtered](type.Email.html#method.from_stdin_filtered)
 //! methods to filter the email, in both cases creating a new email.
 //!
 //! ```no_run
@@ -102,7 +102,7 @@
 //! ```
 //!
 //! To perform more general processing use the
-//! [Email::process](struct.Email.html#method.process)
+//! [Email::process](type.Email.html#method.process)
 //! method:
 //!
 //! ```no_run
@@ -117,9 +117,9 @@
 //!
 //! # Access to byte data
 //!
-//! Use the [Email::header](struct.Email.html#method.header),
-//! [Email::body](struct.Email.html#method.body),
-//! [Email::data](struct.Email.html#method.data) methods to access the
+//! Use the [Email::header](type.Email.html#method.header),
+//! [Email::body](type.Email.html#method.body),
+//! [Email::data](type.Email.html#method.data) methods to access the
 //! normalized byte data of the header, body and whole email respectively.
 //!
 //! Normalization involves ensuring header fields are in single lines, decoding
@@ -127,7 +127,7 @@
 //! base64), and converting all text to UTF-8 character encoding.
 //!
 //! If for some reason you need access to non-normalized data use
-//! [Email::raw_data](struct.Email.html#method.raw_data).
+//! [Email::raw_data](type.Email.html#method.raw_data).
 //!
 //! ```no_run
 //! use std::str;
@@ -143,7 +143,7 @@
 //!
 //! # Decide delivery durability vs speed trade-off
 //!
-//! Use the [Email::set_delivery_durability](struct.Email.html#method.set_delivery_durability)
+//! Use the [Email::set_delivery_durability](type.Email.html#method.set_delivery_durability)
 //! to decide which [DeliveryDurability](enum.DeliveryDurability.html) method to use.
 //! By default the most durable (but also slower) method is used.
 //!
@@ -153,32 +153,118 @@
 //! email.set_delivery_durability(DeliveryDurability::FileSyncOnly);
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
+//!
+//! # Delivering data without parsing
+//!
+//! The [Maildir](struct.Maildir.html) type provides the delivery machinery
+//! used by [Email](type.Email.html), and can be used directly to deliver
+//! arbitrary byte data, e.g., when no parsing of the email is needed.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use std::sync::{Arc, Mutex};
+//! use mda::{DeliveryDurability, EmailFilenameGenerator, Maildir};
+//! let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+//! let maildir = Maildir::open_or_create(Path::new("/my/maildir/path"), gen)?;
+//! let path = maildir.deliver(b"Subject: Hello\n\nBody\n", DeliveryDurability::FileAndDirSync)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
 
 mod deliver;
 mod regex;
 mod processing;
 mod normalize;
 mod decode;
+mod encode;
+mod dkim;
+mod util;
+mod mbox;
+mod params;
+mod validate;
+mod address;
+mod classify;
+mod compare;
+mod route;
+mod subject;
+mod preview;
+mod reencode;
+mod attachment;
+mod backend;
+mod parts;
+mod compress;
+mod quote;
+mod summary;
+mod spf;
+mod date;
+mod edit;
+mod html;
+mod error;
+mod quota;
+#[cfg(feature = "imap")]
+mod imap;
 
 use std::io;
 use std::io::prelude::*;
 use std::path::{PathBuf, Path};
-use std::sync:: {Arc, Mutex, RwLock};
+use std::sync:: {Arc, Mutex, OnceLock, RwLock};
 use std::collections::HashMap;
+use std::borrow::Cow;
+use std::time::Duration;
 
-use deliver::{Maildir, EmailFilenameGenerator};
-use normalize::normalize_email;
+use sha2::{Digest, Sha256};
 
-pub use crate::regex::EmailRegex;
+use normalize::{normalize_email, normalize_email_to, BodyPart, NormalizationSettings, NormalizedEmail};
+use util::HashingReader;
 
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub use crate::address::Address;
+pub use crate::attachment::Attachment;
+pub use crate::backend::{FilesystemMaildirBackend, MaildirBackend};
+#[cfg(feature = "test-util")]
+pub use crate::backend::{InMemoryDelivery, InMemoryMaildirBackend};
+pub use crate::classify::Priority;
+pub use crate::compress::Compression;
+pub use crate::deliver::{sanitize_maildir_component, DeliveryError, DeliveryResult, EmailFilenameGenerator, Maildir, MaildirFlag};
+pub use crate::dkim::{Canon, HashAlgo};
+pub use crate::error::MdaError;
+pub use crate::encode::{base64_encode_into_buf, encode_word, qp_encode_into_buf};
+#[cfg(feature = "imap")]
+pub use crate::imap::ImapConfig;
+pub use crate::mbox::{read_mbox, split_mbox, MboxDateFormat, MboxFormat, MboxOptions};
+pub use crate::normalize::{normalize_body, NormalizationOptions, PartContext, PartTransform};
+pub use crate::parts::{Descend, EmailPart, OwnedPart, PartInfo, TextPartStrategy};
+pub use crate::regex::{CompiledMatcherSet, EmailRegex, RegionMatch, SearchOptions};
+pub use crate::route::{Route, RouteRule};
+pub use crate::spf::{SpfResult, SpfVerdict};
+pub use crate::subject::Script;
+pub use crate::summary::EmailSummary;
+pub use crate::util::SliceLines;
+pub use crate::validate::ValidationIssue;
 
+pub type Result<T> = std::result::Result<T, MdaError>;
+
+/// Returns the position of the line terminator that precedes the first
+/// empty line in the data. Only truly empty lines are considered, not lines
+/// consisting only of whitespace.
 fn find_empty_line(data: &[u8]) -> Option<usize> {
-    data.windows(2).position(|w| w[0]== b'\n' && (w[1] == b'\n' || w[1] == b'\r'))
+    (0..data.len()).find(|&i| {
+        data[i] == b'\n' && (data[i + 1..].starts_with(b"\n") || data[i + 1..].starts_with(b"\r\n"))
+    })
+}
+
+/// Returns the position of the body in normalized email data, i.e., the
+/// position of the line terminator that precedes the empty line separating
+/// the header from the body.
+fn body_index_of(data: &[u8]) -> usize {
+    // Data starting with an empty line has an empty header.
+    if data.starts_with(b"\n") || data.starts_with(b"\r\n") {
+        0
+    } else {
+        find_empty_line(data).unwrap_or(data.len())
+    }
 }
 
 /// The method to use to try to guarantee durable email delivery.
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Copy, Clone, Debug)]
 pub enum DeliveryDurability {
     /// Perform both file and directory syncing during delivery.
     /// This is the default delivery durability method.
@@ -190,17 +276,34 @@ pub enum DeliveryDurability {
     FileSyncOnly,
 }
 
-/// A representation of an email.
-pub struct Email {
-    data: Vec<u8>,
+/// A representation of an email whose raw data may be borrowed.
+///
+/// Emails created with [Email::from_slice](type.Email.html#method.from_slice)
+/// borrow their raw data from the caller instead of copying it. All other
+/// constructors create an [Email](type.Email.html), which owns its data.
+pub struct BorrowedEmail<'a> {
+    raw_data: Cow<'a, [u8]>,
+    raw_hash: OnceLock<Vec<u8>>,
     normalized_data: Vec<u8>,
     body_index: usize,
     deliver_path: RwLock<Option<PathBuf>>,
     fields: HashMap<String, Vec<String>>,
+    lossy_fields: Vec<String>,
+    body_parts: Vec<BodyPart>,
+    parts: Vec<PartInfo>,
+    issues: Vec<ValidationIssue>,
+    longest_field: Option<(String, usize)>,
     email_filename_gen: Arc<Mutex<EmailFilenameGenerator>>,
     delivery_durability: DeliveryDurability,
+    delivery_retries: u32,
+    delivery_retry_base_delay: Duration,
+    maildir_backend: Option<Arc<dyn MaildirBackend>>,
+    normalization_settings: NormalizationSettings,
 }
 
+/// A representation of an email.
+pub type Email = BorrowedEmail<'static>;
+
 impl Email {
     /// Creates an `Email` by reading data from stdin.
     ///
@@ -212,12 +315,81 @@ impl Email {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn from_stdin() -> Result<Self> {
-        let stdin = io::stdin();
+        Email::from_reader(io::stdin().lock())
+    }
+
+    /// Creates an `Email` by reading all the data from a reader.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let file = std::fs::File::open("/my/email")?;
+    /// let email = Email::from_reader(std::io::BufReader::new(file))?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self> {
         let mut data = Vec::new();
-        stdin.lock().read_to_end(&mut data)?;
+        reader.read_to_end(&mut data)?;
         Email::from_vec(data)
     }
 
+    /// Creates an `Email` by reading data from stdin, computing the
+    /// [raw_hash](type.Email.html#method.raw_hash) of the data while it's
+    /// being read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin_with_raw_hash()?;
+    /// let hash = email.raw_hash();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_stdin_with_raw_hash() -> Result<Self> {
+        Email::from_reader_with_raw_hash(io::stdin().lock())
+    }
+
+    /// Creates an `Email` by reading all the data from a reader, computing
+    /// the [raw_hash](type.Email.html#method.raw_hash) of the data while
+    /// it's being read, instead of in a separate pass over the data when
+    /// it's first requested. This is useful for large emails that are
+    /// going to be hashed anyway, e.g., for deduplication, but the hashing
+    /// cost is paid even if the hash is never requested.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let file = std::fs::File::open("/my/email")?;
+    /// let email = Email::from_reader_with_raw_hash(std::io::BufReader::new(file))?;
+    /// let hash = email.raw_hash();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_reader_with_raw_hash<R: Read>(reader: R) -> Result<Self> {
+        let mut reader = HashingReader::new(reader);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let email = Email::from_vec(data)?;
+        email.raw_hash.set(reader.finalize()).unwrap();
+        Ok(email)
+    }
+
+    /// Creates an `Email` by reading the data of a file, e.g., an email
+    /// archived in a maildir.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_file("/my/maildir/cur/1234.host:2,S")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Email::from_vec(std::fs::read(path)?)
+    }
+
     /// Creates an `Email` by using data passed in a `Vec<u8>`.
     ///
     /// # Example
@@ -228,23 +400,147 @@ impl Email {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn from_vec(data: Vec<u8>) -> Result<Self> {
-        let (normalized_data, fields) = normalize_email(&data);
-        let body_index = find_empty_line(&normalized_data).unwrap_or(normalized_data.len());
+        Email::from_vec_with_options(data, NormalizationOptions::default())
+    }
+
+    /// Creates an `Email` by using data passed in a `Vec<u8>`, normalizing
+    /// it according to the specified options.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::{Email, NormalizationOptions};
+    /// let options = NormalizationOptions{
+    ///     fold_whitespace_to_space: true,
+    ///     ..Default::default()
+    /// };
+    /// let email = Email::from_vec_with_options(vec![1, 2, 3], options)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_vec_with_options(
+        data: Vec<u8>,
+        options: NormalizationOptions
+    ) -> Result<Self> {
+        BorrowedEmail::from_cow_with_options(Cow::Owned(data), options)
+    }
+
+    /// Creates an `Email` by using data passed in a `Vec<u8>`, and delivers
+    /// its normalized form, according to the specified options, to the
+    /// specified maildir. Returns the email and the path of the delivered
+    /// file in the new/ directory of the maildir.
+    ///
+    /// The normalized data is streamed to the delivered file while it's
+    /// being produced, and is never held in memory as a whole. This is
+    /// useful for archiving the normalized form of huge emails, when
+    /// holding both the raw and the normalized data in memory is
+    /// prohibitive. The delivery is durable, as with
+    /// `DeliveryDurability::FileAndDirSync`.
+    ///
+    /// Since the normalized data isn't available, the returned email has
+    /// empty normalized [data](type.Email.html#method.data),
+    /// [header](type.Email.html#method.header) and
+    /// [body](type.Email.html#method.body), so searching them never
+    /// matches, and it has no [parts](type.Email.html#method.parts). The
+    /// header fields, the raw data and the validation issues are available
+    /// as usual, and other delivery methods deliver the raw data as usual.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::{Email, NormalizationOptions};
+    /// let data = std::fs::read("/my/huge/email")?;
+    /// let (email, path) = Email::from_vec_delivering_normalized(
+    ///     data,
+    ///     NormalizationOptions::default(),
+    ///     "/my/maildir/archive"
+    /// )?;
+    /// eprintln!("Archived {:?} to {}", email.header_field("Subject"), path.display());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_vec_delivering_normalized(
+        data: Vec<u8>,
+        mut options: NormalizationOptions,
+        path: impl AsRef<Path>
+    ) -> Result<(Self, PathBuf)> {
         let email_filename_gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+        let maildir = Maildir::open_or_create(path.as_ref(), email_filename_gen)?;
+        let mut normalized = None;
+        let email_path = maildir.deliver_with_writer(
+            |writer| {
+                normalized = Some(normalize_email_to(&data, &mut options, Some(writer))?);
+                Ok(())
+            },
+            DeliveryDurability::FileAndDirSync
+        )?;
 
-        Ok(
-            Email{
-                data: data,
-                normalized_data: normalized_data,
-                body_index: body_index,
-                deliver_path: RwLock::new(None),
-                fields: fields,
-                email_filename_gen: email_filename_gen,
-                delivery_durability: DeliveryDurability::FileAndDirSync,
-            }
-        )
+        // The ranges of the parts refer to the delivered data.
+        let mut normalized = normalized.unwrap();
+        normalized.body_parts.clear();
+        normalized.parts.clear();
+
+        Ok((BorrowedEmail::from_normalized(Cow::Owned(data), normalized, options), email_path))
+    }
+
+    /// Creates a `BorrowedEmail` by using data passed in a `&[u8]`. The
+    /// raw data of the email is borrowed instead of copied, which is useful
+    /// when processing emails from a memory mapped file or a larger buffer,
+    /// like an mbox.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let data = std::fs::read("/my/email")?;
+    /// let email = Email::from_slice(&data)?;
+    /// assert_eq!(email.raw_data(), &data[..]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_slice(data: &[u8]) -> Result<BorrowedEmail<'_>> {
+        BorrowedEmail::from_cow_with_options(Cow::Borrowed(data), NormalizationOptions::default())
     }
+}
 
+impl<'a> BorrowedEmail<'a> {
+    fn from_cow_with_options(
+        data: Cow<'a, [u8]>,
+        mut options: NormalizationOptions
+    ) -> Result<Self> {
+        let normalized = normalize_email(&data, &mut options)?;
+        Ok(BorrowedEmail::from_normalized(data, normalized, options))
+    }
+
+    fn from_normalized(
+        data: Cow<'a, [u8]>,
+        normalized: NormalizedEmail,
+        normalization_options: NormalizationOptions
+    ) -> Self {
+        let normalized_data = normalized.data;
+        let body_index = body_index_of(&normalized_data);
+        let email_filename_gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+
+        BorrowedEmail{
+            raw_data: data,
+            raw_hash: OnceLock::new(),
+            normalized_data,
+            body_index,
+            deliver_path: RwLock::new(None),
+            fields: normalized.fields,
+            lossy_fields: normalized.lossy_fields,
+            body_parts: normalized.body_parts,
+            parts: normalized.parts,
+            issues: normalized.issues,
+            longest_field: normalized.longest_field,
+            email_filename_gen,
+            delivery_durability: DeliveryDurability::FileAndDirSync,
+            delivery_retries: 0,
+            delivery_retry_base_delay: Duration::ZERO,
+            maildir_backend: None,
+            normalization_settings: normalization_options.into_settings(),
+        }
+    }
+}
+
+impl BorrowedEmail<'_> {
     /// Sets the durability method for delivery of this email.
     ///
     /// # Example
@@ -259,6 +555,72 @@ impl Email {
         self.delivery_durability = delivery_durability;
     }
 
+    /// Sets the number of times failed steps of maildir deliveries of this
+    /// email are retried, if they fail with errors that may be transient,
+    /// starting with a delay of `base_delay` and doubling it after each
+    /// retry. See
+    /// [Maildir::set_delivery_retries](struct.Maildir.html#method.set_delivery_retries)
+    /// for details.
+    ///
+    /// By default failed steps aren't retried.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use mda::Email;
+    /// let mut email = Email::from_stdin()?;
+    /// email.set_delivery_retries(3, Duration::from_millis(100));
+    /// email.deliver_to_maildir("/my/maildir")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_delivery_retries(&mut self, count: u32, base_delay: Duration) {
+        self.delivery_retries = count;
+        self.delivery_retry_base_delay = base_delay;
+    }
+
+    /// Sets the backend that stores the email when it's delivered to a
+    /// maildir, instead of the filesystem. All the maildir delivery methods,
+    /// including delivery by [routing rules](#method.when), deliver through
+    /// the backend.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use mda::{Email, FilesystemMaildirBackend};
+    /// let mut email = Email::from_stdin()?;
+    /// email.set_maildir_backend(Arc::new(FilesystemMaildirBackend));
+    /// email.deliver_to_maildir("/my/maildir")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_maildir_backend(&mut self, backend: Arc<dyn MaildirBackend>) {
+        self.maildir_backend = Some(backend);
+    }
+
+    /// Returns the backend that stores the maildir deliveries of this email.
+    pub(crate) fn maildir_backend(&self) -> &dyn MaildirBackend {
+        self.maildir_backend.as_deref().unwrap_or(&FilesystemMaildirBackend)
+    }
+
+    /// Records the path of the first delivered file of the email, if no
+    /// file has been recorded yet, so that further deliveries can hard-link
+    /// to it.
+    pub(crate) fn set_deliver_path_if_unset(&self, email_path: &Path) {
+        let mut deliver_path = self.deliver_path.write().unwrap();
+        if deliver_path.is_none() {
+            *deliver_path = Some(email_path.to_path_buf());
+        }
+    }
+
+    /// Opens, or creates, a maildir for delivering this email, configured
+    /// with the delivery settings of the email.
+    pub(crate) fn open_maildir(&self, path: &Path) -> Result<Maildir> {
+        let mut maildir = Maildir::open_or_create(path, self.email_filename_gen.clone())?;
+        maildir.set_delivery_retries(self.delivery_retries, self.delivery_retry_base_delay);
+        Ok(maildir)
+    }
+
     /// Returns the value of a header field, if present. If a field occurs
     /// multiple times, the value of the first occurrence is returned.
     ///
@@ -287,7 +649,87 @@ impl Email {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn header_field_all_occurrences(&self, name: &str) -> Option<&Vec<String>> {
-        self.fields.get(&name.to_lowercase()).map(|v| v)
+        self.fields.get(&name.to_lowercase())
+    }
+
+    /// Returns the conventional capitalization of a header field name,
+    /// e.g., `Content-Type` for `content-type`. This is useful for
+    /// presenting the lowercase field names used by this crate.
+    ///
+    /// Each hyphen-separated word of the name is capitalized, except for
+    /// well-known acronyms, which are uppercased, so that names like
+    /// `MIME-Version`, `Message-ID` and `DKIM-Signature` are produced.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mda::Email;
+    /// assert_eq!(Email::header_field_canonical_name("message-id"), "Message-ID");
+    /// assert_eq!(Email::header_field_canonical_name("X-SPAM-STATUS"), "X-Spam-Status");
+    /// ```
+    pub fn header_field_canonical_name(name: &str) -> String {
+        const ACRONYMS: &[&str] = &["arc", "dkim", "id", "mime", "mta", "smtp", "spf"];
+
+        name.trim()
+            .split('-')
+            .map(|word| {
+                let word = word.to_lowercase();
+                if ACRONYMS.contains(&word.as_str()) {
+                    return word.to_uppercase();
+                }
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// Returns the lowercase names of the header fields whose values
+    /// couldn't be converted to UTF-8 without loss, either because they
+    /// contain invalid data, or because they contain MIME encoded-words that
+    /// couldn't be decoded properly. The values of such fields, as returned
+    /// by `header_field`, contain replacement characters or undecoded data.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if !email.lossy_fields().is_empty() {
+    ///     email.deliver_to_maildir("/my/maildir/review")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn lossy_fields(&self) -> &[String] {
+        &self.lossy_fields
+    }
+
+    /// Returns the lowercase name and the length in bytes of the longest
+    /// header field value of the email, or `None` if the email has no
+    /// header fields. The length is measured on the original, unfolded
+    /// value, before any MIME decoding or truncation.
+    ///
+    /// Header fields that are megabytes long, usually folded across
+    /// thousands of lines, are a sign of abusive email. To limit their size,
+    /// use the
+    /// [max_header_field_len](struct.NormalizationOptions.html#structfield.max_header_field_len)
+    /// normalization option.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.longest_header_field().is_some_and(|(_, len)| len > 64 * 1024) {
+    ///     email.deliver_to_maildir("/my/maildir/suspicious")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn longest_header_field(&self) -> Option<(&str, usize)> {
+        self.longest_field.as_ref().map(|(name, len)| (name.as_str(), *len))
     }
 
     /// Delivers the email to the specified maildir. If the maildir isn't
@@ -302,6 +744,10 @@ impl Email {
     /// unless a different durability method is specified with
     /// `set_delivery_durability`.
     ///
+    /// If a [maildir backend](#method.set_maildir_backend) is set, the
+    /// email is delivered through it instead. This applies to all the
+    /// maildir delivery methods.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -311,11 +757,51 @@ impl Email {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn deliver_to_maildir(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        self.deliver_to_maildir_path(path.as_ref()).map(|result| result.path)
+    }
+
+    /// Delivers the email to the specified maildir, like
+    /// [deliver_to_maildir](#method.deliver_to_maildir), and returns
+    /// detailed information about the delivery, e.g., the size of the
+    /// delivered file, which durability method was used and which syncs
+    /// were performed.
+    ///
+    /// The returned [DeliveryResult](struct.DeliveryResult.html) may gain
+    /// more fields in the future without breaking callers, so this method
+    /// is preferable when more than the path of the delivered file is
+    /// needed. If a [maildir backend](#method.set_maildir_backend) is set,
+    /// the result doesn't report any syncs, since the backend is
+    /// responsible for storing the email.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::{DeliveryDurability, Email};
+    /// let email = Email::from_stdin()?;
+    /// let result = email.deliver_to_maildir_detailed("/path/to/maildir/")?;
+    /// if result.durability != DeliveryDurability::FileAndDirSync {
+    ///     eprintln!("Delivered {} without directory sync", result.path.display());
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_maildir_detailed(&self, path: impl AsRef<Path>) -> Result<DeliveryResult> {
         self.deliver_to_maildir_path(path.as_ref())
     }
 
-    fn deliver_to_maildir_path(&self, path: &Path) -> Result<PathBuf> {
-        let maildir = Maildir::open_or_create(&path, self.email_filename_gen.clone())?;
+    fn deliver_to_maildir_path(&self, path: &Path) -> Result<DeliveryResult> {
+        match &self.maildir_backend {
+            Some(backend) => {
+                let email_path = backend.deliver(self, path)?;
+                Ok(DeliveryResult::new(email_path, self.raw_data.len() as u64, self.delivery_durability, false, false))
+            },
+            None => self.deliver_to_maildir_fs(path),
+        }
+    }
+
+    /// Delivers the email to the specified maildir on the filesystem.
+    pub(crate) fn deliver_to_maildir_fs(&self, path: &Path) -> Result<DeliveryResult> {
+        let maildir = self.open_maildir(path)?;
+        let dirs_synced = self.delivery_durability == DeliveryDurability::FileAndDirSync;
 
         if let Some(deliver_path) = self.deliver_path.read().unwrap().as_ref() {
             let email_path_result =
@@ -323,16 +809,166 @@ impl Email {
                     deliver_path,
                     self.delivery_durability);
 
-            if email_path_result.is_ok() {
-                return email_path_result;
+            if let Ok(email_path) = email_path_result {
+                // The data was synced when it was first delivered.
+                return Ok(DeliveryResult::new(email_path, self.raw_data.len() as u64, self.delivery_durability, false, dirs_synced));
             }
         }
 
-        let email_path = maildir.deliver(&self.data, self.delivery_durability)?;
+        let email_path = maildir.deliver(&self.raw_data, self.delivery_durability)?;
 
         *self.deliver_path.write().unwrap() = Some(email_path.clone());
 
-        Ok(email_path)
+        Ok(DeliveryResult::new(email_path, self.raw_data.len() as u64, self.delivery_durability, true, dirs_synced))
+    }
+
+    /// Delivers the email to the specified maildir, compressing the email
+    /// data with the specified algorithm. If the maildir isn't present it is
+    /// created.
+    ///
+    /// The delivered file is written atomically and durably like with
+    /// [deliver_to_maildir](#method.deliver_to_maildir). Its filename
+    /// contains the suffix of the compression algorithm (e.g., `.gz`), so
+    /// that compressed and uncompressed emails can be told apart, followed
+    /// by the `S=<size>` maildir extension with the uncompressed size of the
+    /// email, e.g., `1234.host.gz,S=2048`, as used by Dovecot. Since the
+    /// data differs from the uncompressed email, the file is never
+    /// hard-linked to other deliveries.
+    ///
+    /// The compression algorithms are available only if the respective
+    /// features (`gzip`, `zstd`) are enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::{Compression, Email};
+    /// let email = Email::from_stdin()?;
+    /// email.deliver_to_maildir_compressed("/path/to/archive/", Compression::None)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_maildir_compressed(
+        &self,
+        path: impl AsRef<Path>,
+        compression: Compression
+    ) -> Result<PathBuf> {
+        self.maildir_backend().deliver_compressed(self, path.as_ref(), compression)
+    }
+
+    /// Delivers the email to the specified maildir at most once for the
+    /// specified identity, e.g., the Message-ID or envelope id of the email.
+    /// If the maildir isn't present it is created.
+    ///
+    /// The filename of the delivered email deterministically encodes the
+    /// identity, so that if the delivery is retried, e.g., because the
+    /// process crashed before reporting success to the MTA, the retry is
+    /// detected and skipped. See
+    /// [Maildir::deliver_once](struct.Maildir.html#method.deliver_once).
+    ///
+    /// Returns the path of the delivered email file, or `None` if an email
+    /// with the same identity has already been delivered.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let message_id = email.header_field("Message-ID").unwrap_or("").trim().to_string();
+    /// email.deliver_to_maildir_once("/path/to/maildir/", &message_id)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_maildir_once(
+        &self,
+        path: impl AsRef<Path>,
+        identity: &str
+    ) -> Result<Option<PathBuf>> {
+        self.maildir_backend().deliver_once(self, path.as_ref(), identity)
+    }
+
+    /// Delivers the email to the specified maildir, using the specified
+    /// base as the filename instead of a generated unique name, e.g., to
+    /// correlate the filename with an identifier assigned by an external
+    /// system. If the maildir isn't present it is created.
+    ///
+    /// The delivered file is written atomically and durably like with
+    /// [deliver_to_maildir](#method.deliver_to_maildir). The base is
+    /// sanitized with
+    /// [sanitize_maildir_component](fn.sanitize_maildir_component.html), and
+    /// the `:` character is also escaped. Fails if the base is empty or
+    /// starts with `.`, or if an email with the same filename already exists
+    /// in the maildir. See
+    /// [Maildir::deliver_named](struct.Maildir.html#method.deliver_named).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// email.deliver_to_maildir_named("/path/to/maildir/", "ticket-1234")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_maildir_named(
+        &self,
+        path: impl AsRef<Path>,
+        base: &str
+    ) -> Result<PathBuf> {
+        self.maildir_backend().deliver_named(self, path.as_ref(), base)
+    }
+
+    /// Delivers the email to the specified maildir, marked with the
+    /// specified flags, e.g., to file a copy of an email as already seen.
+    /// If the maildir isn't present it is created.
+    ///
+    /// The email is delivered to the cur/ directory, with the letters of the
+    /// flags in ASCII order in the `:2,` info section of its filename, e.g.,
+    /// `1546300800.1234_0.host:2,FS`. If no flags are specified, the email
+    /// is delivered to the new/ directory, like with
+    /// [deliver_to_maildir](#method.deliver_to_maildir).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, MaildirFlag};
+    /// let email = Email::from_stdin()?;
+    /// email.deliver_to_maildir_with_flags("/path/to/maildir/", &[MaildirFlag::Seen])?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_maildir_with_flags(
+        &self,
+        path: impl AsRef<Path>,
+        flags: &[MaildirFlag]
+    ) -> Result<PathBuf> {
+        if flags.is_empty() {
+            return self.deliver_to_maildir(path);
+        }
+
+        self.maildir_backend().deliver_with_flags(self, path.as_ref(), flags)
+    }
+
+    /// Delivers the email to the specified maildir, tagged with the
+    /// specified Dovecot keywords. If the maildir isn't present it is
+    /// created.
+    ///
+    /// The keywords are registered in the `dovecot-keywords` file of the
+    /// maildir, if needed, and the email is delivered to the cur/ directory
+    /// with the letters referencing the keywords in the `:2,` info section
+    /// of its filename, e.g., `1546300800.1234_0.host:2,ab`. Dovecot exposes
+    /// these keywords as IMAP keywords. A maildir can hold up to 26 distinct
+    /// keywords.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// email.deliver_to_maildir_with_keywords("/path/to/maildir/", &["work", "$Label1"])?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_maildir_with_keywords(
+        &self,
+        path: impl AsRef<Path>,
+        keywords: &[&str]
+    ) -> Result<PathBuf> {
+        self.maildir_backend().deliver_with_keywords(self, path.as_ref(), keywords)
     }
 
     /// Returns whether the email has been delivered to at least one maildir.
@@ -362,12 +998,341 @@ impl Email {
     }
 
     /// Provides access to the normalized email body byte data.
+    ///
+    /// For multipart emails, the body data includes the boundary lines,
+    /// the headers of the parts, and the [preamble](#method.preamble) and
+    /// [epilogue](#method.epilogue) of the email, which MIME readers
+    /// ignore. Use [body_text](#method.body_text) to access only the text
+    /// content of the email.
     pub fn body(&self) -> &[u8] {
         &self.normalized_data[self.body_index..]
     }
 
+    /// Returns the human-readable text of the email body as a `String`.
+    ///
+    /// The text consists of the decoded contents of the text parts of the
+    /// email, in the order they appear, excluding any non-text parts (e.g.,
+    /// attachments), multi-part boundaries, preambles and epilogues. Of the
+    /// alternatives in a `multipart/alternative` container, only the
+    /// `text/plain` one is used, or the `text/html` one if there is no
+    /// plain text alternative. HTML is converted to plain text, by removing
+    /// tags, decoding character references and collapsing whitespace.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.body_text().contains("FREE BEER") {
+    ///     email.deliver_to_maildir("/my/spam/path")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn body_text(&self) -> String {
+        self.readable_body_text()
+    }
+
+    /// Returns whether the decoded text of the email body, as returned by
+    /// [body_text](#method.body_text), is empty or contains only
+    /// whitespace. Multi-part boundaries, preambles and non-text parts
+    /// (e.g., attachments) are not considered to be body text.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.body_is_empty() {
+    ///     email.deliver_to_maildir("/my/maildir/empty")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn body_is_empty(&self) -> bool {
+        self.body_text().trim().is_empty()
+    }
+
+    /// Returns the lowercase charset that the first text part of the email
+    /// was declared to be in, if any.
+    ///
+    /// Since normalized text data is always UTF-8, this is only useful for
+    /// informational purposes, e.g., logging, or deciding how to re-encode
+    /// text in replies.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let charset = email.primary_charset().unwrap_or("us-ascii");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn primary_charset(&self) -> Option<&str> {
+        self.body_parts.iter().find(|p| p.is_text())?.charset.as_deref()
+    }
+
     /// Provides access to the raw (non-normalized) email byte data.
     pub fn raw_data(&self) -> &[u8] {
-        &self.data
+        &self.raw_data
+    }
+
+    /// Returns the SHA-256 hash of the raw (non-normalized) email data,
+    /// e.g., for detecting duplicate emails.
+    ///
+    /// The hash is computed when it's first requested, and is cached,
+    /// unless the email was created with
+    /// [from_reader_with_raw_hash](type.Email.html#method.from_reader_with_raw_hash)
+    /// or [from_stdin_with_raw_hash](type.Email.html#method.from_stdin_with_raw_hash),
+    /// which compute it while reading the data.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let hash: String = email.raw_hash().iter().map(|b| format!("{:02x}", b)).collect();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn raw_hash(&self) -> &[u8] {
+        self.raw_hash.get_or_init(|| Sha256::digest(&self.raw_data).to_vec())
+    }
+
+    /// Returns the size of the raw email data with all line endings
+    /// canonicalized to CRLF, which is how SMTP and IMAP servers count
+    /// email sizes, e.g., for quota enforcement. For emails using LF line
+    /// endings this is larger than the length of the raw data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use mda::Email;
+    /// let email = Email::from_vec(b"Subject: Hi\n\nBody\r\n".to_vec())?;
+    /// assert_eq!(email.smtp_size(), 21);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn smtp_size(&self) -> usize {
+        util::crlf_len(&self.raw_data)
+    }
+
+    /// Consumes the email, returning the raw (non-normalized) email byte
+    /// data. The data is returned without copying only if the email owns
+    /// it; the data of an email that borrows it is copied.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let raw_data: Vec<u8> = email.into_raw();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn into_raw(self) -> Vec<u8> {
+        self.raw_data.into_owned()
+    }
+
+    /// Consumes the email, returning the normalized email byte data without
+    /// copying it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let normalized_data: Vec<u8> = email.into_normalized();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn into_normalized(self) -> Vec<u8> {
+        self.normalized_data
+    }
+
+    /// Returns an iterator over the lines of the normalized email byte data.
+    /// Each line includes its line terminator.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let long_lines = email.data_lines().filter(|l| l.len() > 998).count();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn data_lines(&self) -> SliceLines<'_> {
+        SliceLines::new(self.data())
+    }
+
+    /// Returns an iterator over the lines of the normalized email header
+    /// byte data. Each line includes its line terminator.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// for line in email.header_lines() {
+    ///     // process line
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn header_lines(&self) -> SliceLines<'_> {
+        SliceLines::new(self.header())
+    }
+
+    /// Returns an iterator over the logical lines of the raw
+    /// (non-normalized) email header byte data, i.e., the header fields
+    /// with their exact original bytes. A line folded over multiple physical
+    /// lines is returned as a single item including all its continuation
+    /// lines, and each item includes its line terminators.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let mut new_data: Vec<u8> = Vec::new();
+    /// for field in email.raw_header_lines() {
+    ///     if !field.starts_with(b"X-Spam-") {
+    ///         new_data.extend(field);
+    ///     }
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn raw_header_lines(&self) -> impl Iterator<Item=&[u8]> {
+        let header = &self.raw_data[..self.raw_header_end()];
+        let mut pos = 0;
+
+        std::iter::from_fn(move || {
+            if pos >= header.len() {
+                return None;
+            }
+
+            let start = pos;
+            for line in SliceLines::new(&header[pos..]) {
+                // Lines beginning with whitespace are continuation lines.
+                if pos > start && line[0] != b' ' && line[0] != b'\t' {
+                    break;
+                }
+                pos += line.len();
+            }
+
+            Some(&header[start..pos])
+        })
+    }
+
+    /// Returns a new email consisting of only the header of this email,
+    /// followed by the empty line separating the header from the body.
+    ///
+    /// The raw header data is copied unchanged, and the header fields of the
+    /// new email are the same as the header fields of this email. The body
+    /// of the new email contains no data apart from the separating empty
+    /// line. The new email has the same delivery settings (durability,
+    /// retries and maildir backend) and normalization options as this
+    /// email, apart from any part transform and deadline.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// email.header_only_copy().deliver_to_maildir("/my/maildir/header-archive")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn header_only_copy(&self) -> Email {
+        let header_end = self.raw_header_end();
+        let mut data = self.raw_data[..header_end].to_vec();
+        if !data.is_empty() && !data.ends_with(b"\n") {
+            data.push(b'\n');
+        }
+        if self.raw_data[header_end..].starts_with(b"\r\n") {
+            data.extend(b"\r\n");
+        } else {
+            data.push(b'\n');
+        }
+
+        // Normalization can only fail if a deadline is set.
+        let mut email = Email::from_vec_with_options(data, self.normalization_settings.to_options())
+            .expect("normalization without deadline failed");
+        email.fields = self.fields.clone();
+        email.lossy_fields = self.lossy_fields.clone();
+        email.email_filename_gen = self.email_filename_gen.clone();
+        email.delivery_durability = self.delivery_durability;
+        email.delivery_retries = self.delivery_retries;
+        email.delivery_retry_base_delay = self.delivery_retry_base_delay;
+        email.maildir_backend = self.maildir_backend.clone();
+        email
+    }
+
+    /// Returns the raw (not decoded) value of the first occurrence of a
+    /// header field, with any folding line breaks removed.
+    fn raw_header_field(&self, name: &str) -> Option<String> {
+        self.raw_header_lines().find_map(|line| {
+            let colon = line.iter().position(|c| *c == b':')?;
+            if !line[..colon].trim_ascii().eq_ignore_ascii_case(name.as_bytes()) {
+                return None;
+            }
+            let value: Vec<u8> = line[colon + 1..].iter()
+                .filter(|c| **c != b'\r' && **c != b'\n')
+                .cloned()
+                .collect();
+            Some(String::from_utf8_lossy(&value).into_owned())
+        })
+    }
+
+    /// Provides access to the raw (non-normalized) email body byte data,
+    /// i.e., the raw data after the empty line separating the header from
+    /// the body.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let body_len = email.raw_body().len();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn raw_body(&self) -> &[u8] {
+        let rest = &self.raw_data[self.raw_header_end()..];
+        if rest.starts_with(b"\r\n") {
+            &rest[2..]
+        } else if rest.starts_with(b"\n") {
+            &rest[1..]
+        } else {
+            rest
+        }
+    }
+
+    /// Returns the end offset of the header in the raw data, including the
+    /// line terminator of the last header field.
+    fn raw_header_end(&self) -> usize {
+        let raw_data = &self.raw_data;
+        if raw_data.starts_with(b"\n") || raw_data.starts_with(b"\r\n") {
+            0
+        } else {
+            find_empty_line(raw_data).map_or(raw_data.len(), |i| i + 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn header_only_copy_keeps_delivery_settings() {
+        let options = NormalizationOptions{
+            fold_whitespace_to_space: true,
+            ..Default::default()
+        };
+        let backend: Arc<dyn MaildirBackend> = Arc::new(FilesystemMaildirBackend);
+        let mut email = Email::from_vec_with_options(b"Subject: Hi\n\nBody\n".to_vec(), options).unwrap();
+        email.set_delivery_durability(DeliveryDurability::FileSyncOnly);
+        email.set_delivery_retries(3, Duration::from_millis(100));
+        email.set_maildir_backend(backend.clone());
+
+        let copy = email.header_only_copy();
+
+        assert_eq!(copy.delivery_durability, DeliveryDurability::FileSyncOnly);
+        assert_eq!(copy.delivery_retries, 3);
+        assert_eq!(copy.delivery_retry_base_delay, Duration::from_millis(100));
+        assert!(Arc::ptr_eq(copy.maildir_backend.as_ref().unwrap(), &backend));
+        assert!(copy.normalization_settings.fold_whitespace_to_space);
     }
 }
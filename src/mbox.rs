@@ -0,0 +1,382 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reading of emails from, and delivery of emails to, mbox files.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::prelude::*;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{BorrowedEmail, DeliveryDurability, Email, Result};
+use crate::date::{DateTime, MONTHS};
+use crate::util::{is_empty_line, SliceLines};
+
+/// The variant of the mbox format, which determines how `From ` lines in
+/// email bodies are escaped.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MboxFormat {
+    /// Body lines starting with `From ` are escaped as `>From `. Lines that
+    /// already started with `>From ` are not escaped, so the escaping is
+    /// not reversible for them.
+    Mboxo,
+    /// Body lines matching `^>*From ` are escaped by prepending a `>`,
+    /// making the escaping fully reversible.
+    Mboxrd,
+}
+
+fn is_separator_line(line: &[u8]) -> bool {
+    line.starts_with(b"From ")
+}
+
+fn unescape_line(line: &[u8], format: MboxFormat) -> &[u8] {
+    let quotes = line.iter().take_while(|c| **c == b'>').count();
+    let unescape = match format {
+        MboxFormat::Mboxo => quotes == 1,
+        MboxFormat::Mboxrd => quotes >= 1,
+    };
+
+    if unescape && line[quotes..].starts_with(b"From ") {
+        &line[1..]
+    } else {
+        line
+    }
+}
+
+/// Splits mbox data into the data of the contained emails, removing the
+/// `From ` separator lines and un-escaping body lines according to the
+/// specified mbox format variant.
+///
+/// A `From ` line is treated as a separator only if it's at the start of
+/// the data or follows an empty line. The empty line preceding each
+/// separator is considered part of the mbox format and is removed.
+///
+/// # Example
+///
+/// ```
+/// use mda::{split_mbox, MboxFormat};
+/// let mbox = b"From a@example.com Mon Jan 1 00:00:00 2019\nSubject: 1\n\n>From me\n\n\
+///              From b@example.com Mon Jan 1 00:00:00 2019\nSubject: 2\n\nBody\n";
+/// let emails = split_mbox(mbox, MboxFormat::Mboxrd);
+/// assert_eq!(emails, [&b"Subject: 1\n\nFrom me\n"[..], &b"Subject: 2\n\nBody\n"[..]]);
+/// ```
+pub fn split_mbox(data: &[u8], format: MboxFormat) -> Vec<Vec<u8>> {
+    let mut emails = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    // An empty line is held back until we know whether it precedes a
+    // separator line.
+    let mut pending_empty_line: Option<&[u8]> = None;
+    let mut prev_line_empty = true;
+
+    for line in SliceLines::new(data) {
+        let line_empty = is_empty_line(line);
+
+        if is_separator_line(line) && prev_line_empty {
+            if let Some(email) = current.take() {
+                emails.push(email);
+            }
+            current = Some(Vec::new());
+            pending_empty_line = None;
+            prev_line_empty = false;
+            continue;
+        }
+
+        prev_line_empty = line_empty;
+
+        // Data before the first separator line doesn't belong to any email.
+        let email = match current.as_mut() {
+            Some(email) => email,
+            None => continue,
+        };
+
+        if let Some(empty) = pending_empty_line.take() {
+            email.extend(empty);
+        }
+
+        if line_empty {
+            pending_empty_line = Some(line);
+        } else {
+            email.extend(unescape_line(line, format));
+        }
+    }
+
+    // A trailing empty line at the end of the mbox is the padding after the
+    // last email, so it's dropped.
+    if let Some(email) = current.take() {
+        emails.push(email);
+    }
+
+    emails
+}
+
+/// Reads all the emails contained in an mbox file, using the specified
+/// mbox format variant to un-escape `From ` lines.
+///
+/// # Example
+///
+/// ```no_run
+/// use mda::{read_mbox, MboxFormat};
+/// for email in read_mbox("/my/mbox/path", MboxFormat::Mboxrd)? {
+///     email.deliver_to_maildir("/my/maildir/path")?;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn read_mbox(path: impl AsRef<Path>, format: MboxFormat) -> Result<Vec<Email>> {
+    let data = fs::read(path)?;
+    split_mbox(&data, format).into_iter().map(Email::from_vec).collect()
+}
+
+impl Email {
+    /// Removes a leading mbox `From ` separator line from email data, if
+    /// present, returning the data unchanged otherwise.
+    ///
+    /// Emails copied out of mbox files often keep their separator line,
+    /// which isn't a valid header field, and would otherwise be parsed as a
+    /// bogus first header field. Use this function to strip the line before
+    /// creating an `Email` from such data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use mda::Email;
+    /// let data = b"From me@example.com Mon Jan 1 00:00:00 2019\nSubject: Hi\n\nBody\n".to_vec();
+    /// assert_eq!(Email::strip_mbox_from_line(data), b"Subject: Hi\n\nBody\n");
+    /// ```
+    pub fn strip_mbox_from_line(mut data: Vec<u8>) -> Vec<u8> {
+        if is_separator_line(&data) {
+            let end = data.iter().position(|&b| b == b'\n').map_or(data.len(), |pos| pos + 1);
+            data.drain(..end);
+        }
+        data
+    }
+}
+
+/// The format of the date in the `From ` separator line of delivered emails.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum MboxDateFormat {
+    /// The `asctime` format, e.g., `Mon Jan  7 10:00:00 2019`, as
+    /// recommended by RFC 4155. This is the default.
+    Asctime,
+    /// The RFC 5322 format, e.g., `Mon, 07 Jan 2019 10:00:00 +0000`.
+    Rfc5322,
+}
+
+/// Options controlling the delivery of emails to mbox files.
+///
+/// # Example
+///
+/// ```no_run
+/// use mda::{Email, MboxFormat, MboxOptions};
+/// let email = Email::from_stdin()?;
+/// let options = MboxOptions{
+///     sender: Some("MAILER-DAEMON".to_string()),
+///     format: MboxFormat::Mboxo,
+///     ..Default::default()
+/// };
+/// email.deliver_to_mbox_with_options("/my/mbox/path", &options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct MboxOptions {
+    /// The envelope sender to use in the `From ` separator line. If not
+    /// set, the address in the Return-Path header field is used, or
+    /// `MAILER-DAEMON` if there is no usable Return-Path address.
+    pub sender: Option<String>,
+    /// The format of the delivery date in the `From ` separator line.
+    /// Defaults to `MboxDateFormat::Asctime`.
+    pub date_format: MboxDateFormat,
+    /// The mbox format variant, which determines how `From ` lines in the
+    /// email data are escaped. Defaults to `MboxFormat::Mboxrd`.
+    pub format: MboxFormat,
+}
+
+impl Default for MboxOptions {
+    fn default() -> Self {
+        MboxOptions{
+            sender: None,
+            date_format: MboxDateFormat::Asctime,
+            format: MboxFormat::Mboxrd,
+        }
+    }
+}
+
+fn escape_line(line: &[u8], format: MboxFormat) -> bool {
+    match format {
+        MboxFormat::Mboxo => line.starts_with(b"From "),
+        MboxFormat::Mboxrd => {
+            let quotes = line.iter().take_while(|c| **c == b'>').count();
+            line[quotes..].starts_with(b"From ")
+        },
+    }
+}
+
+/// Formats the `From ` separator line for an email delivered at the
+/// specified Unix time.
+fn from_line(sender: &str, unix_time: u64, date_format: MboxDateFormat) -> String {
+    let date = DateTime::from_unix_time(unix_time);
+    // The Unix epoch was a Thursday.
+    let weekday = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"][(unix_time / 86400 % 7) as usize];
+    let month = MONTHS[date.month as usize - 1];
+
+    match date_format {
+        MboxDateFormat::Asctime => format!(
+            "From {} {} {} {:2} {:02}:{:02}:{:02} {}\n",
+            sender, weekday, month, date.day, date.hour, date.minute, date.second, date.year),
+        MboxDateFormat::Rfc5322 => format!(
+            "From {} {}, {:02} {} {} {:02}:{:02}:{:02} +0000\n",
+            sender, weekday, date.day, month, date.year, date.hour, date.minute, date.second),
+    }
+}
+
+/// Returns the envelope sender address from a Return-Path field value,
+/// if it's usable in a `From ` separator line.
+fn sender_from_return_path(value: &str) -> Option<&str> {
+    let sender = value.trim().trim_start_matches('<').trim_end_matches('>').trim();
+    if sender.is_empty() || sender.contains(char::is_whitespace) {
+        None
+    } else {
+        Some(sender)
+    }
+}
+
+/// Builds the mbox representation of email data, i.e., the `From ` line
+/// followed by the escaped data and a terminating empty line.
+fn mbox_entry(from_line: &str, data: &[u8], format: MboxFormat) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(from_line.len() + data.len() + 64);
+    entry.extend(from_line.as_bytes());
+
+    for line in SliceLines::new(data) {
+        if escape_line(line, format) {
+            entry.push(b'>');
+        }
+        entry.extend(line);
+    }
+
+    if !entry.ends_with(b"\n") {
+        entry.push(b'\n');
+    }
+    entry.push(b'\n');
+    entry
+}
+
+/// Locks a file exclusively, waiting until the lock is available. The
+/// lock is released when the file is closed.
+fn lock_file(file: &File) -> Result<()> {
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err.into());
+        }
+    }
+}
+
+/// Appends an entry to an mbox file, making sure it's separated by an
+/// empty line from any previous email data.
+fn append_entry(path: &Path, entry: &[u8], durability: DeliveryDurability) -> Result<()> {
+    let mut file = OpenOptions::new().read(true).append(true).create(true).mode(0o600).open(path)?;
+    lock_file(&file)?;
+
+    let initial_len = file.metadata()?.len();
+    let mut tail = [0; 2];
+    let separator: &[u8] = match initial_len {
+        0 => b"",
+        1 => {
+            file.read_exact_at(&mut tail[..1], 0)?;
+            if tail[0] == b'\n' { b"" } else { b"\n\n" }
+        },
+        _ => {
+            file.read_exact_at(&mut tail, initial_len - 2)?;
+            match tail {
+                [b'\n', b'\n'] => b"",
+                [_, b'\n'] => b"\n",
+                _ => b"\n\n",
+            }
+        },
+    };
+
+    let result = file.write_all(separator)
+        .and_then(|_| file.write_all(entry))
+        .and_then(|_| file.sync_all());
+
+    // Don't leave a partially written email behind, since it would corrupt
+    // the next email in the mbox.
+    if let Err(err) = result {
+        let _ = file.set_len(initial_len);
+        return Err(err.into());
+    }
+
+    if initial_len == 0 && durability == DeliveryDurability::FileAndDirSync {
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+impl BorrowedEmail<'_> {
+    /// Delivers the email to the specified mbox file, using the default
+    /// [MboxOptions](struct.MboxOptions.html). If the mbox file isn't
+    /// present it is created.
+    ///
+    /// The original (non-normalized) email data is appended to the mbox,
+    /// preceded by a `From ` separator line. The mbox file is locked with
+    /// `flock` during delivery, so concurrent deliveries don't corrupt it.
+    /// The mbox file is synced according to the email's delivery
+    /// durability setting.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// email.deliver_to_mbox("/my/mbox/path")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_mbox(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.deliver_to_mbox_with_options(path, &MboxOptions::default())
+    }
+
+    /// Delivers the email to the specified mbox file, using the specified
+    /// options for the `From ` separator line and the escaping of `From `
+    /// lines in the email data. If the mbox file isn't present it is
+    /// created.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, MboxDateFormat, MboxOptions};
+    /// let email = Email::from_stdin()?;
+    /// let options = MboxOptions{
+    ///     date_format: MboxDateFormat::Rfc5322,
+    ///     ..Default::default()
+    /// };
+    /// email.deliver_to_mbox_with_options("/my/mbox/path", &options)?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deliver_to_mbox_with_options(
+        &self,
+        path: impl AsRef<Path>,
+        options: &MboxOptions
+    ) -> Result<()> {
+        let sender = match &options.sender {
+            Some(sender) => sender.as_str(),
+            None => self.header_field("Return-Path")
+                        .and_then(sender_from_return_path)
+                        .unwrap_or("MAILER-DAEMON"),
+        };
+        let unix_time = SystemTime::now().duration_since(UNIX_EPOCH).map_err(io::Error::other)?.as_secs();
+        let from_line = from_line(sender, unix_time, options.date_format);
+        let entry = mbox_entry(&from_line, &self.raw_data, options.format);
+
+        append_entry(path.as_ref(), &entry, self.delivery_durability)
+    }
+}
@@ -17,24 +17,197 @@
 
 use ::regex::bytes::{RegexBuilder, Regex, Captures};
 use std::collections::HashMap;
+use std::io;
+use std::io::Write;
 use std::iter::Peekable;
+use std::ops::Range;
+use std::time::Instant;
 use memchr::{memchr, memchr_iter};
 use charset::Charset;
 use std::borrow::Cow;
 use lazy_static::lazy_static;
 
 use crate::decode::{base64_decode_into_buf, qp_decode_into_buf};
+use crate::parts::PartInfo;
+use crate::{MdaError, Result};
+use crate::util::{is_empty_line, is_whitespace_only_line, SliceLines};
+use crate::validate::ValidationIssue;
+
+/// Options controlling the normalization of email data.
+///
+/// All boolean options are disabled by default, in which case normalization
+/// behaves as described in the [crate documentation](index.html).
+///
+/// # Example
+///
+/// ```no_run
+/// use mda::{Email, NormalizationOptions};
+/// let options = NormalizationOptions{
+///     fold_whitespace_to_space: true,
+///     ..Default::default()
+/// };
+/// let email = Email::from_vec_with_options(vec![97, 98, 99], options)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct NormalizationOptions {
+    /// Convert tabs and runs of folding whitespace in header fields to a
+    /// single space.
+    pub fold_whitespace_to_space: bool,
+    /// The maximum nesting depth of multipart parts, with the top level of
+    /// the email being at depth 1. The boundaries of multiparts that would
+    /// create deeper parts are ignored, and their content is treated as
+    /// plain body data. Defaults to 100.
+    pub max_part_depth: usize,
+    /// Rewrite the Content-Transfer-Encoding and Content-Type charset header
+    /// fields of text parts that were decoded without errors to `8bit` and
+    /// `utf-8` respectively, so that the normalized data is a consistent
+    /// email on its own. The raw email data is not affected.
+    pub rewrite_encoding_fields: bool,
+    /// A callback invoked with the data of each part, after any decoding
+    /// and before it's added to the normalized data. The callback can
+    /// modify the data in place, e.g., to redact sensitive information.
+    pub part_transform: Option<PartTransform>,
+    /// The point in time by which normalization must complete. The
+    /// deadline is checked periodically while parsing, and if it's
+    /// exceeded, normalization is aborted and email creation fails with an
+    /// `std::io::Error` of kind `TimedOut`. Defaults to no deadline.
+    pub deadline: Option<Instant>,
+    /// Use the top-level Content-Length header field, if present, to
+    /// delimit the body, ignoring any data after the declared body length.
+    /// This is useful for non-standard formats which concatenate emails
+    /// with length prefixes. Only the normalized data is affected; the raw
+    /// email data is kept complete.
+    pub use_content_length: bool,
+    /// Additional charset aliases, mapping lowercase charset labels to the
+    /// labels of charsets to decode them as, e.g., `x-mac-cyrillic-custom`
+    /// to `x-mac-cyrillic`. These aliases take precedence over the
+    /// standard labels and over the built-in aliases for common
+    /// nonstandard labels, like `cp-1256` or `ks_c_5601`.
+    pub charset_aliases: HashMap<String, String>,
+    /// The maximum length in bytes of an unfolded header field value.
+    /// Longer values are truncated, protecting later processing from
+    /// header fields that are megabytes long, and a
+    /// [HeaderFieldTruncated](enum.ValidationIssue.html#variant.HeaderFieldTruncated)
+    /// issue is reported. Defaults to no limit.
+    pub max_header_field_len: Option<usize>,
+    /// Copy the body data verbatim to the normalized data, including the
+    /// headers of any parts, without decoding it from its content transfer
+    /// encoding or charset, so that the normalized body is identical to the
+    /// raw body, e.g., for DKIM body hashing. The top-level header is still
+    /// normalized, and the fields of all headers are still parsed. The
+    /// [part_transform](#structfield.part_transform) is not invoked, and
+    /// [rewrite_encoding_fields](#structfield.rewrite_encoding_fields) has no
+    /// effect.
+    pub body_passthrough: bool,
+    /// Remove the blank (i.e., empty or whitespace-only) lines at the end
+    /// of the decoded data of each text part, keeping the line terminator
+    /// of the last non-blank line. This is useful for tools that want
+    /// clean text, since decoded data often ends with spurious blank lines,
+    /// which affect, e.g., the body previews and hashes. Non-text parts and
+    /// the raw email data are not affected.
+    pub trim_trailing_blank_lines: bool,
+}
+
+/// A callback that transforms the data of a part during normalization.
+pub type PartTransform = Box<dyn FnMut(&PartContext, &mut Vec<u8>)>;
+
+/// Information about the part whose data is passed to the
+/// [part_transform](struct.NormalizationOptions.html#structfield.part_transform)
+/// callback.
+#[derive(Debug, Clone, Copy)]
+pub struct PartContext<'a> {
+    /// The lowercase content type of the part, if specified.
+    pub content_type: Option<&'a str>,
+    /// The lowercase charset of the part, if specified.
+    pub charset: Option<&'a str>,
+}
+
+impl Default for NormalizationOptions {
+    fn default() -> Self {
+        NormalizationOptions{
+            fold_whitespace_to_space: false,
+            max_part_depth: 100,
+            rewrite_encoding_fields: false,
+            part_transform: None,
+            deadline: None,
+            use_content_length: false,
+            charset_aliases: HashMap::new(),
+            max_header_field_len: None,
+            body_passthrough: false,
+            trim_trailing_blank_lines: false,
+        }
+    }
+}
+
+impl NormalizationOptions {
+    /// Returns the settings of the options, without the part transform
+    /// and the deadline.
+    pub(crate) fn into_settings(self) -> NormalizationSettings {
+        NormalizationSettings{
+            fold_whitespace_to_space: self.fold_whitespace_to_space,
+            max_part_depth: self.max_part_depth,
+            rewrite_encoding_fields: self.rewrite_encoding_fields,
+            use_content_length: self.use_content_length,
+            charset_aliases: self.charset_aliases,
+            max_header_field_len: self.max_header_field_len,
+            body_passthrough: self.body_passthrough,
+            trim_trailing_blank_lines: self.trim_trailing_blank_lines,
+        }
+    }
+}
+
+/// The normalization options an email keeps after its creation, to
+/// normalize data again with the same settings, e.g., after header edits.
+/// The part transform and the deadline apply only to the creation of the
+/// email, so they are not kept. This also keeps emails `Send` and `Sync`,
+/// since part transforms don't have to be.
+#[derive(Clone)]
+pub(crate) struct NormalizationSettings {
+    pub(crate) fold_whitespace_to_space: bool,
+    pub(crate) max_part_depth: usize,
+    pub(crate) rewrite_encoding_fields: bool,
+    pub(crate) use_content_length: bool,
+    pub(crate) charset_aliases: HashMap<String, String>,
+    pub(crate) max_header_field_len: Option<usize>,
+    pub(crate) body_passthrough: bool,
+    pub(crate) trim_trailing_blank_lines: bool,
+}
+
+impl NormalizationSettings {
+    /// Returns normalization options with these settings, and without a
+    /// part transform or deadline.
+    pub(crate) fn to_options(&self) -> NormalizationOptions {
+        NormalizationOptions{
+            fold_whitespace_to_space: self.fold_whitespace_to_space,
+            max_part_depth: self.max_part_depth,
+            rewrite_encoding_fields: self.rewrite_encoding_fields,
+            part_transform: None,
+            deadline: None,
+            use_content_length: self.use_content_length,
+            charset_aliases: self.charset_aliases.clone(),
+            max_header_field_len: self.max_header_field_len,
+            body_passthrough: self.body_passthrough,
+            trim_trailing_blank_lines: self.trim_trailing_blank_lines,
+        }
+    }
+}
 
 /// An element recognized by the [EmailParser](struct.EmailParser.html).
-enum Element {
-    HeaderField{data: Vec<u8>},
+///
+/// Body and verbatim elements borrow their data from the parsed buffer,
+/// so that large parts are not copied before being decoded.
+pub(crate) enum Element<'a> {
+    /// An unfolded header field, along with its original, possibly folded,
+    /// lines.
+    HeaderField{data: Vec<u8>, raw: &'a [u8]},
     Body{
-        data: Vec<u8>,
+        data: &'a [u8],
         encoding: Option<String>,
         content_type: Option<String>,
-        charset: Option<String>
+        charset: Option<String>,
+        multipart: bool,
     },
-    Verbatim{data: Vec<u8>},
+    Verbatim{data: &'a [u8]},
 }
 
 /// Information about a part in a multi-part email message.
@@ -44,6 +217,8 @@ struct Part {
     content_type: Option<String>,
     charset: Option<String>,
     subpart_boundary: Option<Vec<u8>>,
+    // Whether this is a multipart container.
+    multipart: bool,
 }
 
 impl Part {
@@ -53,35 +228,7 @@ impl Part {
             content_type: None,
             charset: None,
             subpart_boundary: None,
-        }
-    }
-}
-
-/// Iterator for the lines contained in a slice of [u8].
-pub struct SliceLines<'a> {
-    buf: &'a [u8],
-    last: usize,
-}
-
-impl<'a> Iterator for SliceLines<'a> {
-    type Item = &'a [u8];
-
-    fn next(&mut self) -> Option<&'a [u8]> {
-        match memchr(b'\n', &self.buf[self.last..]) {
-            Some(m) => {
-                let line = &self.buf[self.last..=(self.last + m)];
-                self.last = self.last + m + 1;
-                Some(line)
-            },
-            None => {
-                let line = &self.buf[self.last..];
-                if line.is_empty() {
-                    None
-                } else {
-                    self.last = self.buf.len();
-                    Some(line)
-                }
-            }
+            multipart: false,
         }
     }
 }
@@ -93,7 +240,8 @@ impl<'a> Iterator for SliceLines<'a> {
 /// Every line in the email is contained in a MIME part (which itself may be
 /// nested in another part). The top level of the email is also considered
 /// to be a part for convenience of processing.
-struct EmailParser<'a> {
+pub(crate) struct EmailParser<'a> {
+    buf: &'a [u8],
     lines: Peekable<SliceLines<'a>>,
     // The stack of nested parts the line we are processing is contained in.
     part_stack: Vec<Part>,
@@ -101,42 +249,60 @@ struct EmailParser<'a> {
     in_header: bool,
     // The active multi-part boundary.
     active_boundary: Vec<u8>,
-    content_encoding_regex: Regex,
-    content_type_regex: Regex,
-    boundary_regex: Regex,
+    // The maximum allowed length of the part stack.
+    max_part_depth: usize,
+    // Issues found while parsing.
+    issues: Vec<ValidationIssue>,
+    // The point in time by which parsing must complete.
+    deadline: Option<Instant>,
+    // The number of lines parsed since the deadline was last checked.
+    lines_since_deadline_check: usize,
+    // Whether parsing was aborted because the deadline was exceeded.
+    timed_out: bool,
 }
 
 impl<'a> EmailParser<'a> {
-    fn new(buf: &'a [u8]) -> Self {
-        let content_encoding_regex =
-            RegexBuilder::new(r"Content-Transfer-Encoding:\s*([[:alnum:]-]+)")
-                .case_insensitive(true)
-                .build().unwrap();
-        let content_type_regex =
-            RegexBuilder::new(r#"^Content-Type:\s*([^;]+)\s*(?:;\s*charset\s*=\s*"?([[:alnum:]_:\-\.]+))?"?"#)
-                .case_insensitive(true)
-                .build().unwrap();
-
-        let boundary_regex =
-            RegexBuilder::new(r#"^Content-Type:\s*multipart/.*boundary\s*=\s*"?([[:alnum:]'_,/:=\(\)\+\-\.\?]+)"?"#)
-                .case_insensitive(true)
-                .build().unwrap();
-
+    pub(crate) fn new(buf: &'a [u8], max_part_depth: usize, deadline: Option<Instant>) -> Self {
         EmailParser{
-            lines: SliceLines{buf, last: 0}.peekable(),
+            buf,
+            lines: SliceLines::new(buf).peekable(),
             // All emails have the top-level part.
             part_stack: vec![Part::new()],
             in_header: true,
             active_boundary: Vec::new(),
-            content_encoding_regex: content_encoding_regex,
-            content_type_regex: content_type_regex,
-            boundary_regex: boundary_regex,
+            max_part_depth,
+            issues: Vec::new(),
+            deadline,
+            lines_since_deadline_check: 0,
+            timed_out: false,
         }
     }
 
-    // Returns the content type of the active part.
+    // Returns whether the deadline has been exceeded. To keep the overhead
+    // low, the time is checked only periodically.
+    fn deadline_exceeded(&mut self) -> bool {
+        const LINES_PER_CHECK: usize = 256;
+
+        if let Some(deadline) = self.deadline {
+            self.lines_since_deadline_check += 1;
+            if self.lines_since_deadline_check >= LINES_PER_CHECK {
+                self.lines_since_deadline_check = 0;
+                return Instant::now() > deadline;
+            }
+        }
+        false
+    }
+
+    // Returns the offset of a line in the parsed buffer.
+    fn offset_of(&self, line: &[u8]) -> usize {
+        line.as_ptr() as usize - self.buf.as_ptr() as usize
+    }
+
+    // Returns the content type of the active part. The body data of
+    // multipart containers (i.e., the preamble and epilogue) is untyped.
     fn active_content_type(&self) -> Option<String> {
-        self.part_stack.last()?.content_type.clone()
+        let part = self.part_stack.last()?;
+        if part.multipart { None } else { part.content_type.clone() }
     }
 
     // Returns the encoding of the active part.
@@ -149,6 +315,11 @@ impl<'a> EmailParser<'a> {
         self.part_stack.last()?.charset.clone()
     }
 
+    // Returns whether the active part is a multipart container.
+    fn active_multipart(&self) -> bool {
+        self.part_stack.last().is_some_and(|p| p.multipart)
+    }
+
     fn begin_part(&mut self) {
         let part = self.part_stack.last().unwrap();
 
@@ -179,30 +350,60 @@ impl<'a> EmailParser<'a> {
         self.part_stack.last_mut().unwrap().subpart_boundary = None;
         self.active_boundary.clear();
 
+        // The innermost remaining multipart boundary becomes active.
         for p in self.part_stack.iter().rev() {
             if let Some(b) = &p.subpart_boundary {
                 self.active_boundary = b.clone();
+                break;
             }
         }
     }
 
     fn update_active_part_from_header_field(&mut self, field: &[u8]) {
-        let mut part = self.part_stack.last_mut().unwrap();
+        let depth = self.part_stack.len();
+        let part = self.part_stack.last_mut().unwrap();
+
+        // Multipart containers have no charset, but track their type.
+        if BOUNDARY_REGEX.is_match(field) {
+            if let Some(captures) = CONTENT_TYPE_REGEX.captures(field) {
+                let type_bytes = captures.get(1).unwrap().as_bytes();
+                part.content_type = Some(std::str::from_utf8(type_bytes).unwrap().trim().to_lowercase());
+            }
+        }
 
-        if let Some(captures) = self.content_encoding_regex.captures(&field) {
+        if let Some(captures) = CONTENT_ENCODING_REGEX.captures(field) {
             let enc_bytes = captures.get(1).unwrap().as_bytes();
-            part.encoding = Some(std::str::from_utf8(&enc_bytes).unwrap().to_lowercase());
-        } else if let Some(captures) = self.boundary_regex.captures(&field) {
+            part.encoding = Some(std::str::from_utf8(enc_bytes).unwrap().to_lowercase());
+        } else if BOUNDARY_REGEX.is_match(field) && depth >= self.max_part_depth {
+            // Don't track the boundary, so that subparts are treated as plain
+            // body data of this part.
+            part.multipart = true;
+            if !self.issues.contains(&ValidationIssue::PartDepthLimitReached) {
+                self.issues.push(ValidationIssue::PartDepthLimitReached);
+            }
+        } else if let Some(captures) = BOUNDARY_REGEX.captures(field) {
             part.subpart_boundary = Some(captures.get(1).unwrap().as_bytes().to_vec());
+            part.multipart = true;
             self.active_boundary = part.subpart_boundary.as_ref().unwrap().clone();
         }
-        else if let Some(captures) = self.content_type_regex.captures(&field) {
+        else if let Some(captures) = CONTENT_TYPE_REGEX.captures(field) {
             let type_bytes = captures.get(1).unwrap().as_bytes();
-            part.content_type = Some(std::str::from_utf8(&type_bytes).unwrap().to_lowercase());
+            part.content_type = Some(std::str::from_utf8(type_bytes).unwrap().trim().to_lowercase());
             if let Some(charset) = captures.get(2) {
                 part.charset = Some(std::str::from_utf8(charset.as_bytes()).unwrap().to_lowercase());
             }
         }
+
+        // Multipart containers can't be encoded, since their parts are
+        // encoded individually. The encoding and the type may be declared
+        // in any order, so check after every field.
+        if part.multipart &&
+           part.encoding.as_deref().is_some_and(|e| !matches!(e, "7bit" | "8bit" | "binary")) {
+            part.encoding = None;
+            if !self.issues.contains(&ValidationIssue::InvalidMultipartEncoding) {
+                self.issues.push(ValidationIssue::InvalidMultipartEncoding);
+            }
+        }
     }
 }
 
@@ -216,6 +417,53 @@ fn vec_trim_end_newline(line: &mut Vec<u8>) {
     }
 }
 
+/// Replaces each run of spaces and tabs in the data after the specified
+/// start position with a single space.
+fn vec_fold_whitespace_to_space(data: &mut Vec<u8>, start: usize) {
+    let mut write = start;
+    let mut prev_wsp = false;
+
+    for read in start..data.len() {
+        let b = data[read];
+        let wsp = b == b' ' || b == b'\t';
+        if !(wsp && prev_wsp) {
+            data[write] = if wsp { b' ' } else { b };
+            write += 1;
+        }
+        prev_wsp = wsp;
+    }
+
+    data.truncate(write);
+}
+
+/// Removes the blank (i.e., empty or whitespace-only) lines at the end of
+/// the data after the specified start, keeping the line terminator of the
+/// last remaining line. If all the lines are blank, only a single line
+/// terminator is kept.
+fn vec_trim_trailing_blank_lines(data: &mut Vec<u8>, start: usize) {
+    let is_blank = |line: &[u8]| line.iter().all(|b| b" \t\r\n".contains(b));
+    let mut end = None;
+    let mut first_newline: Option<&[u8]> = None;
+
+    for line in SliceLines::new(&data[start..]) {
+        if first_newline.is_none() {
+            first_newline = Some(&line[slice_trim_end_newline(line).len()..]);
+        }
+        if !is_blank(line) {
+            end = Some(line.as_ptr() as usize - data.as_ptr() as usize + line.len());
+        }
+    }
+
+    match end {
+        Some(end) => data.truncate(end),
+        None => {
+            let newline = first_newline.unwrap_or_default().to_vec();
+            data.truncate(start);
+            data.extend(newline);
+        },
+    }
+}
+
 /// Returns a new slice not including any newline characters from the
 /// end of an existing slice.
 fn slice_trim_end_newline(mut line: &[u8]) -> &[u8] {
@@ -228,12 +476,36 @@ fn slice_trim_end_newline(mut line: &[u8]) -> &[u8] {
     line
 }
 
+/// Returns the length of the value of an unfolded header field, excluding
+/// the line terminator.
+fn field_value_len(field: &[u8]) -> usize {
+    let content = slice_trim_end_newline(field);
+    memchr(b':', content).map_or(content.len(), |colon| content.len() - colon - 1)
+}
+
+/// Truncates the value of the unfolded header field that starts at the
+/// specified position and extends to the end of the data, keeping the line
+/// terminator of the field. Returns whether the field was truncated.
+fn truncate_field_value(data: &mut Vec<u8>, start: usize, max_len: usize) -> bool {
+    let field = &data[start..];
+    let content_len = slice_trim_end_newline(field).len();
+    let value_start = memchr(b':', &field[..content_len]).map_or(0, |colon| colon + 1);
+    if content_len - value_start <= max_len {
+        return false;
+    }
+
+    let newline = field[content_len..].to_vec();
+    data.truncate(start + value_start + max_len);
+    data.extend(newline);
+    true
+}
+
 /// Returns whether a line of bytes is a multi-part boundary line for the
 /// specified boundary string.
 fn is_boundary_line(line: &[u8], boundary: &[u8]) -> bool {
     if line.starts_with(b"--") && !boundary.is_empty() {
-        let line = slice_trim_end_newline(&line);
-        let line = if line.ends_with(b"--") { &line[..line.len()-2] } else { &line[..] };
+        let line = slice_trim_end_newline(line);
+        let line = if line.ends_with(b"--") { &line[..line.len()-2] } else { line };
         return line.len() > 2 && &line[2..] == boundary;
     }
 
@@ -241,36 +513,50 @@ fn is_boundary_line(line: &[u8], boundary: &[u8]) -> bool {
 }
 
 
-impl Iterator for EmailParser<'_> {
-    type Item = Element;
+impl<'a> Iterator for EmailParser<'a> {
+    type Item = Element<'a>;
 
-    fn next(&mut self) -> Option<Element> {
+    fn next(&mut self) -> Option<Element<'a>> {
         let mut inprogress = Vec::new();
+        // Header field and body lines are contiguous in the parsed buffer,
+        // so we only need to track their range.
+        let mut field: Option<Range<usize>> = None;
+        let mut body: Option<Range<usize>> = None;
         let mut element = None;
 
         // Loop until we recognize an element (or reach end of input).
-        loop {
-            let line = match self.lines.next() {
-                Some(l) => l,
-                None => break,
-            };
+        // SliceLines never yields empty lines, so indexing the first byte of
+        // a line is always valid, even for empty input.
+        while let Some(line) = self.lines.next() {
+            if self.deadline_exceeded() {
+                self.timed_out = true;
+                return None;
+            }
 
             if self.in_header {
                 match line[0] {
                     // Empty lines denote the end of header.
-                    b'\n' | b'\r' => {
+                    b'\n' | b'\r' if is_empty_line(line) => {
                         self.in_header = false;
-                        element = Some(Element::Verbatim{data: line.to_vec()});
+                        element = Some(Element::Verbatim{data: line});
                         break;
                     },
                     // Lines beginning with are continuation lines.
                     b' ' | b'\t' => {
+                        if is_whitespace_only_line(line) &&
+                           !self.issues.contains(&ValidationIssue::WhitespaceOnlyHeaderLine) {
+                            self.issues.push(ValidationIssue::WhitespaceOnlyHeaderLine);
+                        }
                         vec_trim_end_newline(&mut inprogress);
                         inprogress.extend(line);
                     },
                     _ => inprogress = line.to_vec(),
                 };
 
+                let offset = self.offset_of(line);
+                let start = field.as_ref().map_or(offset, |f| f.start);
+                field = Some(start..offset + line.len());
+
                 // If the next line is not a continuation line, break
                 // to emit the current header field.
                 if let Some(next_line) = self.lines.peek() {
@@ -282,8 +568,8 @@ impl Iterator for EmailParser<'_> {
                 continue;
             }
 
-            if is_boundary_line(&line, &self.active_boundary) {
-                if slice_trim_end_newline(&line).ends_with(b"--") {
+            if is_boundary_line(line, &self.active_boundary) {
+                if slice_trim_end_newline(line).ends_with(b"--") {
                     self.end_part();
                 } else {
                     self.begin_part();
@@ -291,13 +577,15 @@ impl Iterator for EmailParser<'_> {
                     self.in_header = true;
                 }
 
-                element = Some(Element::Verbatim{data: line.to_vec()});
+                element = Some(Element::Verbatim{data: line});
                 break;
             }
 
-            // If we reached this point, this line is a body line. Append
-            // it to the inprogress data.
-            inprogress.extend(line);
+            // If we reached this point, this line is a body line. Extend
+            // the body range to include it.
+            let offset = self.offset_of(line);
+            let start = body.as_ref().map_or(offset, |b| b.start);
+            body = Some(start..offset + line.len());
 
             // If next line is a boundary line, break to emit the current
             // body.
@@ -311,94 +599,226 @@ impl Iterator for EmailParser<'_> {
         // Breaking out the loop happens in three cases:
         // 1. End of input
         // 2. We have recognized a verbatim element.
-        // 3. We have inprogress data that we have recognized as a header field
-        //    or body.
+        // 3. We have inprogress data that we have recognized as a header field,
+        //    or a body range.
 
-        // If we have inprogress data, emit it as header or body.
+        // We shouldn't have set an element at this point if we have inprogress
+        // data or a body range, since this would lead to loss of data.
         if !inprogress.is_empty() {
-            // We shouldn't have set an element at this point, since we have
-            // inprogress data, and this would lead to loss of data.
             assert!(element.is_none());
+            element = Some(Element::HeaderField{data: inprogress, raw: &self.buf[field.unwrap()]});
+        } else if let Some(body) = body {
+            assert!(element.is_none());
+            element = Some(
+                Element::Body{
+                    data: &self.buf[body],
+                    encoding: self.active_encoding(),
+                    content_type: self.active_content_type(),
+                    charset: self.active_charset(),
+                    multipart: self.active_multipart(),
+                }
+            );
+        }
 
-            if self.in_header {
-                element = Some(Element::HeaderField{data: inprogress});
-            } else {
-                element = Some(
-                    Element::Body{
-                        data: inprogress,
-                        encoding: self.active_encoding(),
-                        content_type: self.active_content_type(),
-                        charset: self.active_charset(),
-                    }
-                );
-            }
+        if let Some(Element::HeaderField{data: field, ..}) = element.as_ref() {
+            self.update_active_part_from_header_field(field);
         }
 
-        if let Some(Element::HeaderField{data: field}) = element.as_ref() {
-            self.update_active_part_from_header_field(&field);
+        // At the end of input all multipart parts should have been closed,
+        // leaving only the top-level part.
+        if element.is_none() && self.part_stack.len() != 1 &&
+           !self.issues.contains(&ValidationIssue::UnbalancedBoundaries) {
+            self.issues.push(ValidationIssue::UnbalancedBoundaries);
         }
 
         element
     }
 }
 
+/// The outcome of decoding text data.
+struct TextDecoding {
+    /// Whether the data was converted to utf-8, i.e., the content encoding
+    /// was valid and the charset was recognized. Otherwise, the data is left
+    /// (partially) undecoded.
+    utf8: bool,
+    /// Whether the data was decoded without any loss, i.e., the content
+    /// encoding was valid and all characters could be converted to utf-8.
+    lossless: bool,
+}
+
+/// Aliases for nonstandard charset labels that are common in emails, but
+/// aren't recognized by the WHATWG Encoding Standard.
+const CHARSET_ALIASES: &[(&str, &str)] = &[
+    ("big-5", "big5"),
+    ("cp-866", "ibm866"),
+    ("cp932", "shift_jis"),
+    ("cp936", "gbk"),
+    ("cp949", "euc-kr"),
+    ("cp950", "big5"),
+    ("gb-2312", "gb2312"),
+    ("iso_8859_1", "iso-8859-1"),
+    ("koi8r", "koi8-r"),
+    ("ks_c_5601", "euc-kr"),
+    ("latin-1", "iso-8859-1"),
+    ("macroman", "macintosh"),
+];
+
+/// Returns the standard label for a nonstandard, lowercase charset label,
+/// if it's a known alias.
+fn builtin_charset_alias(label: &str) -> Option<String> {
+    if let Some((_, standard)) = CHARSET_ALIASES.iter().find(|(alias, _)| *alias == label) {
+        return Some(standard.to_string());
+    }
+
+    // Windows code pages, e.g., cp-1252, win-1252 or windows1252.
+    let number = ["cp-", "cp", "win-", "windows"].iter().find_map(|p| label.strip_prefix(p))?;
+    match number.parse() {
+        Ok(874) | Ok(1250..=1258) => Some(format!("windows-{}", number)),
+        _ => None,
+    }
+}
+
+/// Returns the charset for a charset label, taking into account the
+/// specified aliases, and the built-in aliases for nonstandard labels.
+pub(crate) fn charset_for_label(label: &str, aliases: &HashMap<String, String>) -> Option<Charset> {
+    let label = label.trim().to_lowercase();
+    if let Some(standard) = aliases.get(&label) {
+        return Charset::for_label(standard.as_bytes());
+    }
+
+    Charset::for_label(label.as_bytes())
+        .or_else(|| Charset::for_label(builtin_charset_alias(&label)?.as_bytes()))
+}
+
 /// Decodes a byte array slice with the specified content encoding and charset
 /// to utf-8 byte data, appending to the specified Vec<u8>.
 fn decode_text_data_to_buf(
     data: &[u8],
     encoding: Option<&str>,
     charset: Option<&str>,
-    mut out: &mut Vec<u8>,
-) {
+    charset_aliases: &HashMap<String, String>,
+    out: &mut Vec<u8>,
+) -> TextDecoding {
     let should_decode = encoding.is_some();
     let mut should_convert_charset = true;
+    let mut lossless = true;
+    let mut utf8 = false;
     let initial_len = out.len();
+    let mut restored_newline: Option<&[u8]> = None;
 
     if should_decode {
-        let result = match encoding.unwrap().as_ref() {
-            "base64" => base64_decode_into_buf(&data, &mut out),
-            "quoted-printable" => qp_decode_into_buf(&data, &mut out),
-            "8bit" | "binary" => { out.extend(data); Ok(()) },
-            _ => Err("unknown encoding".into()),
+        let result = match encoding.unwrap() {
+            "base64" => base64_decode_into_buf(data, out),
+            "quoted-printable" => qp_decode_into_buf(data, out),
+            "7bit" | "8bit" | "binary" => { out.extend(data); Ok(()) },
+            _ => Err(MdaError::Decode("unknown encoding".into())),
         };
 
         if result.is_ok() {
             // During decoding the final CRLF/LF in the data may be dropped.
             // Restore it to ensure that subsequent lines don't get folded
             // with the decoded data.
-            const CRLF: &[u8] = &[b'\r', b'\n'];
-            const LF: &[u8] = &[b'\n'];
+            const CRLF: &[u8] = b"\r\n";
+            const LF: &[u8] = b"\n";
             if data.ends_with(CRLF) && !out.ends_with(CRLF) {
-                out.extend(CRLF);
+                restored_newline = Some(CRLF);
             } else if data.ends_with(LF) && !out.ends_with(LF) {
-                out.extend(LF);
+                restored_newline = Some(LF);
             }
         } else {
             out.resize(initial_len, 0);
             should_convert_charset = false;
+            lossless = false;
         }
     }
 
-    if out.len() == initial_len {
+    if out.len() == initial_len && restored_newline.is_none() {
         out.extend(data);
     }
 
     if should_convert_charset {
-        if let Some(chr) = Charset::for_label(charset.unwrap_or("us-ascii").as_bytes()) {
-            let (cow, _, _) = chr.decode(&out[initial_len..]);
-            if let Cow::Owned(c) = cow {
-                out.resize(initial_len, 0);
-                out.extend(c.bytes());
+        // Labels are resolved according to the WHATWG Encoding Standard, so,
+        // e.g., iso-8859-1 and us-ascii are decoded as windows-1252, which
+        // is what mislabeled data actually uses in practice. Data with an
+        // unknown charset is still decoded if it starts with a BOM.
+        let chr = charset_for_label(charset.unwrap_or("us-ascii"), charset_aliases)
+            .or_else(|| Charset::for_bom(&out[initial_len..]).map(|(chr, _)| chr));
+        if let Some(chr) = chr {
+            // Decoding detects the actual charset from any BOM, regardless
+            // of the declared charset, and strips the BOM.
+            let decoded_len = out.len() - initial_len;
+            let (cow, _, had_errors) = chr.decode(&out[initial_len..]);
+            match cow {
+                Cow::Owned(c) => {
+                    out.resize(initial_len, 0);
+                    out.extend(c.bytes());
+                },
+                Cow::Borrowed(c) => {
+                    let bom_len = decoded_len - c.len();
+                    out.drain(initial_len..initial_len + bom_len);
+                },
             }
+            lossless = lossless && !had_errors;
+            utf8 = true;
+        } else {
+            // The data is left in its unknown charset.
+            lossless = false;
         }
     }
+
+    // Restore the final newline after converting the charset, since the
+    // newline is in UTF-8, not in the charset of the data (e.g., UTF-16).
+    if let Some(newline) = restored_newline {
+        if !out.ends_with(newline) {
+            out.extend(newline);
+        }
+    }
+
+    TextDecoding{utf8, lossless}
+}
+
+/// Normalizes the body data of a single part with the specified content
+/// type, content transfer encoding and charset, as is done for the parts
+/// of an [Email](type.Email.html).
+///
+/// Text data (i.e., with a `text/*` content type) is decoded according to
+/// the content transfer encoding and converted from the charset to UTF-8.
+/// If the data can't be decoded, it is returned unchanged. Data of other
+/// content types is always returned unchanged.
+///
+/// # Example
+///
+/// ```
+/// use mda::normalize_body;
+/// let body = normalize_body(b"zpHOks6T", "text/plain", Some("base64"), Some("utf-8"));
+/// assert_eq!(body, "ΑΒΓ".as_bytes());
+/// ```
+pub fn normalize_body(
+    body: &[u8],
+    content_type: &str,
+    encoding: Option<&str>,
+    charset: Option<&str>,
+) -> Vec<u8> {
+    let content_type = content_type.trim().to_lowercase();
+    let encoding = encoding.map(|e| e.trim().to_lowercase());
+    let charset = charset.map(|c| c.trim().to_lowercase());
+    let mut normalized = Vec::with_capacity(body.len());
+
+    if content_type.starts_with("text/") {
+        decode_text_data_to_buf(
+            body, encoding.as_deref(), charset.as_deref(), &HashMap::new(), &mut normalized);
+    } else {
+        normalized.extend(body);
+    }
+
+    normalized
 }
 
 /// Returns whether a byte array slice could contain an MIME encoded-word.
 ///
 /// This function could return a false positive, but never a false negative.
 fn maybe_contains_encoded_word(data: &[u8]) -> bool {
-    for spacepos in memchr_iter(b'?', &data) {
+    for spacepos in memchr_iter(b'?', data) {
         if spacepos + 1 < data.len() && data[spacepos + 1] == b'=' {
             return true;
         }
@@ -408,7 +828,12 @@ fn maybe_contains_encoded_word(data: &[u8]) -> bool {
 }
 
 /// Decodes a MIME encoded-word represented as regex captures.
-fn decode_encoded_word_from_captures(caps: &Captures) -> Vec<u8> {
+///
+/// Returns the decoded data and whether it was decoded without loss.
+fn decode_encoded_word_from_captures(
+    caps: &Captures,
+    charset_aliases: &HashMap<String, String>,
+) -> (Vec<u8>, bool) {
     let charset = String::from_utf8_lossy(&caps[1]).to_lowercase();
     let encoding = match &caps[2] {
         b"q" | b"Q" => "quoted-printable",
@@ -426,19 +851,49 @@ fn decode_encoded_word_from_captures(caps: &Captures) -> Vec<u8> {
     }
 
     let mut decoded = Vec::new();
-    decode_text_data_to_buf(&data, Some(encoding), Some(&charset), &mut decoded);
-    decoded
+    let decoding = decode_text_data_to_buf(
+        &data, Some(encoding), Some(&charset), charset_aliases, &mut decoded);
+    (decoded, decoding.lossless)
 }
 
-/// Normalizes an email and parses header fields.
-///
-/// See module documentation about what is involved in normalization.
+/// Returns the email data up to the end of the body length declared in the
+/// top-level Content-Length header field. If there is no valid
+/// Content-Length header field, all the data is returned.
+fn limit_to_content_length(data: &[u8]) -> &[u8] {
+    let mut content_length = None;
+    let mut offset = 0;
+
+    for line in SliceLines::new(data) {
+        offset += line.len();
+
+        if line == b"\n" || line == b"\r\n" {
+            return match content_length {
+                Some(len) => &data[..data.len().min(offset.saturating_add(len))],
+                None => data,
+            };
+        }
+
+        if content_length.is_none() && line.len() > 15 &&
+           line[..15].eq_ignore_ascii_case(b"content-length:") {
+            content_length = std::str::from_utf8(&line[15..]).ok()
+                .and_then(|v| v.trim().parse::<usize>().ok());
+        }
+    }
+
+    data
+}
+
+/// Decodes the MIME encoded-words in header data, removing any whitespace
+/// between consecutive encoded-words as required by RFC 2047.
 ///
-/// Returns the normalized data and a map of header field names to values.
-pub fn normalize_email(data: &[u8]) -> (Vec<u8>, HashMap<String, Vec<String>>) {
+/// Returns the decoded data and whether it was decoded without loss.
+pub(crate) fn decode_encoded_words<'a>(
+    data: &'a [u8],
+    charset_aliases: &HashMap<String, String>,
+) -> (Cow<'a, [u8]>, bool) {
     lazy_static! {
         static ref ENCODED_WORD_REGEX: Regex =
-            RegexBuilder::new(r"=\?([^?]+)\?([^?]+)\?([^? \t]+)\?=")
+            RegexBuilder::new(r"=\?([^?]+)\?([BbQq])\?([^? \t]+)\?=")
                 .case_insensitive(true)
                 .build().unwrap();
         static ref ENCODED_WORD_WSP_REGEX: Regex =
@@ -446,55 +901,585 @@ pub fn normalize_email(data: &[u8]) -> (Vec<u8>, HashMap<String, Vec<String>>) {
                 .case_insensitive(true)
                 .build().unwrap();
     }
-    let parser = EmailParser::new(&data);
-    let mut normalized = Vec::new();
+
+    if !maybe_contains_encoded_word(data) {
+        return (Cow::Borrowed(data), true);
+    }
+
+    let mut lossless = true;
+    let data = ENCODED_WORD_WSP_REGEX.replace_all(data, "?$1?==?$2?".as_bytes());
+    let decoded = ENCODED_WORD_REGEX.replace_all(
+        &data,
+        |caps: &Captures| {
+            let (decoded, word_lossless) = decode_encoded_word_from_captures(caps, charset_aliases);
+            lossless = lossless && word_lossless;
+            decoded
+        }).into_owned();
+
+    (Cow::Owned(decoded), lossless)
+}
+
+/// Information about the body data of a part, as placed in the normalized
+/// email data.
+pub struct BodyPart {
+    /// The lowercase content type of the part, if specified.
+    pub content_type: Option<String>,
+    /// The lowercase charset the part data was declared to be in, before
+    /// normalization, if specified.
+    pub charset: Option<String>,
+    /// Whether the part is a multipart container, in which case the
+    /// body data is the preamble or epilogue of the container.
+    pub multipart: bool,
+    /// Whether the body data was decoded to utf-8 text during
+    /// normalization. Undecoded body data keeps its content transfer
+    /// encoding and charset.
+    pub decoded: bool,
+    /// The range of the body data in the normalized email data.
+    pub range: Range<usize>,
+}
+
+impl BodyPart {
+    /// Returns whether the body data is human-readable text.
+    pub fn is_text(&self) -> bool {
+        match self.content_type {
+            Some(ref content_type) => content_type.starts_with("text/"),
+            None => !self.multipart,
+        }
+    }
+}
+
+/// The result of normalizing an email.
+pub struct NormalizedEmail {
+    /// The normalized email data.
+    pub data: Vec<u8>,
+    /// A map of lowercase header field names to values.
+    pub fields: HashMap<String, Vec<String>>,
+    /// The lowercase names of header fields whose values could not be
+    /// converted to utf-8 without loss.
+    pub lossy_fields: Vec<String>,
+    /// The parts with body data, in the order they appear in the email.
+    pub body_parts: Vec<BodyPart>,
+    /// The MIME parts of the email, in depth-first order.
+    pub parts: Vec<PartInfo>,
+    /// Issues found while parsing the email.
+    pub issues: Vec<ValidationIssue>,
+    /// The lowercase name and the length of the longest header field
+    /// value, before any truncation.
+    pub longest_field: Option<(String, usize)>,
+}
+
+/// A header field that may need to be rewritten after the body of its part
+/// has been decoded.
+struct EncodingField {
+    // The range of the field in the normalized data.
+    range: Range<usize>,
+    // The lowercase field name.
+    name: String,
+    // The index of the field value in the fields map entry.
+    index: usize,
+}
+
+lazy_static! {
+    /// Matches a Content-Transfer-Encoding header field, with the encoding
+    /// captured.
+    static ref CONTENT_ENCODING_REGEX: Regex =
+        RegexBuilder::new(r"Content-Transfer-Encoding:\s*([[:alnum:]-]+)")
+            .case_insensitive(true)
+            .build().unwrap();
+    /// Matches a Content-Type header field, with the content type and the
+    /// charset parameter value, if it's the first parameter, captured.
+    static ref CONTENT_TYPE_REGEX: Regex =
+        RegexBuilder::new(r#"^Content-Type:\s*([^;]+)\s*(?:;\s*charset\s*=\s*"?([[:alnum:]_:\-\.]+))?"?"#)
+            .case_insensitive(true)
+            .build().unwrap();
+    /// Matches a multipart Content-Type header field, with the boundary
+    /// captured.
+    static ref BOUNDARY_REGEX: Regex =
+        RegexBuilder::new(r#"^Content-Type:\s*multipart/.*boundary\s*=\s*"?([[:alnum:]'_,/:=\(\)\+\-\.\?]+)"?"#)
+            .case_insensitive(true)
+            .build().unwrap();
+    /// Matches the charset parameter of a Content-Type header field, with
+    /// the part before the parameter value captured.
+    pub(crate) static ref CHARSET_PARAM_REGEX: Regex =
+        RegexBuilder::new(r#"(;\s*charset\s*=\s*)(?:"[^"]*"|[^;\s]+)"#)
+            .case_insensitive(true)
+            .build().unwrap();
+}
+
+/// Splits a header field into its lowercase name and its value.
+fn split_field(field: &[u8]) -> (String, String) {
+    let field_str = String::from_utf8_lossy(field);
+    let mut split = field_str.trim().splitn(2, ':');
+    let name = split.next().map(|n| n.to_lowercase()).unwrap();
+    let value = split.next().unwrap_or("").to_owned();
+    (name, value)
+}
+
+/// Rewrites the encoding header fields of a decoded text part to describe
+/// the normalized data, updating the fields map accordingly.
+fn rewrite_encoding_fields(
+    normalized: &mut Vec<u8>,
+    fields: &mut HashMap<String, Vec<String>>,
+    encoding_fields: &[EncodingField],
+) {
+    // Rewrite later fields first, so that the ranges of earlier fields
+    // remain valid.
+    for field in encoding_fields.iter().rev() {
+        let old = &normalized[field.range.clone()];
+        let content_end = slice_trim_end_newline(old).len();
+        let newline = old[content_end..].to_vec();
+
+        let mut new = match field.name.as_str() {
+            "content-transfer-encoding" => {
+                let name_len = memchr(b':', old).unwrap_or(0);
+                let mut new = old[..name_len].to_vec();
+                new.extend(b": 8bit");
+                new
+            },
+            _ => CHARSET_PARAM_REGEX.replace(&old[..content_end], &b"${1}utf-8"[..]).into_owned(),
+        };
+        new.extend(newline);
+
+        fields.get_mut(&field.name).unwrap()[field.index] = split_field(&new).1;
+        normalized.splice(field.range.clone(), new);
+    }
+}
+
+/// Tracks the MIME parts of an email while it's being normalized.
+struct PartTracker {
+    parts: Vec<PartInfo>,
+    // The indices of the parts that haven't ended yet, from the top level
+    // to the current part, and whether their header has ended.
+    open: Vec<(usize, bool)>,
+}
+
+impl PartTracker {
+    fn new() -> Self {
+        PartTracker{parts: vec![PartInfo::new(0, 0)], open: vec![(0, false)]}
+    }
+
+    // Updates the current part with information from the parser.
+    fn update_current(&mut self, part: &Part) {
+        let (index, _) = *self.open.last().unwrap();
+        let info = &mut self.parts[index];
+        info.content_type = part.content_type.clone();
+        info.charset = part.charset.clone();
+        info.encoding = part.encoding.clone();
+        info.multipart = part.multipart;
+    }
+
+    fn end_current_header(&mut self, offset: usize) {
+        let (index, header_ended) = self.open.last_mut().unwrap();
+        let info = &mut self.parts[*index];
+        info.header.end = offset;
+        info.body = offset..offset;
+        *header_ended = true;
+    }
+
+    // Moves the start of the body of the current part, after its header
+    // has been rewritten.
+    fn shift_current_body(&mut self, delta: isize) {
+        let (index, _) = *self.open.last().unwrap();
+        let info = &mut self.parts[index];
+        info.header.end = info.header.end.saturating_add_signed(delta);
+        info.body.start = info.header.end;
+    }
+
+    // Ends all open parts at the specified depth or deeper.
+    fn end(&mut self, depth: usize, offset: usize) {
+        while let Some(&(index, header_ended)) = self.open.last() {
+            let info = &mut self.parts[index];
+            if info.depth < depth {
+                break;
+            }
+            if !header_ended {
+                info.header.end = offset;
+                info.body.start = offset;
+            }
+            info.body.end = offset;
+            self.open.pop();
+        }
+    }
+
+    // Begins a part at the specified depth, ending any previous parts at
+    // the same depth or deeper. The header of the new part starts at the
+    // specified offset, after its boundary line.
+    fn begin(&mut self, depth: usize, boundary_offset: usize, offset: usize) {
+        self.end(depth, boundary_offset);
+        self.open.push((self.parts.len(), false));
+        self.parts.push(PartInfo::new(depth, offset));
+    }
+}
+
+/// Normalizes a plain email, i.e., an all-ASCII email without encoded-words,
+/// content transfer encodings or multipart parts, whose normalized data is
+/// the same as its original data except for unfolded header fields.
+///
+/// The header is scanned directly from the original data, and the body is
+/// copied as a whole, avoiding the per-line processing of the
+/// [EmailParser](struct.EmailParser.html). Returns `None` if the email
+/// doesn't qualify, or if the options may require changing the data, in
+/// which case the email must be normalized by the full parser.
+fn normalize_plain_email(data: &[u8], options: &NormalizationOptions) -> Option<NormalizedEmail> {
+    if options.fold_whitespace_to_space || options.rewrite_encoding_fields ||
+       options.part_transform.is_some() || options.trim_trailing_blank_lines ||
+       !data.is_ascii() {
+        return None;
+    }
+
+    // Find the empty line that ends the header, if any.
+    let mut header_len = data.len();
+    for line in SliceLines::new(data) {
+        if is_empty_line(line) {
+            header_len = line.as_ptr() as usize - data.as_ptr() as usize;
+            break;
+        }
+    }
+
+    let header = &data[..header_len];
+    if maybe_contains_encoded_word(header) || CONTENT_ENCODING_REGEX.is_match(header) {
+        return None;
+    }
+
+    let mut normalized = Vec::with_capacity(data.len());
+    let mut fields: HashMap<String, Vec<String>> = HashMap::new();
+    let mut part = PartInfo::new(0, 0);
+    let mut issues = Vec::new();
+    let mut longest_field: Option<(String, usize)> = None;
+    let mut lines = SliceLines::new(header).peekable();
+
+    while let Some(line) = lines.next() {
+        let field_start = normalized.len();
+        normalized.extend(line);
+        // Unfold continuation lines into the field.
+        while let Some(next_line) = lines.next_if(|l| l[0] == b' ' || l[0] == b'\t') {
+            if is_whitespace_only_line(next_line) &&
+               !issues.contains(&ValidationIssue::WhitespaceOnlyHeaderLine) {
+                issues.push(ValidationIssue::WhitespaceOnlyHeaderLine);
+            }
+            vec_trim_end_newline(&mut normalized);
+            normalized.extend(next_line);
+        }
+
+        let value_len = field_value_len(&normalized[field_start..]);
+        if let Some(max_len) = options.max_header_field_len {
+            if truncate_field_value(&mut normalized, field_start, max_len) &&
+               !issues.contains(&ValidationIssue::HeaderFieldTruncated) {
+                issues.push(ValidationIssue::HeaderFieldTruncated);
+            }
+        }
+
+        let field = &normalized[field_start..];
+        if BOUNDARY_REGEX.is_match(field) {
+            return None;
+        }
+        if let Some(captures) = CONTENT_TYPE_REGEX.captures(field) {
+            let type_bytes = captures.get(1).unwrap().as_bytes();
+            part.content_type = Some(std::str::from_utf8(type_bytes).unwrap().trim().to_lowercase());
+            if let Some(charset) = captures.get(2) {
+                part.charset = Some(std::str::from_utf8(charset.as_bytes()).unwrap().to_lowercase());
+            }
+        }
+
+        let (name, value) = split_field(field);
+        if longest_field.as_ref().is_none_or(|(_, len)| value_len > *len) {
+            longest_field = Some((name.clone(), value_len));
+        }
+        fields.entry(name).or_default().push(value);
+    }
+
+    // Text data is decoded from its charset, which leaves all-ASCII data
+    // unchanged only for ASCII compatible charsets.
+    let is_text = part.content_type.as_ref().is_none_or(|ct| ct.starts_with("text/"));
+    let charset = charset_for_label(part.charset.as_deref().unwrap_or("us-ascii"), &options.charset_aliases);
+    let decoded = is_text && match charset {
+        Some(charset) if charset.is_ascii_compatible() => true,
+        Some(_) => return None,
+        None => false,
+    };
+
+    let header_end = normalized.len();
+    normalized.extend(&data[header_len..]);
+
+    let mut body_parts = Vec::new();
+    part.header = 0..normalized.len();
+    part.body = normalized.len()..normalized.len();
+
+    if let Some(empty_line) = SliceLines::new(&data[header_len..]).next() {
+        let body_start = header_end + empty_line.len();
+        part.header.end = body_start;
+        part.body.start = body_start;
+
+        if body_start < normalized.len() {
+            body_parts.push(
+                BodyPart{
+                    content_type: part.content_type.clone(),
+                    charset: part.charset.clone(),
+                    multipart: false,
+                    decoded,
+                    range: body_start..normalized.len(),
+                }
+            );
+        }
+    }
+
+    Some(
+        NormalizedEmail{
+            data: normalized,
+            fields,
+            lossy_fields: Vec::new(),
+            body_parts,
+            parts: vec![part],
+            issues,
+            longest_field,
+        }
+    )
+}
+
+/// Normalizes an email and parses header fields.
+///
+/// See module documentation about what is involved in normalization.
+///
+/// Fails only if the deadline specified in the options is exceeded.
+pub fn normalize_email(
+    data: &[u8],
+    options: &mut NormalizationOptions,
+) -> Result<NormalizedEmail> {
+    normalize_email_to(data, options, None)
+}
+
+/// Normalizes an email, like `normalize_email`, optionally streaming the
+/// normalized data to a writer instead of collecting it in memory.
+///
+/// When streaming, the normalized data is written out as soon as it can't
+/// change anymore, so at most the normalized form of a single part is held
+/// in memory at a time. The data of the returned `NormalizedEmail` is then
+/// empty, but its ranges still refer to offsets in the streamed data.
+///
+/// Fails if the deadline specified in the options is exceeded, or if
+/// writing to the writer fails.
+pub(crate) fn normalize_email_to(
+    data: &[u8],
+    options: &mut NormalizationOptions,
+    mut writer: Option<&mut dyn Write>,
+) -> Result<NormalizedEmail> {
+    let data = if options.use_content_length { limit_to_content_length(data) } else { data };
+    // The fast path takes linear time, so there is no need to check the
+    // deadline while taking it. It builds all the normalized data in
+    // memory, though, so it's not used when streaming.
+    if writer.is_none() {
+        if let Some(normalized) = normalize_plain_email(data, options) {
+            return Ok(normalized);
+        }
+    }
+
+    let mut parser = EmailParser::new(data, options.max_part_depth, options.deadline);
+    // Normalized data is usually not larger than the original data, so
+    // reserve enough space to avoid reallocations while building it.
+    let mut normalized = Vec::with_capacity(if writer.is_none() { data.len() } else { 0 });
+    // The length of the normalized data already written to the writer.
+    let mut base = 0;
     let mut fields = HashMap::new();
+    let mut lossy_fields = Vec::new();
+    let mut body_parts = Vec::new();
+    // The encoding related header fields of the current part.
+    let mut encoding_fields = Vec::new();
+    let mut prev_was_header_field = false;
+    let mut parts = PartTracker::new();
+    let mut longest_field: Option<(String, usize)> = None;
+
+    loop {
+        let was_in_header = parser.in_header;
+        let element = match parser.next() {
+            Some(element) => element,
+            None => break,
+        };
+        let is_header_field = matches!(element, Element::HeaderField{..});
 
-    for element in parser {
         match element {
-            Element::HeaderField{data} => {
+            Element::HeaderField{mut data, raw} => {
                 let initial_len = normalized.len();
+                let mut lossless = true;
 
-                if maybe_contains_encoded_word(&data) {
-                    // First remove whitespace between consecutive encoded-words
-                    // as required by the RFC, then decode.
-                    let data = ENCODED_WORD_WSP_REGEX.replace_all(
-                        &data, "?$1?==?$2?".as_bytes());
-                    let data = ENCODED_WORD_REGEX.replace_all(
-                        &data, decode_encoded_word_from_captures);
-                    normalized.extend(data.as_ref());
-                } else {
-                    normalized.extend(&data);
+                let value_len = field_value_len(&data);
+                if let Some(max_len) = options.max_header_field_len {
+                    if truncate_field_value(&mut data, 0, max_len) &&
+                       !parser.issues.contains(&ValidationIssue::HeaderFieldTruncated) {
+                        parser.issues.push(ValidationIssue::HeaderFieldTruncated);
+                    }
+                }
+
+                // A new header begins, so forget the fields of the previous one.
+                if !prev_was_header_field {
+                    encoding_fields.clear();
+                }
+
+                let (decoded, words_lossless) = decode_encoded_words(&data, &options.charset_aliases);
+                lossless = lossless && words_lossless;
+                normalized.extend(decoded.as_ref());
+
+                if options.fold_whitespace_to_space {
+                    vec_fold_whitespace_to_space(&mut normalized, initial_len);
                 }
 
                 // Populate the fields map.
-                let field_str = String::from_utf8_lossy(&normalized[initial_len..]);
-                let field_str = field_str.trim();
-                let mut split = field_str.splitn(2, ':');
-                let name = split.next().map(|n| n.to_lowercase()).unwrap();
-                let value = split.next().unwrap_or("").to_owned();
+                if let Cow::Owned(_) = String::from_utf8_lossy(&normalized[initial_len..]) {
+                    lossless = false;
+                }
+                let (name, value) = split_field(&normalized[initial_len..]);
+                if longest_field.as_ref().is_none_or(|(_, len)| value_len > *len) {
+                    longest_field = Some((name.clone(), value_len));
+                }
+                if !lossless && !lossy_fields.contains(&name) {
+                    lossy_fields.push(name.clone());
+                }
+                if name == "content-transfer-encoding" || name == "content-type" {
+                    encoding_fields.push(
+                        EncodingField{
+                            range: initial_len..normalized.len(),
+                            name: name.clone(),
+                            index: fields.get(&name).map_or(0, Vec::len),
+                        }
+                    );
+                }
                 fields.entry(name).or_insert(Vec::new()).push(value);
+                parts.update_current(parser.part_stack.last().unwrap());
+
+                // The headers of the parts are body data.
+                if options.body_passthrough && parser.part_stack.len() > 1 {
+                    normalized.truncate(initial_len);
+                    normalized.extend(raw);
+                }
             },
-            Element::Body{data, encoding, content_type, charset} => {
+            Element::Body{data, encoding, content_type, charset, multipart} => {
+                let initial_len = normalized.len();
+                let mut rewrite = false;
+                let mut decoded = false;
+                let mut is_text = false;
+
                 // Only decode text content.
                 match content_type {
+                    _ if options.body_passthrough => normalized.extend(data),
                     Some(ref content_type) if !content_type.starts_with("text/") => {
-                        normalized.extend(&data);
+                        normalized.extend(data);
                     },
                     _ => {
-                        decode_text_data_to_buf(
-                            &data,
-                            encoding.as_ref().map(String::as_str),
-                            charset.as_ref().map(String::as_str),
+                        let decoding = decode_text_data_to_buf(
+                            data,
+                            encoding.as_deref(),
+                            charset.as_deref(),
+                            &options.charset_aliases,
                             &mut normalized);
+                        rewrite = options.rewrite_encoding_fields && decoding.lossless && !multipart;
+                        decoded = decoding.utf8;
+                        is_text = !multipart;
                     }
                 };
+
+                let transform = if options.body_passthrough { None } else { options.part_transform.as_mut() };
+                if let Some(transform) = transform {
+                    let mut part_data = normalized.split_off(initial_len);
+                    let context = PartContext{
+                        content_type: content_type.as_deref(),
+                        charset: charset.as_deref(),
+                    };
+                    transform(&context, &mut part_data);
+                    normalized.extend(part_data);
+                }
+
+                if options.trim_trailing_blank_lines && is_text {
+                    vec_trim_trailing_blank_lines(&mut normalized, initial_len);
+                }
+
+                let body_len = normalized.len() - initial_len;
+                if rewrite {
+                    let len = normalized.len();
+                    rewrite_encoding_fields(&mut normalized, &mut fields, &encoding_fields);
+                    parts.shift_current_body(normalized.len() as isize - len as isize);
+                }
+                encoding_fields.clear();
+
+                body_parts.push(
+                    BodyPart{
+                        content_type,
+                        charset,
+                        multipart,
+                        decoded,
+                        // Rewriting may have changed the length of the header.
+                        range: (base + normalized.len() - body_len)..(base + normalized.len()),
+                    }
+                );
             },
             Element::Verbatim{data} => {
-                normalized.extend(&data);
+                let offset = base + normalized.len();
+                normalized.extend(data);
+
+                if was_in_header {
+                    parts.end_current_header(base + normalized.len());
+                } else if parser.in_header {
+                    // A boundary line starting a new part.
+                    parts.begin(parser.part_stack.len() - 1, offset, base + normalized.len());
+                } else {
+                    // A boundary line ending the subparts of a multipart.
+                    parts.end(parser.part_stack.len(), offset);
+                }
             },
         }
+
+        prev_was_header_field = is_header_field;
+
+        // The encoding related header fields of the current part may still
+        // be rewritten, so keep them until the body of the part is done.
+        if let Some(writer) = writer.as_mut() {
+            if encoding_fields.is_empty() {
+                writer.write_all(&normalized)?;
+                base += normalized.len();
+                normalized.clear();
+            }
+        }
+    }
+
+    if let Some(writer) = writer.as_mut() {
+        writer.write_all(&normalized)?;
+        base += normalized.len();
+        normalized.clear();
     }
 
-    (normalized, fields)
+    parts.end(0, base + normalized.len());
+
+    if parser.timed_out {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "Email normalization deadline exceeded").into());
+    }
+
+    let mut issues = parser.issues;
+    if lacks_mime_version(&fields, &parts.parts) {
+        issues.push(ValidationIssue::MissingMimeVersion);
+    }
+
+    Ok(
+        NormalizedEmail{
+            data: normalized,
+            fields,
+            lossy_fields,
+            body_parts,
+            parts: parts.parts,
+            issues,
+            longest_field,
+        }
+    )
+}
+
+/// Returns whether the email uses MIME features, i.e., multipart containers
+/// or content transfer encodings, without a `MIME-Version: 1.0` header
+/// field.
+fn lacks_mime_version(fields: &HashMap<String, Vec<String>>, parts: &[PartInfo]) -> bool {
+    let uses_mime = parts.iter().any(|part| {
+        part.multipart ||
+            part.encoding.as_deref().is_some_and(|e| !matches!(e, "7bit" | "8bit" | "binary"))
+    });
+    // The version may be followed by a comment, e.g., `1.0 (produced by ...)`.
+    let has_version = fields.get("mime-version").and_then(|values| values.first())
+        .is_some_and(|value| value.split('(').next().unwrap().trim() == "1.0");
+
+    uses_mime && !has_version
 }
@@ -0,0 +1,247 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Parsing of MIME header field parameters.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::BorrowedEmail;
+use crate::normalize::charset_for_label;
+use crate::parts::PartInfo;
+use crate::util::SliceLines;
+
+/// A single segment of a, possibly split, parameter value.
+struct Segment {
+    value: String,
+    extended: bool,
+}
+
+/// Returns the parsed parameter value starting at the beginning of `s`,
+/// and the remaining unparsed input.
+fn parse_value(s: &str) -> (String, &str) {
+    let mut value = String::new();
+
+    if let Some(quoted) = s.strip_prefix('"') {
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return (value, &quoted[i + 1..]),
+                '\\' => if let Some((_, escaped)) = chars.next() {
+                    value.push(escaped);
+                },
+                _ => value.push(c),
+            }
+        }
+        // Unterminated quoted string, use everything up to the end.
+        return (value, "");
+    }
+
+    let end = s.find(|c: char| c == ';' || c.is_whitespace()).unwrap_or(s.len());
+    value.push_str(&s[..end]);
+    (value, &s[end..])
+}
+
+/// Decodes the percent-encoded bytes of an RFC 2231 extended value.
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Some(b) = s.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    decoded
+}
+
+/// Joins the segments of a parameter value, decoding any RFC 2231 extended
+/// segments using the charset declared in the first segment.
+fn join_segments(segments: BTreeMap<u32, Segment>) -> String {
+    let mut charset = None;
+    let mut bytes = Vec::new();
+
+    for (index, segment) in segments {
+        let mut value = segment.value.as_str();
+        if segment.extended {
+            if index == 0 {
+                let mut parts = value.splitn(3, '\'');
+                if let (Some(chr), Some(_lang), Some(rest)) =
+                    (parts.next(), parts.next(), parts.next()) {
+                    charset = charset_for_label(chr, &HashMap::new());
+                    value = rest;
+                }
+            }
+            bytes.extend(percent_decode(value));
+        } else {
+            bytes.extend(value.as_bytes());
+        }
+    }
+
+    match charset {
+        Some(chr) => chr.decode_without_bom_handling(&bytes).0.into_owned(),
+        None => String::from_utf8_lossy(&bytes).into_owned(),
+    }
+}
+
+/// Parses the parameters of a MIME header field value, e.g. the value
+/// of a Content-Type or Content-Disposition field.
+///
+/// Parameter names are returned in lowercase. Quoted values are
+/// unquoted, and RFC 2231 continuations and extended values are joined
+/// and decoded. If a parameter occurs multiple times, the first
+/// occurrence is used.
+pub fn parse_parameters(value: &str) -> HashMap<String, String> {
+    let mut raw: HashMap<String, BTreeMap<u32, Segment>> = HashMap::new();
+    // Skip the main value (e.g. the content type).
+    let mut rest = match value.find(';') {
+        Some(i) => &value[i..],
+        None => "",
+    };
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ';' || c.is_whitespace());
+        if rest.is_empty() {
+            break;
+        }
+
+        let name_end = rest.find(['=', ';']).unwrap_or(rest.len());
+        let name = rest[..name_end].trim().to_lowercase();
+        rest = &rest[name_end..];
+
+        let param_value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let (param_value, after) = parse_value(after.trim_start());
+                rest = after;
+                param_value
+            },
+            // A parameter without a value is malformed, ignore it.
+            None => continue,
+        };
+
+        // Split "name*N*" into the base name, the segment index and whether
+        // the segment is extended (i.e. charset and percent encoded).
+        let (name, extended) = match name.strip_suffix('*') {
+            Some(n) => (n.to_string(), true),
+            None => (name, false),
+        };
+        let (name, index) = match name.rfind('*') {
+            Some(i) if name[i + 1..].parse::<u32>().is_ok() =>
+                (name[..i].to_string(), name[i + 1..].parse().unwrap()),
+            _ => (name, 0),
+        };
+
+        if name.is_empty() {
+            continue;
+        }
+
+        raw.entry(name)
+            .or_default()
+            .entry(index)
+            .or_insert(Segment{value: param_value, extended});
+    }
+
+    raw.into_iter().map(|(name, segments)| (name, join_segments(segments))).collect()
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns all the parameters of the top-level Content-Type header
+    /// field, e.g., `boundary`, `charset`, `protocol`. Parameter names are
+    /// lowercase, and values are unquoted. If the email doesn't have a
+    /// Content-Type header field, the returned map is empty.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let params = email.content_type_params();
+    /// if params.get("report-type").map(String::as_str) == Some("delivery-status") {
+    ///     email.deliver_to_maildir("/my/maildir/bounces")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn content_type_params(&self) -> HashMap<String, String> {
+        self.header_field("Content-Type")
+            .map(parse_parameters)
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of the first header field with the specified name
+    /// in the header of a part of the email, if any.
+    pub(crate) fn part_header_field(&self, part: &PartInfo, name: &str) -> Option<String> {
+        let header = &self.normalized_data[part.header.clone()];
+        let prefix_len = name.len() + 1;
+
+        SliceLines::new(header)
+            .find(|line| {
+                line.len() > prefix_len && line[..name.len()].eq_ignore_ascii_case(name.as_bytes()) &&
+                    line[name.len()] == b':'
+            })
+            .map(|line| String::from_utf8_lossy(&line[prefix_len..]).into_owned())
+    }
+
+    /// Returns the parameters of the Content-Type header field of the
+    /// first text part of the email.
+    fn primary_text_part_params(&self) -> HashMap<String, String> {
+        self.parts
+            .iter()
+            .find(|part| {
+                !part.multipart && part.content_type.as_ref().is_none_or(|ct| ct.starts_with("text/"))
+            })
+            .and_then(|part| self.part_header_field(part, "Content-Type"))
+            .map(|value| parse_parameters(&value))
+            .unwrap_or_default()
+    }
+
+    /// Returns whether the first text part of the email is in the
+    /// `format=flowed` format (RFC 3676), in which lines ending with a
+    /// space are soft line breaks that readers may reflow.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.is_format_flowed() {
+    ///     eprintln!("The text of the email can be reflowed");
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_format_flowed(&self) -> bool {
+        self.primary_text_part_params()
+            .get("format")
+            .is_some_and(|format| format.eq_ignore_ascii_case("flowed"))
+    }
+
+    /// Returns whether the first text part of the email is in the
+    /// `format=flowed` format with `delsp=yes` (RFC 3676), in which case
+    /// the space before each soft line break must be deleted when the
+    /// text is reflowed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let delete_space = email.is_delsp();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_delsp(&self) -> bool {
+        let params = self.primary_text_part_params();
+        params.get("format").is_some_and(|format| format.eq_ignore_ascii_case("flowed")) &&
+        params.get("delsp").is_some_and(|delsp| delsp.eq_ignore_ascii_case("yes"))
+    }
+}
@@ -0,0 +1,495 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Access to the MIME part structure of emails.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::mem;
+use std::ops::Range;
+
+use crate::{BorrowedEmail, EmailRegex, Result};
+use crate::attachment::{decoded_len, trim_final_newline};
+use crate::decode::{base64_decode_into_buf, qp_decode_into_buf};
+use crate::html::html_to_text;
+
+/// Information about a MIME part of an email. The top level of the email
+/// is also considered to be a part.
+///
+/// The ranges of a part refer to the normalized email data, as returned by
+/// [Email::data](type.Email.html#method.data).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartInfo {
+    /// The lowercase content type of the part, e.g., `text/plain` or
+    /// `multipart/alternative`, if specified.
+    pub content_type: Option<String>,
+    /// The lowercase charset the part data was declared to be in, before
+    /// normalization, if specified.
+    pub charset: Option<String>,
+    /// The lowercase content transfer encoding the part data was declared
+    /// to be in, before normalization, if specified.
+    pub encoding: Option<String>,
+    /// Whether the part is a multipart container.
+    pub multipart: bool,
+    /// The nesting depth of the part, with the top level of the email being
+    /// at depth 0.
+    pub depth: usize,
+    /// The range of the header of the part, including the empty line
+    /// that terminates it.
+    pub header: Range<usize>,
+    /// The range of the body of the part. The body of a multipart container
+    /// includes the boundary lines and data of its subparts.
+    pub body: Range<usize>,
+}
+
+impl PartInfo {
+    pub(crate) fn new(depth: usize, start: usize) -> Self {
+        PartInfo{
+            content_type: None,
+            charset: None,
+            encoding: None,
+            multipart: false,
+            depth,
+            header: start..start,
+            body: start..start,
+        }
+    }
+}
+
+/// A leaf MIME part of an email, i.e., a part that isn't a multipart
+/// container, with its normalized body data, as returned by
+/// [Email::into_parts](type.Email.html#method.into_parts).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedPart {
+    /// Information about the part. The ranges refer to the normalized data
+    /// of the consumed email.
+    pub info: PartInfo,
+    /// The normalized body data of the part.
+    pub data: Vec<u8>,
+}
+
+/// A leaf MIME part of an email, i.e., a part that isn't a multipart
+/// container, borrowing its data from the email, as returned by
+/// [Email::parts](type.Email.html#method.parts).
+#[derive(Debug, Clone)]
+pub struct EmailPart<'e> {
+    info: &'e PartInfo,
+    data: &'e [u8],
+    decoded: bool,
+}
+
+impl<'e> EmailPart<'e> {
+    /// Returns information about the part, including its nesting depth and
+    /// its ranges in the normalized email data.
+    pub fn info(&self) -> &'e PartInfo {
+        self.info
+    }
+
+    /// Returns the lowercase content type of the part, if specified.
+    pub fn content_type(&self) -> Option<&'e str> {
+        self.info.content_type.as_deref()
+    }
+
+    /// Returns the lowercase charset the part data was declared to be in,
+    /// if specified. Text data that was decoded during normalization is
+    /// always UTF-8, regardless of the declared charset.
+    pub fn charset(&self) -> Option<&'e str> {
+        self.info.charset.as_deref()
+    }
+
+    /// Returns the lowercase content transfer encoding the part data was
+    /// declared to be in, if specified.
+    pub fn encoding(&self) -> Option<&'e str> {
+        self.info.encoding.as_deref()
+    }
+
+    /// Returns whether the part contains human-readable text, i.e., has a
+    /// `text/*` content type, or no content type at all.
+    pub fn is_text(&self) -> bool {
+        self.content_type().is_none_or(|ct| ct.starts_with("text/"))
+    }
+
+    /// Returns the data of the part, without its content transfer encoding.
+    ///
+    /// Text data that was decoded during normalization is returned as UTF-8
+    /// text, without copying. Other data is decoded according to its
+    /// content transfer encoding (e.g., base64). Data that isn't encoded,
+    /// or can't be decoded, is returned unchanged.
+    pub fn decoded_bytes(&self) -> Cow<'e, [u8]> {
+        if self.decoded {
+            return Cow::Borrowed(self.data);
+        }
+
+        let mut decoded = Vec::new();
+        let result = match self.encoding() {
+            Some("base64") => base64_decode_into_buf(self.data, &mut decoded),
+            Some("quoted-printable") => qp_decode_into_buf(trim_final_newline(self.data), &mut decoded),
+            _ => return Cow::Borrowed(trim_final_newline(self.data)),
+        };
+
+        match result {
+            Ok(()) => Cow::Owned(decoded),
+            Err(_) => Cow::Borrowed(trim_final_newline(self.data)),
+        }
+    }
+
+    /// Returns the length of the [decoded data](#method.decoded_bytes) of
+    /// the part, without decoding data that wasn't decoded during
+    /// normalization.
+    pub(crate) fn decoded_len(&self) -> usize {
+        if self.decoded {
+            return self.data.len();
+        }
+        decoded_len(self.data, self.encoding())
+    }
+
+    /// Returns whether the [decoded data](#method.decoded_bytes) of the part
+    /// matches a regular expression, configured as in
+    /// [EmailRegex](trait.EmailRegex.html). Unlike searching the whole body
+    /// of the email, this allows rules that depend on which part matches.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let parts = email.parts();
+    /// let text = parts.iter().find(|p| p.content_type() == Some("text/plain"));
+    /// let html = parts.iter().find(|p| p.content_type() == Some("text/html"));
+    /// if let (Some(text), Some(html)) = (text, html) {
+    ///     if text.search(r"unsubscribe")? && !html.search(r"unsubscribe")? {
+    ///         email.deliver_to_maildir("/my/maildir/suspicious")?;
+    ///     }
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn search(&self, regex: &str) -> Result<bool> {
+        self.decoded_bytes().as_ref().search(regex)
+    }
+}
+
+/// The strategy [Email::primary_text_part](type.Email.html#method.primary_text_part)
+/// uses to choose the text of an email with multiple text parts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextPartStrategy {
+    /// Use the first text part.
+    First,
+    /// Use the text part with the most decoded data. Among equally large
+    /// parts, the first one is used.
+    Largest,
+    /// Use all the text parts, concatenated in the order they appear.
+    Concatenated,
+}
+
+/// The kind of the readable text of a part, ordered by preference among
+/// the alternatives of a `multipart/alternative` container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum TextKind {
+    Plain,
+    Html,
+    Other,
+}
+
+/// Returns the readable text of a leaf part, and its kind, if it's a text
+/// part. HTML is converted to plain text.
+fn leaf_readable_text(part: &EmailPart) -> Option<(String, TextKind)> {
+    if !part.is_text() {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(part.data);
+    match part.content_type() {
+        None | Some("text/plain") => Some((text.into_owned(), TextKind::Plain)),
+        Some("text/html") => Some((html_to_text(&text), TextKind::Html)),
+        Some(_) => Some((text.into_owned(), TextKind::Other)),
+    }
+}
+
+/// Controls whether [Email::walk_parts](type.Email.html#method.walk_parts)
+/// visits the subparts of a part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Descend {
+    /// Visit the subparts of the part.
+    Into,
+    /// Skip the subparts of the part.
+    Skip,
+}
+
+impl BorrowedEmail<'_> {
+    /// Walks the MIME part tree of the email in depth-first order, calling
+    /// the visitor for each part, starting with the top level of the email.
+    ///
+    /// The visitor is passed information about the part, and about its
+    /// ancestors, ordered from the top level to the direct parent of the
+    /// part. The visitor's return value controls whether the subparts of
+    /// the part are visited.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Descend, Email};
+    /// let email = Email::from_stdin()?;
+    /// let mut text_types = Vec::new();
+    /// // Find the types of the first text part of each alternative.
+    /// email.walk_parts(|part, ancestors| {
+    ///     let in_alternative = ancestors.last().is_some_and(|p| {
+    ///         p.content_type.as_deref() == Some("multipart/alternative")
+    ///     });
+    ///     if in_alternative && text_types.len() < 1 {
+    ///         text_types.extend(part.content_type.clone());
+    ///     }
+    ///     Descend::Into
+    /// });
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn walk_parts(&self, mut visitor: impl FnMut(&PartInfo, &[PartInfo]) -> Descend) {
+        let mut ancestors: Vec<PartInfo> = Vec::new();
+        // The depth below which parts are skipped, if any.
+        let mut skip_below = None;
+
+        for part in &self.parts {
+            if skip_below.is_some_and(|depth| part.depth > depth) {
+                continue;
+            }
+            skip_below = None;
+
+            ancestors.truncate(part.depth);
+            if visitor(part, &ancestors) == Descend::Skip {
+                skip_below = Some(part.depth);
+            }
+            ancestors.push(part.clone());
+        }
+    }
+
+    /// Returns the leaf MIME parts of the email, i.e., the parts that aren't
+    /// multipart containers, in the order they appear in the email. Parts
+    /// of nested multipart containers are included, and their nesting depth
+    /// is available through [EmailPart::info](struct.EmailPart.html#method.info).
+    /// Use [walk_parts](#method.walk_parts) to also inspect the containers.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// // Prefer the plain text alternative, and skip images.
+    /// let text = email.parts()
+    ///     .into_iter()
+    ///     .filter(|part| !part.content_type().is_some_and(|ct| ct.starts_with("image/")))
+    ///     .find(|part| part.content_type().is_none_or(|ct| ct == "text/plain"))
+    ///     .map(|part| part.decoded_bytes());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn parts(&self) -> Vec<EmailPart<'_>> {
+        self.parts
+            .iter()
+            .filter(|info| !info.multipart)
+            .map(|info| {
+                let body_part = self.body_parts
+                    .iter()
+                    .find(|part| !part.multipart && part.range == info.body);
+                EmailPart{
+                    info,
+                    data: &self.normalized_data[info.body.clone()],
+                    decoded: body_part.is_some_and(|part| part.decoded),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the text of the email, chosen from its text parts with the
+    /// specified strategy, or `None` if the email has no text parts. Text
+    /// parts are the leaf parts with a `text/*` content type, or no content
+    /// type at all.
+    ///
+    /// Unlike [body_text](#method.body_text), which picks the best
+    /// alternative of each `multipart/alternative` container and converts
+    /// HTML to plain text, this returns the decoded text parts as they
+    /// are, and allows treating a single part (e.g., the cover note of a
+    /// `multipart/mixed` email) as the message text.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, TextPartStrategy};
+    /// let email = Email::from_stdin()?;
+    /// let text = email.primary_text_part(TextPartStrategy::First).unwrap_or_default();
+    /// if text.contains("URGENT") {
+    ///     email.deliver_to_maildir("/my/maildir/urgent")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn primary_text_part(&self, strategy: TextPartStrategy) -> Option<String> {
+        let texts: Vec<Cow<[u8]>> = self.parts()
+            .iter()
+            .filter(|part| part.is_text())
+            .map(|part| part.decoded_bytes())
+            .collect();
+
+        let text = match strategy {
+            TextPartStrategy::First => texts.into_iter().next()?,
+            TextPartStrategy::Largest => {
+                // max_by_key returns the last maximum, so search in reverse
+                // to use the first one.
+                texts.into_iter().rev().max_by_key(|text| text.len())?
+            },
+            TextPartStrategy::Concatenated => {
+                if texts.is_empty() {
+                    return None;
+                }
+                Cow::Owned(texts.concat())
+            },
+        };
+
+        Some(String::from_utf8_lossy(&text).into_owned())
+    }
+
+    /// Returns the readable text of the part at the specified index of the
+    /// part tree, and the most preferred kind of text it contains, if it
+    /// contains any text. `leaves` holds the leaf parts of the email, at
+    /// the indices of their information in the part tree.
+    fn readable_text(&self, index: usize, leaves: &[Option<EmailPart>]) -> Option<(String, TextKind)> {
+        let part = &self.parts[index];
+        if !part.multipart {
+            return leaves[index].as_ref().and_then(leaf_readable_text);
+        }
+
+        let children = self.parts[index + 1..]
+            .iter()
+            .take_while(|p| p.depth > part.depth)
+            .enumerate()
+            .filter(|(_, p)| p.depth == part.depth + 1)
+            .filter_map(|(i, _)| self.readable_text(index + 1 + i, leaves));
+
+        if part.content_type.as_deref() == Some("multipart/alternative") {
+            // min_by_key returns the first of equally preferred alternatives.
+            return children.min_by_key(|(_, kind)| *kind);
+        }
+
+        let texts: Vec<(String, TextKind)> = children.collect();
+        let kind = texts.iter().map(|(_, kind)| *kind).min()?;
+        Some((texts.into_iter().map(|(text, _)| text).collect(), kind))
+    }
+
+    /// Returns the readable text of the email, as described in
+    /// [body_text](#method.body_text).
+    pub(crate) fn readable_body_text(&self) -> String {
+        let mut leaves = self.parts().into_iter();
+        let leaves: Vec<Option<EmailPart>> = self.parts
+            .iter()
+            .map(|info| if info.multipart { None } else { leaves.next() })
+            .collect();
+
+        if self.parts.is_empty() {
+            return String::new();
+        }
+        self.readable_text(0, &leaves).map(|(text, _)| text).unwrap_or_default()
+    }
+
+    /// Returns the data of the top-level multipart container of the email
+    /// that is accepted by the predicate, i.e., its preamble or epilogue.
+    fn multipart_data(&self, predicate: impl Fn(&Range<usize>, &[&PartInfo]) -> bool) -> Option<&[u8]> {
+        let top = self.parts.first().filter(|top| top.multipart)?;
+        let children: Vec<&PartInfo> = self.parts.iter().filter(|p| p.depth == 1).collect();
+
+        self.body_parts
+            .iter()
+            .filter(|part| part.multipart && top.body.start <= part.range.start && part.range.end <= top.body.end)
+            .find(|part| predicate(&part.range, &children))
+            .map(|part| &self.normalized_data[part.range.clone()])
+    }
+
+    /// Returns the preamble of a multipart email, i.e., the data between
+    /// the header and the first boundary line, if any. MIME readers ignore
+    /// the preamble, which often contains a note for readers that don't
+    /// support MIME, like "This is a multi-part message in MIME format.".
+    ///
+    /// The preamble is part of the [body](#method.body) data, but isn't
+    /// part of the [body_text](#method.body_text).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if let Some(preamble) = email.preamble() {
+    ///     eprintln!("Preamble: {}", String::from_utf8_lossy(preamble));
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn preamble(&self) -> Option<&[u8]> {
+        self.multipart_data(|range, children| {
+            children.first().is_none_or(|first| range.end <= first.header.start)
+        })
+    }
+
+    /// Returns the epilogue of a multipart email, i.e., the data after the
+    /// final boundary line, if any. Like the preamble, the epilogue is
+    /// ignored by MIME readers, and is part of the [body](#method.body)
+    /// data, but not of the [body_text](#method.body_text).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let has_epilogue = email.epilogue().is_some();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn epilogue(&self) -> Option<&[u8]> {
+        self.multipart_data(|range, children| {
+            children.last().is_some_and(|last| range.start >= last.body.end)
+        })
+    }
+
+    /// Consumes the email, returning the map of lowercase header field
+    /// names to values, and the leaf parts of the email (i.e., the parts
+    /// that aren't multipart containers) with their normalized body data,
+    /// in the order they appear in the email.
+    ///
+    /// The body data is moved out of the normalized email data, so, unlike
+    /// with [walk_parts](#method.walk_parts), the parts can be processed
+    /// after the email is gone, e.g., by worker threads. The data of the
+    /// first part reuses the buffer of the normalized email data.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let (fields, parts) = email.into_parts();
+    /// let workers: Vec<_> = parts
+    ///     .into_iter()
+    ///     .map(|part| std::thread::spawn(move || part.data.len()))
+    ///     .collect();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn into_parts(self) -> (HashMap<String, Vec<String>>, Vec<OwnedPart>) {
+        let mut data = self.normalized_data;
+        let leaves: Vec<PartInfo> = self.parts.into_iter().filter(|part| !part.multipart).collect();
+        let mut parts = Vec::with_capacity(leaves.len());
+
+        // Move the part data out from the end, so that the ranges of the
+        // remaining parts stay valid.
+        for (i, info) in leaves.into_iter().enumerate().rev() {
+            data.truncate(info.body.end);
+            let part_data = if i == 0 {
+                data.drain(..info.body.start);
+                mem::take(&mut data)
+            } else {
+                data.split_off(info.body.start)
+            };
+            parts.push(OwnedPart{info, data: part_data});
+        }
+        parts.reverse();
+
+        (self.fields, parts)
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Short textual previews of emails.
+
+use crate::BorrowedEmail;
+use crate::quote::new_content_lines;
+
+/// The maximum number of characters in a preview.
+const PREVIEW_MAX_CHARS: usize = 200;
+
+/// Collapses runs of whitespace in some text to single spaces and limits
+/// the text to `PREVIEW_MAX_CHARS` characters, marking truncated text with
+/// an ellipsis. Returns `None` if the text contains only whitespace.
+fn make_preview<'a>(words: impl Iterator<Item=&'a str>) -> Option<String> {
+    let mut preview = String::new();
+    let mut truncated = false;
+
+    for word in words {
+        let len = preview.chars().count();
+        let word_len = word.chars().count();
+        let sep = if preview.is_empty() { 0 } else { 1 };
+        if len + sep + word_len > PREVIEW_MAX_CHARS {
+            let room = PREVIEW_MAX_CHARS.saturating_sub(len + sep);
+            if preview.is_empty() {
+                preview.extend(word.chars().take(room));
+            }
+            truncated = true;
+            break;
+        }
+        if sep == 1 {
+            preview.push(' ');
+        }
+        preview.push_str(word);
+    }
+
+    if preview.is_empty() {
+        return None;
+    }
+    if truncated {
+        preview.push('…');
+    }
+    Some(preview)
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns a short, single-line preview of the text body of the email,
+    /// or `None` if the email has no (new) text content.
+    ///
+    /// The preview consists of the new content of the text parts of the
+    /// email, i.e., excluding quoted lines and the signature block, with
+    /// runs of whitespace collapsed to single spaces. Long previews are
+    /// truncated at a word boundary to at most 200 characters and marked
+    /// with a trailing ellipsis (`…`).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if let Some(preview) = email.body_preview() {
+    ///     eprintln!("New email: {}", preview);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn body_preview(&self) -> Option<String> {
+        let body_text = self.body_text();
+        make_preview(new_content_lines(&body_text).flat_map(str::split_whitespace))
+    }
+
+    /// Returns a short, single-line summary of the email, suitable for
+    /// notifications and logs.
+    ///
+    /// If the email has a non-empty X-Summary header field, its decoded
+    /// value is used, otherwise the summary falls back to the
+    /// [body_preview](#method.body_preview). In both cases whitespace is
+    /// collapsed and the summary is limited to 200 characters. Returns
+    /// `None` if neither source provides any text.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let snippet = email.snippet().unwrap_or_default();
+    /// eprintln!("New email: {}", snippet);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn snippet(&self) -> Option<String> {
+        self.header_field("X-Summary")
+            .and_then(|summary| make_preview(summary.split_whitespace()))
+            .or_else(|| self.body_preview())
+    }
+}
@@ -11,9 +11,9 @@
 use std::io::Write;
 use std::process::{Command, Output, Stdio};
 
-use crate::{Email, Result};
+use crate::{BorrowedEmail, Email, Result};
 
-impl Email {
+impl BorrowedEmail<'_> {
     /// Filters the contents of the email using an external command,
     /// returning a new email with the filtered contents.
     ///
@@ -62,7 +62,7 @@ impl Email {
         child.stdin
             .as_mut()
             .ok_or("Failed to write to stdin")?
-            .write_all(&self.data)?;
+            .write_all(&self.raw_data)?;
 
         Ok(child.wait_with_output()?)
     }
@@ -0,0 +1,253 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Maildir++ quota support, using the `maildirsize` file of maildirs.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::Result;
+use crate::deliver::DeliveryError;
+
+/// The name of the file tracking the quota and the usage of a maildir.
+const MAILDIRSIZE: &str = "maildirsize";
+
+/// The length above which a maildirsize file is recalculated, so that it
+/// stays small and quick to read.
+const MAX_MAILDIRSIZE_LEN: u64 = 5120;
+
+/// The age above which a maildirsize file reporting that the maildir is
+/// over quota is recalculated, in case it's inaccurate.
+const MAX_OVER_QUOTA_AGE: Duration = Duration::from_secs(15 * 60);
+
+/// The limits of a Maildir++ quota.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct QuotaLimits {
+    /// The maximum total size of the emails in bytes, if limited.
+    pub bytes: Option<u64>,
+    /// The maximum number of emails, if limited.
+    pub count: Option<u64>,
+}
+
+/// Parses the quota definition in the first line of a maildirsize file,
+/// e.g., `1000000S,1000C`. Either limit may be missing, and limits of 0
+/// are considered to be unlimited.
+pub(crate) fn parse_quota_definition(line: &str) -> QuotaLimits {
+    let mut limits = QuotaLimits::default();
+
+    for item in line.trim().split(',') {
+        let item = item.trim();
+        if let Some(bytes) = item.strip_suffix('S') {
+            limits.bytes = bytes.parse().ok().filter(|&b| b > 0);
+        } else if let Some(count) = item.strip_suffix('C') {
+            limits.count = count.parse().ok().filter(|&c| c > 0);
+        }
+    }
+
+    limits
+}
+
+/// The quota and the usage of a maildir, as recorded in its maildirsize
+/// file.
+struct MaildirSize {
+    definition: String,
+    limits: QuotaLimits,
+    bytes: i64,
+    count: i64,
+    len: u64,
+    modified: SystemTime,
+}
+
+impl MaildirSize {
+    /// Returns whether adding an email of the specified size would exceed
+    /// the quota.
+    fn would_exceed(&self, size: u64) -> bool {
+        let bytes = self.bytes.max(0) as u64 + size;
+        let count = self.count.max(0) as u64 + 1;
+        self.limits.bytes.is_some_and(|limit| bytes > limit) ||
+            self.limits.count.is_some_and(|limit| count > limit)
+    }
+}
+
+/// Reads the maildirsize file of a maildir, summing the size and count of
+/// all the usage lines that follow the quota definition. Returns `None` if
+/// the maildir doesn't have a maildirsize file, i.e., it has no quota.
+fn read_maildirsize(root: &Path) -> Result<Option<MaildirSize>> {
+    let path = root.join(MAILDIRSIZE);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let metadata = fs::metadata(&path)?;
+
+    let mut lines = contents.lines();
+    let definition = lines.next().unwrap_or("").to_string();
+    let mut bytes = 0i64;
+    let mut count = 0i64;
+    for line in lines {
+        let mut fields = line.split_whitespace().map(|f| f.parse::<i64>());
+        if let (Some(Ok(line_bytes)), Some(Ok(line_count))) = (fields.next(), fields.next()) {
+            bytes += line_bytes;
+            count += line_count;
+        }
+    }
+
+    Ok(
+        Some(
+            MaildirSize{
+                limits: parse_quota_definition(&definition),
+                definition,
+                bytes,
+                count,
+                len: metadata.len(),
+                modified: metadata.modified()?,
+            }
+        )
+    )
+}
+
+/// Returns the size of an email in a maildir, preferring the `S=<size>`
+/// extension of its filename, which holds the actual size of compressed
+/// emails, to the size of the file.
+fn email_size(entry: &fs::DirEntry) -> Result<u64> {
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    let base = name.split(':').next().unwrap();
+    let size = base.split(',')
+        .find_map(|ext| ext.strip_prefix("S="))
+        .and_then(|size| size.parse().ok());
+
+    match size {
+        Some(size) => Ok(size),
+        None => Ok(entry.metadata()?.len()),
+    }
+}
+
+/// Adds the sizes and the number of the emails in the new/ and cur/
+/// directories of a maildir folder to the specified totals. Missing
+/// directories are considered empty.
+fn add_folder_usage(folder: &Path, bytes: &mut i64, count: &mut i64) -> Result<()> {
+    for subdir in &["new", "cur"] {
+        let entries = match fs::read_dir(folder.join(subdir)) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                *bytes += email_size(&entry)? as i64;
+                *count += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recalculates the usage of a maildir from the emails in the new/ and cur/
+/// directories of the maildir and of its Maildir++ subfolders (e.g.,
+/// `.Sent`), which share its quota, and replaces its maildirsize file with one containing only
+/// the quota definition and the recalculated usage. The new file is first
+/// written to the specified temporary path, so that it's replaced
+/// atomically.
+fn recalculate_maildirsize(root: &Path, old: MaildirSize, tmp_path: PathBuf) -> Result<MaildirSize> {
+    let mut bytes = 0;
+    let mut count = 0;
+    add_folder_usage(root, &mut bytes, &mut count)?;
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') && entry.file_type()?.is_dir() {
+            add_folder_usage(&entry.path(), &mut bytes, &mut count)?;
+        }
+    }
+
+    let contents = format!("{}\n{} {}\n", old.definition, bytes, count);
+    fs::write(&tmp_path, &contents)?;
+    if let Err(err) = fs::rename(&tmp_path, root.join(MAILDIRSIZE)) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(err.into());
+    }
+
+    Ok(
+        MaildirSize{
+            bytes,
+            count,
+            len: contents.len() as u64,
+            modified: SystemTime::now(),
+            ..old
+        }
+    )
+}
+
+/// Fails with `DeliveryError::QuotaExceeded` if delivering an email of the
+/// specified size to a maildir would exceed its Maildir++ quota.
+///
+/// The maildirsize file is recalculated if it has grown too large, or if it
+/// reports that the maildir is over quota but hasn't been updated for a
+/// while, in which case it may be inaccurate. The temporary path for the
+/// recalculated file is requested only if needed.
+pub(crate) fn check_quota<F>(root: &Path, size: u64, tmp_path: F) -> Result<()>
+where
+    F: FnOnce() -> Result<PathBuf>
+{
+    let mut maildirsize = match read_maildirsize(root)? {
+        Some(maildirsize) => maildirsize,
+        None => return Ok(()),
+    };
+
+    let is_stale = || {
+        maildirsize.modified.elapsed().is_ok_and(|age| age > MAX_OVER_QUOTA_AGE)
+    };
+    if maildirsize.len > MAX_MAILDIRSIZE_LEN || (maildirsize.would_exceed(size) && is_stale()) {
+        maildirsize = recalculate_maildirsize(root, maildirsize, tmp_path()?)?;
+    }
+
+    if maildirsize.would_exceed(size) {
+        return Err(DeliveryError::QuotaExceeded.into());
+    }
+
+    Ok(())
+}
+
+/// Records the delivery of an email of the specified size in the
+/// maildirsize file of a maildir, if the maildir has one.
+pub(crate) fn record_delivery(root: &Path, size: u64) -> Result<()> {
+    let result = OpenOptions::new().append(true).open(root.join(MAILDIRSIZE));
+    let mut file = match result {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+
+    // Write the whole line at once, so that concurrent appends don't mix.
+    file.write_all(format!("{} 1\n", size).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_quota_definitions() {
+        assert_eq!(
+            parse_quota_definition("1000000S,1000C"),
+            QuotaLimits{bytes: Some(1000000), count: Some(1000)}
+        );
+        assert_eq!(
+            parse_quota_definition("500C, 2048S"),
+            QuotaLimits{bytes: Some(2048), count: Some(500)}
+        );
+        assert_eq!(parse_quota_definition("1000S"), QuotaLimits{bytes: Some(1000), count: None});
+        assert_eq!(parse_quota_definition("0S,0C"), QuotaLimits::default());
+        assert_eq!(parse_quota_definition("garbage"), QuotaLimits::default());
+    }
+}
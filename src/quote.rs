@@ -0,0 +1,81 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Analysis of quoted text in replies.
+
+use crate::{BorrowedEmail, Result};
+use crate::regex::EmailRegex;
+
+/// Returns whether a line of text is quoted, i.e., starts with `>`, at any
+/// quoting level.
+fn is_quoted(line: &str) -> bool {
+    line.trim_start().starts_with('>')
+}
+
+/// Returns the lines of a text that are not quoted (i.e., don't start with
+/// `>`), up to the start of the signature block (a `-- ` line).
+pub(crate) fn new_content_lines(text: &str) -> impl Iterator<Item=&str> {
+    text.lines()
+        .take_while(|l| l.trim_end_matches('\r') != "-- ")
+        .filter(|l| !is_quoted(l))
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the fraction of the lines of the body text, as returned by
+    /// [body_text](#method.body_text), that are quoted, i.e., start with
+    /// `>` (including nested quotes like `>>`). Blank lines are not
+    /// counted. Returns 0.0 if the body text has no non-blank lines.
+    ///
+    /// A high ratio indicates a short reply to a long quoted thread.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.quote_ratio() > 0.9 {
+    ///     email.deliver_to_maildir("/my/maildir/terse-replies")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn quote_ratio(&self) -> f32 {
+        let body_text = self.body_text();
+        let (quoted, total) = body_text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .fold((0usize, 0usize), |(quoted, total), line| {
+                (quoted + is_quoted(line) as usize, total + 1)
+            });
+
+        if total == 0 { 0.0 } else { quoted as f32 / total as f32 }
+    }
+
+    /// Returns whether the new content of the email body, i.e., the body
+    /// text excluding quoted lines (starting with `>`) and the signature
+    /// block (following a `-- ` line), matches a regular expression.
+    ///
+    /// This is useful to avoid false positives when searching replies that
+    /// quote long threads. The regular expression is configured as in
+    /// [EmailRegex](trait.EmailRegex.html).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.search_new_content(r"\bURGENT\b")? {
+    ///     email.deliver_to_maildir("/my/maildir/urgent")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn search_new_content(&self, regex: &str) -> Result<bool> {
+        let body_text = self.body_text();
+        let new_content = new_content_lines(&body_text).collect::<Vec<_>>().join("\n");
+        new_content.as_bytes().search(regex)
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Reconstruction of MIME encoded email data from normalized email data.
+
+use ::regex::bytes::{Regex, RegexBuilder};
+use lazy_static::lazy_static;
+
+use crate::BorrowedEmail;
+use crate::encode::{
+    base64_encode_into_buf, encode_header_value, fold_header_field, qp_encode_into_buf, MAX_ENCODED_LINE_LEN,
+};
+use crate::normalize::{BodyPart, Element, EmailParser, CHARSET_PARAM_REGEX};
+use crate::util::{fnv1a64, SliceLines};
+
+lazy_static! {
+    static ref BOUNDARY_PARAM_REGEX: Regex =
+        RegexBuilder::new(r#"(;\s*boundary\s*=\s*)("[^"]*"|[^;\s]+)"#)
+            .case_insensitive(true)
+            .build().unwrap();
+}
+
+/// A content transfer encoding to apply to the data of a text part.
+#[derive(PartialEq)]
+enum TransferEncoding {
+    Base64,
+    QuotedPrintable,
+}
+
+/// Returns the content transfer encoding a decoded text part should be
+/// re-encoded with, given its declared encoding. Parts are re-encoded with
+/// their original encoding. Parts without an encoding that can't be sent
+/// as 7bit data are encoded as quoted-printable.
+fn transfer_encoding_for(data: &[u8], declared: Option<&str>) -> Option<TransferEncoding> {
+    match declared {
+        Some("base64") => Some(TransferEncoding::Base64),
+        Some("quoted-printable") => Some(TransferEncoding::QuotedPrintable),
+        Some("8bit") | Some("binary") => None,
+        _ if !data.is_ascii() || SliceLines::new(data).any(|l| l.len() > 1000) =>
+            Some(TransferEncoding::QuotedPrintable),
+        _ => None,
+    }
+}
+
+/// Returns the length of the line terminator at the end of some data.
+fn trailing_newline_len(data: &[u8]) -> usize {
+    if data.ends_with(b"\r\n") {
+        2
+    } else if data.ends_with(b"\n") {
+        1
+    } else {
+        0
+    }
+}
+
+/// Returns the line terminator of a line, or LF if the line isn't
+/// terminated.
+fn newline_of(line: &[u8]) -> &'static [u8] {
+    if line.ends_with(b"\r\n") { b"\r\n" } else { b"\n" }
+}
+
+/// Returns the lowercase name of a header field.
+fn field_name(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == b':').unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).trim().to_lowercase()
+}
+
+/// Encodes any non-ASCII text in a header field as MIME encoded-words,
+/// folding the field if it becomes too long. Fields that aren't valid UTF-8
+/// are left unchanged.
+fn encode_header_field(field: &[u8]) -> Vec<u8> {
+    let text = match std::str::from_utf8(field) {
+        Ok(text) if !text.is_ascii() => text,
+        _ => return field.to_vec(),
+    };
+
+    let newline = std::str::from_utf8(newline_of(field)).unwrap();
+    let content = text.trim_end_matches(['\r', '\n']);
+    let (name, value) = content.split_once(':').unwrap_or((content, ""));
+    let encoded = format!("{}:{}", name, encode_header_value(value));
+
+    fold_header_field(&encoded, newline).into_bytes()
+}
+
+/// The body of a part, as seen when its header is written.
+struct PendingBody<'a> {
+    data: &'a [u8],
+    encoding: Option<String>,
+    decoded: bool,
+}
+
+/// Rebuilds MIME encoded email data from normalized email data.
+struct Reencoder<'a> {
+    out: Vec<u8>,
+    body_parts: &'a [BodyPart],
+    // The offset of the normalized data, for locating body parts.
+    base: usize,
+    // The seed used to generate unique boundaries.
+    boundary_seed: u64,
+    // Pairs of original and regenerated boundaries.
+    boundaries: Vec<(Vec<u8>, Vec<u8>)>,
+    // The fields of the header being processed.
+    header: Vec<Vec<u8>>,
+    // The empty line terminating the header being processed, if seen.
+    separator: Option<&'a [u8]>,
+    in_header: bool,
+    top_level: bool,
+}
+
+impl<'a> Reencoder<'a> {
+    fn new(data: &'a [u8], body_parts: &'a [BodyPart]) -> Self {
+        Reencoder{
+            out: Vec::with_capacity(data.len()),
+            body_parts,
+            base: data.as_ptr() as usize,
+            boundary_seed: fnv1a64(data),
+            boundaries: Vec::new(),
+            header: Vec::new(),
+            separator: None,
+            in_header: true,
+            top_level: true,
+        }
+    }
+
+    // Returns whether the body data at the specified location was decoded
+    // during normalization.
+    fn was_decoded(&self, data: &[u8]) -> bool {
+        let start = data.as_ptr() as usize - self.base;
+        self.body_parts
+            .iter()
+            .find(|p| p.range.start == start)
+            .is_some_and(|p| p.decoded && !p.multipart)
+    }
+
+    // Returns a new boundary for a multipart container with the specified
+    // original boundary. The "=_" sequence can't appear in base64 or
+    // quoted-printable encoded data, so it can't clash with part contents.
+    fn regenerate_boundary(&mut self, old: &[u8]) -> Vec<u8> {
+        let new = format!("=_mda_{:016x}_{}", self.boundary_seed, self.boundaries.len()).into_bytes();
+        self.boundaries.push((old.to_vec(), new.clone()));
+        new
+    }
+
+    // Rewrites a line if it is a boundary line for one of the original
+    // boundaries.
+    fn rewrite_boundary_line(&self, line: &[u8]) -> Vec<u8> {
+        let content = line.strip_suffix(b"\n").unwrap_or(line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        let newline = &line[content.len()..];
+
+        if let Some(rest) = content.strip_prefix(b"--") {
+            let (boundary, end) = match rest.strip_suffix(b"--") {
+                Some(boundary) => (boundary, &b"--"[..]),
+                None => (rest, &b""[..]),
+            };
+            if let Some((_, new)) = self.boundaries.iter().rev().find(|(old, _)| old == boundary) {
+                return [b"--", new.as_slice(), end, newline].concat();
+            }
+        }
+
+        line.to_vec()
+    }
+
+    // Writes the pending header, rewriting its fields to match the way the
+    // body will be written.
+    fn flush_header(&mut self, body: Option<&PendingBody>) {
+        if !self.in_header && self.header.is_empty() && self.separator.is_none() {
+            return;
+        }
+
+        let fields = std::mem::take(&mut self.header);
+        let newline = fields.last().map_or(&b"\n"[..], |f| newline_of(f));
+        let text_body = body.filter(|b| b.decoded);
+        let transfer_encoding =
+            text_body.and_then(|b| transfer_encoding_for(b.data, b.encoding.as_deref()));
+        let needs_charset = text_body.is_some_and(|b| !b.data.is_ascii());
+        let mut has_content_type = false;
+        let mut has_encoding = false;
+        let mut has_mime_version = false;
+
+        for field in fields {
+            let mut field = match field_name(&field).as_str() {
+                "content-type" => {
+                    has_content_type = true;
+                    self.rewrite_content_type(&field, text_body.is_some(), needs_charset)
+                },
+                "content-transfer-encoding" => {
+                    has_encoding = true;
+                    match &transfer_encoding {
+                        Some(encoding) => encoding_field(&field, encoding),
+                        None => field,
+                    }
+                },
+                "mime-version" => {
+                    has_mime_version = true;
+                    field
+                },
+                _ => field,
+            };
+            field = encode_header_field(&field);
+            self.out.extend(field);
+        }
+
+        let adds_content_type = needs_charset && !has_content_type;
+        let adds_encoding = transfer_encoding.is_some() && !has_encoding;
+        if self.top_level && !has_mime_version && (adds_content_type || adds_encoding) {
+            self.out.extend(b"MIME-Version: 1.0");
+            self.out.extend(newline);
+        }
+        if adds_content_type {
+            self.out.extend(b"Content-Type: text/plain; charset=utf-8");
+            self.out.extend(newline);
+        }
+        if let (true, Some(encoding)) = (adds_encoding, &transfer_encoding) {
+            self.out.extend(encoding_field(b"Content-Transfer-Encoding:", encoding));
+            self.out.extend(newline);
+        }
+
+        if let Some(separator) = self.separator.take() {
+            self.out.extend(separator);
+        }
+        self.in_header = false;
+        self.top_level = false;
+    }
+
+    // Rewrites the boundary parameter of a multipart Content-Type header
+    // field, and the charset parameter of the field of a decoded text part.
+    fn rewrite_content_type(&mut self, field: &[u8], decoded_text: bool, needs_charset: bool) -> Vec<u8> {
+        if let Some(captures) = BOUNDARY_PARAM_REGEX.captures(field) {
+            let old = captures.get(2).unwrap().as_bytes();
+            let old = old.strip_prefix(b"\"").and_then(|b| b.strip_suffix(b"\"")).unwrap_or(old);
+            let new = self.regenerate_boundary(old);
+            let mut replacement = captures[1].to_vec();
+            replacement.extend(b"\"");
+            replacement.extend(new);
+            replacement.extend(b"\"");
+            return BOUNDARY_PARAM_REGEX.replace(field, replacement.as_slice()).into_owned();
+        }
+
+        if decoded_text {
+            if CHARSET_PARAM_REGEX.is_match(field) {
+                return CHARSET_PARAM_REGEX.replace(field, &b"${1}utf-8"[..]).into_owned();
+            }
+            if needs_charset {
+                let content_len = field.len() - trailing_newline_len(field);
+                let mut new = field[..content_len].to_vec();
+                new.extend(b"; charset=utf-8");
+                new.extend(&field[content_len..]);
+                return new;
+            }
+        }
+
+        field.to_vec()
+    }
+
+    fn write_body(&mut self, body: &PendingBody) {
+        let transfer_encoding = if body.decoded {
+            transfer_encoding_for(body.data, body.encoding.as_deref())
+        } else {
+            None
+        };
+
+        match transfer_encoding {
+            Some(encoding) => {
+                // The final line terminator belongs to the following
+                // boundary line (or the end of the email), not the content.
+                let content_len = body.data.len() - trailing_newline_len(body.data);
+                let (content, newline) = body.data.split_at(content_len);
+                let line_newline = if newline.is_empty() { newline_of(body.data) } else { newline };
+
+                match encoding {
+                    TransferEncoding::Base64 =>
+                        base64_encode_into_buf(content, Some(MAX_ENCODED_LINE_LEN), line_newline, &mut self.out),
+                    TransferEncoding::QuotedPrintable =>
+                        qp_encode_into_buf(content, Some(MAX_ENCODED_LINE_LEN), line_newline, &mut self.out),
+                }
+                self.out.extend(newline);
+            },
+            None if self.boundaries.is_empty() => self.out.extend(body.data),
+            None => {
+                for line in SliceLines::new(body.data) {
+                    let line = self.rewrite_boundary_line(line);
+                    self.out.extend(line);
+                }
+            },
+        }
+    }
+}
+
+/// Returns a Content-Transfer-Encoding header field with the name of an
+/// existing field (to preserve its case) and the specified encoding.
+fn encoding_field(field: &[u8], encoding: &TransferEncoding) -> Vec<u8> {
+    let name_len = field.iter().position(|&b| b == b':').unwrap_or(field.len());
+    let mut new = field[..name_len].to_vec();
+    new.extend(match encoding {
+        TransferEncoding::Base64 => &b": base64"[..],
+        TransferEncoding::QuotedPrintable => &b": quoted-printable"[..],
+    });
+    new.extend(&field[field.len() - trailing_newline_len(field)..]);
+    new
+}
+
+/// Rebuilds MIME encoded email data from normalized email data and the
+/// information about its body parts.
+fn reencode_email(data: &[u8], body_parts: &[BodyPart]) -> Vec<u8> {
+    let mut reencoder = Reencoder::new(data, body_parts);
+    // The normalized data was already parsed within the part depth limit,
+    // and any deeper parts must be treated consistently with the way they
+    // were normalized, so don't impose a further limit.
+    let parser = EmailParser::new(data, usize::MAX, None);
+
+    for element in parser {
+        match element {
+            Element::HeaderField{data, ..} => reencoder.header.push(data),
+            Element::Verbatim{data} if reencoder.in_header && (data == b"\n" || data == b"\r\n") => {
+                reencoder.separator = Some(data);
+            },
+            Element::Verbatim{data} => {
+                reencoder.flush_header(None);
+                let line = reencoder.rewrite_boundary_line(data);
+                reencoder.out.extend(line);
+                if !data.trim_ascii_end().ends_with(b"--") {
+                    reencoder.in_header = true;
+                }
+            },
+            Element::Body{data, encoding, ..} => {
+                let body = PendingBody{data, encoding, decoded: reencoder.was_decoded(data)};
+                reencoder.flush_header(Some(&body));
+                reencoder.write_body(&body);
+            },
+        }
+    }
+    reencoder.flush_header(None);
+
+    reencoder.out
+}
+
+impl BorrowedEmail<'_> {
+    /// Reconstructs standards-compliant MIME email data from the
+    /// normalized email data.
+    ///
+    /// This is the counterpart of normalization: text parts that were
+    /// decoded during normalization (and possibly modified with a
+    /// [part_transform](struct.NormalizationOptions.html#structfield.part_transform))
+    /// are re-encoded with their original content transfer encoding
+    /// (base64 or quoted-printable) and declared to be in the `utf-8`
+    /// charset. Decoded text parts without an encoding that contain
+    /// non-ASCII data are encoded as quoted-printable. Non-ASCII text in
+    /// header fields is encoded as MIME encoded-words, and the boundaries
+    /// of multipart containers are regenerated. Parts that weren't decoded,
+    /// e.g., attachments, are included unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, NormalizationOptions};
+    /// let options = NormalizationOptions{
+    ///     part_transform: Some(Box::new(|_, data| {
+    ///         *data = String::from_utf8_lossy(data).replace("secret", "******").into_bytes();
+    ///     })),
+    ///     ..Default::default()
+    /// };
+    /// let data = std::fs::read("/my/maildir/cur/1546300800.1234_0.host:2,")?;
+    /// let email = Email::from_vec_with_options(data, options)?;
+    /// let redacted = Email::from_vec(email.reencode())?;
+    /// redacted.deliver_to_maildir("/my/maildir/")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn reencode(&self) -> Vec<u8> {
+        reencode_email(&self.normalized_data, &self.body_parts)
+    }
+}
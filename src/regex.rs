@@ -10,9 +10,67 @@
 
 use std::str;
 
-use regex::bytes::{RegexBuilder, RegexSetBuilder, SetMatches, Captures};
+use regex::bytes::{Regex, RegexBuilder, RegexSet, RegexSetBuilder, SetMatches, Captures};
 
-use crate::Result;
+use crate::{BorrowedEmail, Result};
+
+/// Options controlling how regular expressions are configured, for the
+/// `*_opts` methods of [EmailRegex](trait.EmailRegex.html).
+///
+/// The default options configure a case-insensitive and multi-line search,
+/// as used by the other methods of [EmailRegex](trait.EmailRegex.html).
+///
+/// # Example
+///
+/// ```no_run
+/// use mda::{Email, EmailRegex, SearchOptions};
+/// let email = Email::from_stdin()?;
+/// let options = SearchOptions{case_insensitive: false, ..Default::default()};
+/// if email.header().search_opts(r"^Message-ID: <AbC123@example\.com>", &options)? {
+///     email.deliver_to_maildir("/my/maildir/path")?;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Match letters regardless of their case. Defaults to `true`.
+    pub case_insensitive: bool,
+    /// Make `^` and `$` match the beginning and end of lines, instead of
+    /// the beginning and end of the data. Defaults to `true`.
+    pub multi_line: bool,
+    /// Make `.` match newlines. Defaults to `false`.
+    pub dot_matches_new_line: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions{
+            case_insensitive: true,
+            multi_line: true,
+            dot_matches_new_line: false,
+        }
+    }
+}
+
+fn build_regex(regex: &str, options: &SearchOptions) -> Result<Regex> {
+    Ok(
+        RegexBuilder::new(regex)
+            .case_insensitive(options.case_insensitive)
+            .multi_line(options.multi_line)
+            .dot_matches_new_line(options.dot_matches_new_line)
+            .build()?
+    )
+}
+
+fn build_regex_set(regex_set: &[&str], options: &SearchOptions) -> Result<RegexSet> {
+    Ok(
+        RegexSetBuilder::new(regex_set)
+            .case_insensitive(options.case_insensitive)
+            .multi_line(options.multi_line)
+            .dot_matches_new_line(options.dot_matches_new_line)
+            .build()?
+    )
+}
 
 /// Trait providing convenience methods for regular expression searching
 /// in emails. The trait methods can be use with the byte data returned by
@@ -22,6 +80,13 @@ use crate::Result;
 /// expression parsing is configured for case-insensitive and multi-line
 /// search (i.e., `^` and `$` match beginning and end of lines respectively).
 ///
+/// The `*_opts` variants of the methods allow configuring the regular
+/// expressions differently, e.g., for case-sensitive search, with
+/// [SearchOptions](struct.SearchOptions.html). They have default
+/// implementations that search the bytes of types implementing
+/// `AsRef<[u8]>`, so implementations of the trait only need to provide the
+/// other methods.
+///
 /// In addition to the single regular expression searching, a method for
 /// matching regular expression sets is provided. This can be more
 /// efficient than matching multiple regular expressions independently.
@@ -43,21 +108,87 @@ pub trait EmailRegex {
     /// ```
     fn search(&self, regex: &str) -> Result<bool>;
 
+    /// Returns whether the contents match a regular expression configured
+    /// with the specified options.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, EmailRegex, SearchOptions};
+    /// let email = Email::from_stdin()?;
+    /// let options = SearchOptions{case_insensitive: false, ..Default::default()};
+    /// if email.body().search_opts(r"\bTOKEN-[A-Za-z0-9+/]{16}\b", &options)? {
+    ///     email.deliver_to_maildir("/my/maildir/tokens")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn search_opts(&self, regex: &str, options: &SearchOptions) -> Result<bool>
+    where
+        Self: AsRef<[u8]>
+    {
+        Ok(build_regex(regex, options)?.is_match(self.as_ref()))
+    }
+
+    /// Returns the start and end byte offsets of the first match of a
+    /// regular expression, relative to the searched contents.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, EmailRegex};
+    /// let email = Email::from_stdin()?;
+    /// let header = email.header();
+    /// if let Some((start, _)) = header.search_find(r"\bTICKET-\d+")? {
+    ///     let line_start = header[..start].iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    ///     let line_end = header[start..].iter().position(|&b| b == b'\n').map_or(header.len(), |i| start + i);
+    ///     eprintln!("{}", String::from_utf8_lossy(&header[line_start..line_end]));
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn search_find(&self, regex: &str) -> Result<Option<(usize, usize)>> {
+        let captures = self.search_with_captures(regex)?;
+        Ok(captures.and_then(|c| c.get(0)).map(|m| (m.start(), m.end())))
+    }
+
+    /// Returns the start and end byte offsets of the first match of a
+    /// regular expression configured with the specified options, relative
+    /// to the searched contents.
+    fn search_find_opts(&self, regex: &str, options: &SearchOptions) -> Result<Option<(usize, usize)>>
+    where
+        Self: AsRef<[u8]>
+    {
+        Ok(build_regex(regex, options)?.find(self.as_ref()).map(|m| (m.start(), m.end())))
+    }
+
     /// Returns the capture groups matched from a regular expression.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use std::path::Path;
-    /// use mda::{Email, EmailRegex};
+    /// use mda::{sanitize_maildir_component, Email, EmailRegex};
     /// let email = Email::from_stdin()?;
     /// if let Some(captures) = email.header().search_with_captures(r"^X-Product: name=(\w+)")? {
     ///     let name = std::str::from_utf8(captures.get(1).unwrap().as_bytes()).unwrap();
-    ///     email.deliver_to_maildir(Path::new("/my/maildir/").join(name))?;
+    ///     let folder = sanitize_maildir_component(name);
+    ///     email.deliver_to_maildir(Path::new("/my/maildir/").join(folder))?;
     /// }
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    fn search_with_captures(&self, regex: &str) -> Result<Option<Captures>>;
+    fn search_with_captures(&self, regex: &str) -> Result<Option<Captures<'_>>>;
+
+    /// Returns the capture groups matched from a regular expression
+    /// configured with the specified options.
+    fn search_with_captures_opts(
+        &self,
+        regex: &str,
+        options: &SearchOptions
+    ) -> Result<Option<Captures<'_>>>
+    where
+        Self: AsRef<[u8]>
+    {
+        Ok(build_regex(regex, options)?.captures(self.as_ref()))
+    }
 
     /// Returns the matches from a set of regular expression. This can be
     /// more efficient than matching multiple regular expressions independently.
@@ -79,36 +210,161 @@ pub trait EmailRegex {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     fn search_set(&self, regex_set: &[&str]) -> Result<SetMatches>;
+
+    /// Returns the matches from a set of regular expressions configured
+    /// with the specified options.
+    fn search_set_opts(&self, regex_set: &[&str], options: &SearchOptions) -> Result<SetMatches>
+    where
+        Self: AsRef<[u8]>
+    {
+        Ok(build_regex_set(regex_set, options)?.matches(self.as_ref()))
+    }
 }
 
 impl EmailRegex for &[u8] {
     fn search(&self, regex: &str) -> Result<bool> {
-        Ok(
-            RegexBuilder::new(regex)
-                .multi_line(true)
-                .case_insensitive(true)
-                .build()?
-                .is_match(self)
-        )
+        self.search_opts(regex, &SearchOptions::default())
     }
 
-    fn search_with_captures(&self, regex: &str) -> Result<Option<Captures>> {
+    fn search_with_captures(&self, regex: &str) -> Result<Option<Captures<'_>>> {
+        self.search_with_captures_opts(regex, &SearchOptions::default())
+    }
+
+    fn search_set(&self, regex_set: &[&str]) -> Result<SetMatches> {
+        self.search_set_opts(regex_set, &SearchOptions::default())
+    }
+}
+
+/// A set of regular expressions compiled once, for efficiently matching
+/// the same set against many emails.
+///
+/// Unlike [EmailRegex::search_set](trait.EmailRegex.html#tymethod.search_set),
+/// which compiles the set on every call, the set is compiled only when the
+/// `CompiledMatcherSet` is created. The regular expressions are configured
+/// as in [EmailRegex](trait.EmailRegex.html).
+///
+/// # Example
+///
+/// ```no_run
+/// use mda::{CompiledMatcherSet, Email};
+/// let rules = CompiledMatcherSet::new(
+///     &[
+///         r"^To:.*lists@example\.com",
+///         r"^List-Id:.*<rust-users",
+///     ]
+/// )?;
+/// let email = Email::from_stdin()?;
+/// if !rules.matches(email.header()).is_empty() {
+///     email.deliver_to_maildir("/my/maildir/lists")?;
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct CompiledMatcherSet {
+    set: RegexSet,
+}
+
+impl CompiledMatcherSet {
+    /// Compiles a set of regular expressions, failing if any of them is
+    /// invalid.
+    pub fn new(regex_set: &[&str]) -> Result<Self> {
         Ok(
-            RegexBuilder::new(regex)
-                .multi_line(true)
-                .case_insensitive(true)
-                .build()?
-                .captures(self)
+            CompiledMatcherSet{
+                set: build_regex_set(regex_set, &SearchOptions::default())?
+            }
         )
     }
 
-    fn search_set(&self, regex_set: &[&str]) -> Result<SetMatches> {
+    /// Creates a matcher set from an already built `RegexSet`, which is used
+    /// with the flags it was built with.
+    pub fn from_regex_set(set: RegexSet) -> Self {
+        CompiledMatcherSet{set}
+    }
+
+    /// Returns the indices of the regular expressions in the set that
+    /// match the data, in ascending order.
+    pub fn matches(&self, data: &[u8]) -> Vec<usize> {
+        self.set.matches(data).into_iter().collect()
+    }
+
+    /// Returns whether any regular expression in the set matches the data.
+    pub fn is_match(&self, data: &[u8]) -> bool {
+        self.set.is_match(data)
+    }
+}
+
+/// The regions of an email matched by a regular expression, as returned by
+/// [Email::search_regions](type.Email.html#method.search_regions).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RegionMatch {
+    /// Whether the header matched.
+    pub header: bool,
+    /// Whether the body matched.
+    pub body: bool,
+}
+
+impl RegionMatch {
+    /// Returns whether any region matched.
+    pub fn any(&self) -> bool {
+        self.header || self.body
+    }
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns which regions of the email, i.e., the header and the body,
+    /// match a regular expression. The regular expression is compiled once
+    /// and configured as in [EmailRegex](trait.EmailRegex.html).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let matched = email.search_regions(r"https?://example\.net")?;
+    /// if matched.header {
+    ///     email.deliver_to_maildir("/my/maildir/suspicious")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn search_regions(&self, regex: &str) -> Result<RegionMatch> {
+        let regex = build_regex(regex, &SearchOptions::default())?;
+
         Ok(
-            RegexSetBuilder::new(regex_set)
-                .multi_line(true)
-                .case_insensitive(true)
-                .build()?
-                .matches(self)
+            RegionMatch{
+                header: regex.is_match(self.header()),
+                body: regex.is_match(self.body()),
+            }
         )
     }
+
+    /// Returns the names of the rules whose regular expressions match the
+    /// normalized email data, in the order the rules are specified. Each
+    /// rule is a `(name, regex)` pair.
+    ///
+    /// The regular expressions are compiled into a single set, as with
+    /// [CompiledMatcherSet](struct.CompiledMatcherSet.html), and are
+    /// configured as in [EmailRegex](trait.EmailRegex.html). Fails if any
+    /// regular expression is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let matched = email.match_rules(
+    ///     &[
+    ///         ("lists", r"^List-Id:"),
+    ///         ("work", r"^From:.*@work\.example\.com"),
+    ///     ]
+    /// )?;
+    /// if let Some(name) = matched.first() {
+    ///     email.deliver_to_maildir(Path::new("/my/maildir/").join(name))?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn match_rules<'r>(&self, rules: &[(&'r str, &str)]) -> Result<Vec<&'r str>> {
+        let regexes: Vec<&str> = rules.iter().map(|(_, regex)| *regex).collect();
+        let set = CompiledMatcherSet::new(&regexes)?;
+        Ok(set.matches(self.data()).into_iter().map(|i| rules[i].0).collect())
+    }
 }
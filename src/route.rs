@@ -0,0 +1,84 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Declarative routing of emails to maildirs.
+
+use std::path::{Path, PathBuf};
+
+use crate::{BorrowedEmail, Result};
+
+/// A chain of routing rules for an email, in which the first matching
+/// rule delivers the email and all subsequent rules are skipped.
+///
+/// A chain is started with [Email::when](type.Email.html#method.when).
+pub struct Route<'a> {
+    email: &'a BorrowedEmail<'a>,
+    delivered_path: Option<PathBuf>,
+}
+
+/// A routing rule whose condition has been evaluated, waiting for the
+/// delivery target.
+pub struct RouteRule<'a> {
+    route: Route<'a>,
+    matched: bool,
+}
+
+impl<'a> Route<'a> {
+    /// Adds a rule to the chain. The predicate is evaluated only if no
+    /// previous rule in the chain has delivered the email.
+    pub fn when(self, predicate: impl FnOnce(&BorrowedEmail) -> bool) -> RouteRule<'a> {
+        let matched = self.delivered_path.is_none() && predicate(self.email);
+        RouteRule{route: self, matched}
+    }
+
+    /// Delivers the email to the specified maildir, if no previous rule in
+    /// the chain has delivered it.
+    pub fn otherwise(self, path: impl AsRef<Path>) -> Result<Route<'a>> {
+        self.when(|_| true).deliver(path)
+    }
+
+    /// Returns the path of the delivered email file, if a rule in the
+    /// chain has delivered the email.
+    pub fn delivered_path(&self) -> Option<&Path> {
+        self.delivered_path.as_deref()
+    }
+}
+
+impl<'a> RouteRule<'a> {
+    /// Delivers the email to the specified maildir if the rule matched,
+    /// returning the chain for further rules.
+    pub fn deliver(mut self, path: impl AsRef<Path>) -> Result<Route<'a>> {
+        if self.matched {
+            self.route.delivered_path = Some(self.route.email.deliver_to_maildir(path)?);
+        }
+        Ok(self.route)
+    }
+}
+
+impl BorrowedEmail<'_> {
+    /// Starts a chain of routing rules, in which the first rule whose
+    /// predicate holds delivers the email to its maildir, and all
+    /// subsequent rules are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// email
+    ///     .when(|e| e.header_field("Subject").unwrap_or("").contains("[URGENT]"))
+    ///     .deliver("/my/maildir/urgent")?
+    ///     .when(|e| e.spam_score().is_some_and(|s| s > 5.0))
+    ///     .deliver("/my/maildir/spam")?
+    ///     .otherwise("/my/maildir/inbox")?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn when(&self, predicate: impl FnOnce(&BorrowedEmail) -> bool) -> RouteRule<'_> {
+        Route{email: self, delivered_path: None}.when(predicate)
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Parsing of SPF results recorded in Received-SPF header fields.
+
+use std::net::IpAddr;
+
+use crate::BorrowedEmail;
+use crate::params::parse_parameters;
+
+/// The result of an SPF check, as defined in RFC 7208.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpfVerdict {
+    /// The client is authorized to send mail for the domain.
+    Pass,
+    /// The client is not authorized to send mail for the domain.
+    Fail,
+    /// The client is probably not authorized to send mail for the domain.
+    SoftFail,
+    /// The domain makes no assertion about the client.
+    Neutral,
+    /// No SPF record was found for the domain.
+    None,
+    /// A transient error occurred during the check.
+    TempError,
+    /// The SPF record of the domain could not be interpreted.
+    PermError,
+}
+
+impl SpfVerdict {
+    fn from_token(token: &str) -> Option<Self> {
+        match token.to_ascii_lowercase().as_str() {
+            "pass" => Some(SpfVerdict::Pass),
+            "fail" => Some(SpfVerdict::Fail),
+            "softfail" => Some(SpfVerdict::SoftFail),
+            "neutral" => Some(SpfVerdict::Neutral),
+            "none" => Some(SpfVerdict::None),
+            "temperror" => Some(SpfVerdict::TempError),
+            "permerror" => Some(SpfVerdict::PermError),
+            _ => Option::None,
+        }
+    }
+}
+
+/// An SPF result recorded in a Received-SPF header field, as returned by
+/// [Email::received_spf](type.Email.html#method.received_spf).
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpfResult {
+    /// The result of the SPF check.
+    pub verdict: SpfVerdict,
+    /// The IP address of the checked client, from the `client-ip=`
+    /// parameter, if present and valid.
+    pub client_ip: Option<IpAddr>,
+}
+
+/// Returns the input without a leading comment, e.g., `(mailfrom)`.
+/// Nested comments are supported.
+fn skip_comment(s: &str) -> &str {
+    if !s.starts_with('(') {
+        return s;
+    }
+
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return &s[i + 1..];
+                }
+            },
+            _ => {},
+        }
+    }
+    ""
+}
+
+/// Parses the value of a Received-SPF header field, e.g.,
+/// `Pass (mailfrom) identity=mailfrom; client-ip=192.0.2.1; helo=example.com`.
+fn parse_received_spf(value: &str) -> Option<SpfResult> {
+    let value = value.trim_start();
+    let token_end = value.find(|c: char| c == '(' || c == ';' || c.is_whitespace())
+        .unwrap_or(value.len());
+    let verdict = SpfVerdict::from_token(&value[..token_end])?;
+
+    // The key-value pairs directly follow the result and the comment, so
+    // mark their start for the parameter parser.
+    let pairs = skip_comment(value[token_end..].trim_start());
+    let params = parse_parameters(&format!(";{}", pairs));
+    let client_ip = params.get("client-ip").and_then(|ip| ip.parse().ok());
+
+    Some(SpfResult{verdict, client_ip})
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the SPF result recorded in the Received-SPF header field of
+    /// the email, e.g., by the SPF policy service of the receiving MTA.
+    ///
+    /// Only the first (i.e., most recently added) Received-SPF header field
+    /// is considered. Returns `None` if the email doesn't have a
+    /// Received-SPF header field, or if its result isn't recognized.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, SpfVerdict};
+    /// let email = Email::from_stdin()?;
+    /// if email.received_spf().is_some_and(|spf| spf.verdict == SpfVerdict::Fail) {
+    ///     email.deliver_to_maildir("/my/spam/path")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn received_spf(&self) -> Option<SpfResult> {
+        self.header_field("Received-SPF").and_then(parse_received_spf)
+    }
+}
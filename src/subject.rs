@@ -0,0 +1,175 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Parsing of bracketed tags and classification of the script of the
+//! Subject header field.
+
+use crate::BorrowedEmail;
+
+/// A coarse classification of the writing system of some text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Script {
+    /// Basic and extended Latin letters.
+    Latin,
+    /// Greek, including polytonic Greek.
+    Greek,
+    /// Cyrillic, including the Cyrillic supplement.
+    Cyrillic,
+    /// Hebrew, written right to left.
+    Hebrew,
+    /// Arabic, including presentation forms, written right to left.
+    Arabic,
+    /// Chinese, Japanese or Korean.
+    Cjk,
+    /// Any other script.
+    Other,
+}
+
+impl Script {
+    const ALL: [Script; 7] = [
+        Script::Latin, Script::Greek, Script::Cyrillic, Script::Hebrew,
+        Script::Arabic, Script::Cjk, Script::Other,
+    ];
+
+    /// Returns whether the script is written right-to-left.
+    pub fn is_rtl(self) -> bool {
+        matches!(self, Script::Hebrew | Script::Arabic)
+    }
+
+    /// Returns the script of an alphabetic character.
+    fn of(c: char) -> Script {
+        match c as u32 {
+            0x0041..=0x024F | 0x1E00..=0x1EFF => Script::Latin,
+            0x0370..=0x03FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x0400..=0x052F => Script::Cyrillic,
+            0x0590..=0x05FF | 0xFB1D..=0xFB4F => Script::Hebrew,
+            0x0600..=0x06FF | 0x0750..=0x077F | 0x08A0..=0x08FF |
+            0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Script::Arabic,
+            0x1100..=0x11FF | 0x3040..=0x312F | 0x3400..=0x4DBF |
+            0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF |
+            0x20000..=0x2FFFF => Script::Cjk,
+            _ => Script::Other,
+        }
+    }
+}
+
+/// Returns the script used by most alphabetic characters of some text, or
+/// `None` if the text doesn't contain any alphabetic characters.
+fn majority_script(text: &str) -> Option<Script> {
+    let mut counts = [0usize; Script::ALL.len()];
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        counts[Script::of(c) as usize] += 1;
+    }
+
+    // On ties, prefer the script that comes first.
+    let (index, count) = counts.iter().enumerate().rev().max_by_key(|(_, count)| **count)?;
+    if *count == 0 {
+        None
+    } else {
+        Some(Script::ALL[index])
+    }
+}
+
+/// Splits the leading bracketed tags from a subject, returning the tags
+/// and the remaining subject text.
+///
+/// Brackets may be nested, in which case the whole outer bracketed text is
+/// a single tag. Parsing stops at the first token that isn't a bracketed
+/// tag, or at an unterminated bracket.
+fn split_tags(subject: &str) -> (Vec<&str>, &str) {
+    let mut tags = Vec::new();
+    let mut rest = subject.trim_start();
+
+    while rest.starts_with('[') {
+        let mut depth = 0;
+        let end = rest.char_indices().find_map(|(i, c)| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => {},
+            }
+            if depth == 0 { Some(i) } else { None }
+        });
+
+        let end = match end {
+            Some(end) => end,
+            None => break,
+        };
+
+        let tag = rest[1..end].trim();
+        if !tag.is_empty() {
+            tags.push(tag);
+        }
+        rest = rest[end + 1..].trim_start();
+    }
+
+    (tags, rest)
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the leading bracketed tags of the decoded Subject header
+    /// field, e.g., `["list", "TICKET-123"]` for the subject
+    /// `[list] [TICKET-123] Printer on fire`. Tags are returned without
+    /// their brackets. If the email has no Subject header field, or the
+    /// subject doesn't start with a tag, the returned vector is empty.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if email.subject_tags().iter().any(|t| t == "rust-users") {
+    ///     email.deliver_to_maildir("/my/maildir/lists/rust-users")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn subject_tags(&self) -> Vec<String> {
+        self.header_field("Subject")
+            .map(|subject| split_tags(subject).0.into_iter().map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the decoded Subject header field with any leading bracketed
+    /// tags and surrounding whitespace removed, if the email has a Subject
+    /// header field.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let subject = email.subject_without_tags().unwrap_or("");
+    /// if subject.starts_with("Printer") {
+    ///     email.deliver_to_maildir("/my/maildir/printer")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn subject_without_tags(&self) -> Option<&str> {
+        self.header_field("Subject").map(|subject| split_tags(subject).1.trim_end())
+    }
+
+    /// Returns the script used by most alphabetic characters of the decoded
+    /// Subject header field, or `None` if the email has no Subject header
+    /// field or the subject doesn't contain any alphabetic characters.
+    /// Leading bracketed tags (e.g., list names) are ignored, since they
+    /// often use a different script than the subject itself.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use mda::{Email, Script};
+    /// let email = Email::from_stdin()?;
+    /// if email.subject_script() == Some(Script::Cjk) {
+    ///     email.deliver_to_maildir("/my/maildir/support/cjk")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn subject_script(&self) -> Option<Script> {
+        majority_script(self.subject_without_tags()?)
+    }
+}
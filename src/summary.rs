@@ -0,0 +1,162 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Summaries of the parsed metadata of emails.
+
+#[cfg(feature = "serde")]
+use std::io;
+#[cfg(feature = "serde")]
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::BorrowedEmail;
+#[cfg(feature = "serde")]
+use crate::Result;
+
+/// A summary of the parsed metadata of an email, as returned by
+/// [Email::summary](type.Email.html#method.summary).
+///
+/// If the `serde` feature is enabled, the summary implements
+/// `serde::Serialize`.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EmailSummary {
+    /// The trimmed value of the Message-ID header field, if present.
+    pub message_id: Option<String>,
+    /// The trimmed value of the Date header field, if present.
+    pub date: Option<String>,
+    /// The bare addresses of the From header field.
+    pub from: Vec<String>,
+    /// The bare addresses of the To header field.
+    pub to: Vec<String>,
+    /// The bare addresses of the Cc header field.
+    pub cc: Vec<String>,
+    /// The decoded and trimmed value of the Subject header field, if present.
+    pub subject: Option<String>,
+    /// The size of the raw email data in bytes.
+    pub size: usize,
+    /// Whether the email has any attachments.
+    pub has_attachments: bool,
+    /// The spam score of the email, if it has been scored.
+    pub spam_score: Option<f32>,
+    /// Whether the email is a bounce.
+    pub is_bounce: bool,
+    /// A short, single-line preview of the email, if available.
+    pub snippet: Option<String>,
+}
+
+impl BorrowedEmail<'_> {
+    fn trimmed_header_field(&self, name: &str) -> Option<String> {
+        self.header_field(name).map(|value| value.trim().to_string())
+    }
+
+    fn addr_specs(&self, name: &str) -> Vec<String> {
+        self.header_field_addresses(name).iter().map(|a| a.addr_spec()).collect()
+    }
+
+    /// Returns a summary of the parsed metadata of the email.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let summary = email.summary();
+    /// eprintln!("{:?} from {:?}", summary.subject, summary.from);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn summary(&self) -> EmailSummary {
+        EmailSummary{
+            message_id: self.trimmed_header_field("Message-ID"),
+            date: self.trimmed_header_field("Date"),
+            from: self.addr_specs("From"),
+            to: self.addr_specs("To"),
+            cc: self.addr_specs("Cc"),
+            subject: self.trimmed_header_field("Subject"),
+            size: self.raw_data().len(),
+            has_attachments: self.has_attachment_larger_than(0),
+            spam_score: self.spam_score(),
+            is_bounce: self.is_bounce(),
+            snippet: self.snippet(),
+        }
+    }
+
+    /// Delivers the email to the specified maildir, like
+    /// [deliver_to_maildir](#method.deliver_to_maildir), and then writes
+    /// the [summary](#method.summary) of the email as JSON to a sidecar
+    /// file next to the delivered email. The sidecar file has the filename
+    /// of the email with a `.meta` suffix. Returns the path of the
+    /// delivered email file along with the result of writing the sidecar
+    /// file, i.e., the path of the sidecar file or the error that occurred.
+    ///
+    /// An `Err` is returned only if the email itself couldn't be delivered.
+    /// If writing the sidecar file fails, the email has already been
+    /// delivered, and it shouldn't be delivered again, since that would
+    /// create a duplicate.
+    ///
+    /// The sidecar file is written atomically, so readers never see a
+    /// partially written file, and with the delivery durability of the
+    /// email. Note that maildir readers that aren't aware of sidecar files
+    /// will consider them to be emails.
+    ///
+    /// This method is available only if the `serde` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let (path, sidecar) = email.deliver_to_maildir_with_sidecar("/path/to/maildir/")?;
+    /// match sidecar {
+    ///     Ok(sidecar) => println!("{} ({})", path.display(), sidecar.display()),
+    ///     Err(err) => eprintln!("{} (no sidecar: {})", path.display(), err),
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn deliver_to_maildir_with_sidecar(
+        &self,
+        path: impl AsRef<Path>
+    ) -> Result<(PathBuf, Result<PathBuf>)> {
+        let metadata = serde_json::to_vec(&self.summary()).map_err(io::Error::from)?;
+        let email_path = self.deliver_to_maildir(path.as_ref())?;
+        let sidecar =
+            self.maildir_backend().write_sidecar(self, path.as_ref(), &email_path, &metadata);
+        Ok((email_path, sidecar))
+    }
+
+    /// Delivers the email to the specified maildir, like
+    /// [deliver_to_maildir](#method.deliver_to_maildir), and returns the
+    /// path of the delivered email file along with the
+    /// [summary](#method.summary) of the email as a JSON string, e.g., to
+    /// notify a webhook about the delivery.
+    ///
+    /// The summary is serialized before the email is delivered, so the
+    /// email is never delivered without a summary to return.
+    ///
+    /// This method is available only if the `serde` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let (path, summary) = email.deliver_and_summarize("/path/to/maildir/")?;
+    /// println!("{}", summary);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn deliver_and_summarize(&self, path: impl AsRef<Path>) -> Result<(PathBuf, String)> {
+        let summary = serde_json::to_string(&self.summary()).map_err(io::Error::from)?;
+        let email_path = self.deliver_to_maildir(path)?;
+        Ok((email_path, summary))
+    }
+}
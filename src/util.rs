@@ -0,0 +1,143 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! General utilities for working with email byte data.
+
+use std::io::{self, Read};
+
+use memchr::{memchr, memchr_iter};
+use sha2::{Digest, Sha256};
+
+/// Iterator for the lines contained in a slice of [u8].
+///
+/// Unlike `str::lines`, each yielded line includes its line terminator
+/// (`\n` or `\r\n`), so concatenating all the yielded lines reproduces
+/// the original data exactly. If the data doesn't end with a line
+/// terminator, the final unterminated line is also yielded.
+///
+/// # Example
+///
+/// ```
+/// use mda::SliceLines;
+/// let lines: Vec<&[u8]> = SliceLines::new(b"a\nb\r\nc").collect();
+/// assert_eq!(lines, [&b"a\n"[..], &b"b\r\n"[..], &b"c"[..]]);
+/// ```
+pub struct SliceLines<'a> {
+    buf: &'a [u8],
+    last: usize,
+}
+
+impl<'a> SliceLines<'a> {
+    /// Creates an iterator over the lines of a byte slice.
+    pub fn new(buf: &'a [u8]) -> Self {
+        SliceLines{buf, last: 0}
+    }
+}
+
+impl<'a> Iterator for SliceLines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        match memchr(b'\n', &self.buf[self.last..]) {
+            Some(m) => {
+                let line = &self.buf[self.last..=(self.last + m)];
+                self.last = self.last + m + 1;
+                Some(line)
+            },
+            None => {
+                let line = &self.buf[self.last..];
+                if line.is_empty() {
+                    None
+                } else {
+                    self.last = self.buf.len();
+                    Some(line)
+                }
+            }
+        }
+    }
+}
+
+/// Converts bare LF line endings to CRLF, as required by SMTP and IMAP.
+#[cfg(feature = "imap")]
+pub(crate) fn to_crlf(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(crlf_len(data));
+    let mut prev = 0;
+    for &b in data {
+        if b == b'\n' && prev != b'\r' {
+            out.push(b'\r');
+        }
+        out.push(b);
+        prev = b;
+    }
+    out
+}
+
+/// Returns the length the data would have after converting bare LF line
+/// endings to CRLF, without performing the conversion.
+pub(crate) fn crlf_len(data: &[u8]) -> usize {
+    let bare_lfs = memchr_iter(b'\n', data)
+        .filter(|&i| i == 0 || data[i - 1] != b'\r')
+        .count();
+    data.len() + bare_lfs
+}
+
+/// A reader that computes the SHA-256 hash of the data read through it.
+pub(crate) struct HashingReader<R: Read> {
+    reader: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        HashingReader{reader, hasher: Sha256::new()}
+    }
+
+    /// Returns the hash of all the data read so far.
+    pub(crate) fn finalize(self) -> Vec<u8> {
+        self.hasher.finalize().to_vec()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of some data. Unlike the std hashers,
+/// FNV-1a is guaranteed to be stable across Rust versions, so the hash can
+/// be used in persistent names.
+pub(crate) fn fnv1a64(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325u64, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns the position of the first occurrence of an ASCII needle in a
+/// string, ignoring ASCII case.
+pub(crate) fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Returns whether a line is empty, i.e., consists only of its line
+/// terminator.
+pub(crate) fn is_empty_line(line: &[u8]) -> bool {
+    line == b"\n" || line == b"\r\n"
+}
+
+/// Returns whether a line consists only of whitespace, but isn't empty.
+/// Such lines are continuation lines, but are sometimes mistaken for the
+/// empty line that ends a header.
+pub(crate) fn is_whitespace_only_line(line: &[u8]) -> bool {
+    line.first().is_some_and(|&b| b == b' ' || b == b'\t') &&
+        line.iter().all(|&b| b == b' ' || b == b'\t' || b == b'\r' || b == b'\n')
+}
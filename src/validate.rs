@@ -0,0 +1,108 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Validation of email structure.
+
+use crate::BorrowedEmail;
+use crate::util::{is_whitespace_only_line, SliceLines};
+
+/// An issue found in the structure of an email.
+///
+/// Issues don't prevent an email from being processed or delivered, but
+/// they may indicate that the email is malformed or crafted to be
+/// expensive to process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// Multipart parts were nested deeper than the configured
+    /// [maximum part depth](struct.NormalizationOptions.html#structfield.max_part_depth),
+    /// so the deeper parts were treated as plain body data.
+    PartDepthLimitReached,
+    /// Some multipart parts were not closed with a matching boundary end
+    /// line by the end of the email, e.g., because boundaries were closed
+    /// out of order, so subsequent parts may have been attributed to the
+    /// wrong multipart.
+    UnbalancedBoundaries,
+    /// Some header field values were longer than the configured
+    /// [maximum header field length](struct.NormalizationOptions.html#structfield.max_header_field_len),
+    /// so they were truncated.
+    HeaderFieldTruncated,
+    /// A header contained a line consisting only of whitespace. Such a
+    /// line is parsed as a continuation line, but some software considers
+    /// it to be the empty line that ends the header, so the header and body
+    /// of the email may be interpreted differently by different software.
+    WhitespaceOnlyHeaderLine,
+    /// The email uses MIME features, i.e., multipart containers or content
+    /// transfer encodings, but lacks a `MIME-Version: 1.0` header field,
+    /// which may indicate a non-conformant or crafted email.
+    MissingMimeVersion,
+    /// A multipart container declared a content transfer encoding other
+    /// than `7bit`, `8bit` or `binary`, which MIME doesn't allow. The
+    /// encoding was ignored, and the parts of the container were decoded
+    /// according to their own encodings.
+    InvalidMultipartEncoding,
+}
+
+impl BorrowedEmail<'_> {
+    /// Returns the issues found in the structure of the email while
+    /// parsing it. An empty vector means that no issues were found.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if !email.validate().is_empty() {
+    ///     email.deliver_to_maildir("/my/maildir/suspicious")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.issues.clone()
+    }
+
+    /// Returns the trimmed value of the MIME-Version header field of the
+    /// email, e.g., `1.0`, if present.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// let is_mime = email.mime_version().is_some();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn mime_version(&self) -> Option<&str> {
+        self.header_field("MIME-Version").map(str::trim)
+    }
+
+    /// Returns whether the header of the email is separated from its body
+    /// in the canonical way, i.e., with a truly empty line, without any
+    /// preceding lines in the header consisting only of whitespace. Emails
+    /// without a body are considered to be canonical if their header
+    /// doesn't contain any such lines.
+    ///
+    /// Lines consisting only of whitespace are parsed as continuation
+    /// lines, but some software considers them to be the end of the header,
+    /// which can be exploited to smuggle header fields or body content past
+    /// filters. See also
+    /// [ValidationIssue::WhitespaceOnlyHeaderLine](enum.ValidationIssue.html#variant.WhitespaceOnlyHeaderLine).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use mda::Email;
+    /// let email = Email::from_stdin()?;
+    /// if !email.separator_is_canonical() {
+    ///     email.deliver_to_maildir("/my/maildir/suspicious")?;
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn separator_is_canonical(&self) -> bool {
+        !SliceLines::new(&self.raw_data[..self.raw_header_end()]).any(is_whitespace_only_line)
+    }
+}
@@ -0,0 +1,18 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+//! Helpers shared by the integration tests.
+
+use mda::Email;
+
+/// Returns an email with the specified header fields after a Return-Path
+/// field, and a short body.
+pub fn email_with_header(header: &str) -> Email {
+    let data = format!("Return-Path: <me@source.com>\n{}\n\nBody\n", header);
+    Email::from_vec(data.into_bytes()).unwrap()
+}
@@ -0,0 +1,261 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+mod common;
+
+use common::email_with_header;
+use mda::{Address, Email, NormalizationOptions};
+
+fn address(display_name: Option<&str>, local: &str, domain: &str) -> Address {
+    Address{
+        display_name: display_name.map(String::from),
+        local: local.to_string(),
+        domain: domain.to_string(),
+    }
+}
+
+#[test]
+fn reply_to_is_preferred() {
+    let email = email_with_header(
+        "From: John Doe <john@example.com>\nReply-To: \"Doe, John\" <john.doe@example.com>");
+
+    assert_eq!(
+        email.reply_to_address(),
+        Some(address(Some("Doe, John"), "john.doe", "example.com"))
+    );
+}
+
+#[test]
+fn from_is_used_without_reply_to() {
+    let email = email_with_header("From: john@example.com (John Doe)");
+
+    assert_eq!(
+        email.reply_to_address(),
+        Some(address(Some("John Doe"), "john", "example.com"))
+    );
+}
+
+#[test]
+fn list_reply_to_is_skipped() {
+    let email = email_with_header(
+        "From: John <john@example.com>\n\
+         Reply-To: Rust Users <rust-users@Lists.Example.com>\n\
+         List-Post: <mailto:rust-users@lists.example.com>");
+
+    assert_eq!(email.reply_to_address().unwrap().addr_spec(), "john@example.com");
+}
+
+#[test]
+fn list_post_scheme_is_case_insensitive() {
+    let email = email_with_header(
+        "From: John <john@example.com>\n\
+         Reply-To: <rust-users@lists.example.com>\n\
+         List-Post: <MAILTO:rust-users@lists.example.com>");
+
+    assert_eq!(email.reply_to_address().unwrap().addr_spec(), "john@example.com");
+}
+
+#[test]
+fn display_names_use_charset_aliases() {
+    let mut options = NormalizationOptions::default();
+    options.charset_aliases.insert("x-custom".to_string(), "iso-8859-7".to_string());
+    let data = b"From: =?x-custom?B?4eLj?= <abc@example.com>\n\nBody\n".to_vec();
+    let email = Email::from_vec_with_options(data, options).unwrap();
+
+    assert_eq!(
+        email.header_field_addresses("From"),
+        [address(Some("αβγ"), "abc", "example.com")]
+    );
+}
+
+#[test]
+fn null_sender_is_never_replied_to() {
+    let email = Email::from_vec(
+        b"Return-Path: <>\nFrom: MAILER-DAEMON@example.com\n\nBody\n".to_vec()).unwrap();
+
+    assert_eq!(email.reply_to_address(), None);
+}
+
+#[test]
+fn invalid_addresses_are_skipped() {
+    let email = email_with_header("Reply-To: undisclosed-recipients:;\nFrom: <john@example.com>");
+
+    assert_eq!(email.reply_to_address(), Some(address(None, "john", "example.com")));
+}
+
+#[test]
+fn address_is_displayed_with_name() {
+    assert_eq!(
+        address(Some("John Doe"), "john", "example.com").to_string(),
+        "John Doe <john@example.com>"
+    );
+    assert_eq!(address(None, "john", "example.com").to_string(), "john@example.com");
+}
+
+#[test]
+fn encoded_display_name_with_comma_is_single_address() {
+    // The display name decodes to "Doe, John".
+    let email = email_with_header("Reply-To: =?utf-8?q?Doe=2C?= =?utf-8?q?_John?= <john@example.com>");
+
+    assert_eq!(
+        email.reply_to_address(),
+        Some(address(Some("Doe, John"), "john", "example.com"))
+    );
+}
+
+#[test]
+fn encoded_display_name_with_angle_brackets_is_not_address() {
+    // The display name decodes to "<evil@example.com>".
+    let email = email_with_header("From: =?utf-8?b?PGV2aWxAZXhhbXBsZS5jb20+?= <john@example.com>");
+
+    assert_eq!(
+        email.reply_to_address(),
+        Some(address(Some("<evil@example.com>"), "john", "example.com"))
+    );
+}
+
+#[test]
+fn source_route_is_ignored() {
+    let email = email_with_header("From: Old Relay <@relay1.example,@relay2.example:user@host.example>");
+
+    assert_eq!(
+        email.reply_to_address(),
+        Some(address(Some("Old Relay"), "user", "host.example"))
+    );
+}
+
+#[test]
+fn source_route_does_not_split_address_list() {
+    let email = email_with_header("To: <@relay1,@relay2:one@example.com>, two@example.com");
+
+    assert_eq!(email.summary().to, vec!["one@example.com", "two@example.com"]);
+}
+
+#[test]
+fn on_behalf_of_returns_differing_sender_and_from() {
+    let email = email_with_header(
+        "From: Boss <boss@example.com>\nSender: Assistant <assistant@example.com>");
+
+    assert_eq!(
+        email.on_behalf_of(),
+        Some((
+            address(Some("Assistant"), "assistant", "example.com"),
+            address(Some("Boss"), "boss", "example.com"),
+        ))
+    );
+}
+
+#[test]
+fn on_behalf_of_is_none_for_same_or_missing_sender() {
+    let same = email_with_header("From: Boss <boss@example.com>\nSender: boss@EXAMPLE.com");
+    let missing = email_with_header("From: Boss <boss@example.com>");
+
+    assert_eq!(same.on_behalf_of(), None);
+    assert_eq!(missing.on_behalf_of(), None);
+}
+
+#[test]
+fn header_field_addresses_parses_address_list() {
+    let email = email_with_header(
+        "To: =?utf-8?q?Doe=2C_J=C3=B6hn?= <john@example.com>, \"odd local\"@example.com,\n <@relay:routed@example.com>, plain@example.com");
+
+    assert_eq!(
+        email.header_field_addresses("To"),
+        vec![
+            address(Some("Doe, Jöhn"), "john", "example.com"),
+            address(None, "odd local", "example.com"),
+            address(None, "routed", "example.com"),
+            address(None, "plain", "example.com"),
+        ]
+    );
+}
+
+#[test]
+fn header_field_addresses_handles_groups() {
+    let email = email_with_header("To: undisclosed-recipients:;\nCc: Team: one@example.com, two@example.com;");
+
+    assert_eq!(email.header_field_addresses("To"), vec![]);
+    assert_eq!(
+        email.header_field_addresses("Cc"),
+        vec![address(None, "one", "example.com"), address(None, "two", "example.com")]
+    );
+    assert_eq!(email.header_field_addresses("Bcc"), vec![]);
+}
+
+fn email_with_body(body: &str) -> Email {
+    Email::from_vec(format!("Subject: Contacts\n\n{}", body).into_bytes()).unwrap()
+}
+
+#[test]
+fn body_addresses_are_found_in_order() {
+    let email = email_with_body(
+        "Please contact support@example.com or, if urgent,\n\
+         <oncall+pager@ops.example.co.uk>. See mailto:info@example.org?subject=hi\n"
+    );
+
+    assert_eq!(
+        email.body_addresses(),
+        vec!["support@example.com", "oncall+pager@ops.example.co.uk", "info@example.org"]
+    );
+}
+
+#[test]
+fn body_addresses_are_deduplicated_case_insensitively() {
+    let email = email_with_body("Write to Jane@Example.com. Again: jane@example.com!\n");
+
+    assert_eq!(email.body_addresses(), vec!["Jane@Example.com"]);
+}
+
+#[test]
+fn body_addresses_ignore_non_addresses() {
+    let email = email_with_body(
+        "Handles like @someone, user@localhost, a..b@example.com, x@1.2.3.4,\n\
+         x@-bad.example.com and café-owner@example.com are not addresses.\n"
+    );
+
+    assert!(email.body_addresses().is_empty());
+}
+
+#[test]
+fn body_addresses_are_found_in_decoded_text() {
+    let email = Email::from_vec(
+        b"Subject: Encoded\n\
+          MIME-Version: 1.0\n\
+          Content-Type: text/plain; charset=utf-8\n\
+          Content-Transfer-Encoding: quoted-printable\n\
+          \n\
+          Caf=C3=A9 owner: owner=40example.com\n".to_vec()
+    ).unwrap();
+
+    assert_eq!(email.body_addresses(), vec!["owner@example.com"]);
+}
+
+#[test]
+fn from_domain_is_lowercased() {
+    let email = email_with_header("From: John <john@Mail.Example.co.uk.>, jane@other.com");
+
+    assert_eq!(email.from_domain().as_deref(), Some("mail.example.co.uk"));
+}
+
+#[test]
+fn from_domain_is_none_without_from_address() {
+    assert_eq!(email_with_header("From: undisclosed").from_domain(), None);
+    assert_eq!(email_with_header("To: john@example.com").from_domain(), None);
+}
+
+#[cfg(feature = "psl")]
+#[test]
+fn from_registrable_domain_uses_public_suffixes() {
+    let registrable = |from: &str| email_with_header(&format!("From: {}", from)).from_registrable_domain();
+
+    assert_eq!(registrable("john@mail.Example.co.uk").as_deref(), Some("example.co.uk"));
+    assert_eq!(registrable("john@news.example.com").as_deref(), Some("example.com"));
+    assert_eq!(registrable("john@example.com").as_deref(), Some("example.com"));
+    assert_eq!(registrable("john@co.uk"), None);
+    assert_eq!(registrable("john@[192.0.2.1]"), None);
+}
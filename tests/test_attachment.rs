@@ -0,0 +1,128 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Attachment, Email};
+
+static TEST_EMAIL_ATTACHMENTS: &str = r#"To: me@example.com
+Content-Type: multipart/mixed; boundary="XtT01VFrJIenjlg+ZCXSSWq4"
+
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: text/plain
+
+A long text part which is not an attachment.
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: application/octet-stream
+Content-Transfer-Encoding: base64
+
+AAECAwQFBgcI
+CQ==
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: application/pdf
+Content-Transfer-Encoding: quoted-printable
+
+%PDF=00=01=
+=02
+--XtT01VFrJIenjlg+ZCXSSWq4--
+"#;
+
+#[test]
+fn base64_attachment_size_is_decoded_size() {
+    let email = Email::from_vec(TEST_EMAIL_ATTACHMENTS.to_string().into_bytes()).unwrap();
+
+    assert!(email.has_attachment_larger_than(9));
+    assert!(!email.has_attachment_larger_than(10));
+}
+
+#[test]
+fn quoted_printable_attachment_size_is_decoded_size() {
+    let data = TEST_EMAIL_ATTACHMENTS.replace("AAECAwQFBgcI\nCQ==", "AAE=");
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert!(email.has_attachment_larger_than(6));
+    assert!(!email.has_attachment_larger_than(7));
+}
+
+#[test]
+fn text_attachment_size_is_checked() {
+    let data = TEST_EMAIL_ATTACHMENTS.replace(
+        "Content-Type: text/plain\n",
+        "Content-Type: text/csv\nContent-Disposition: attachment; filename=\"data.csv\"\n"
+    );
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+    let csv = email.attachments().into_iter().find(|a| a.content_type == "text/csv").unwrap();
+
+    assert!(email.has_attachment_larger_than(csv.data.len() - 1));
+    assert!(!email.has_attachment_larger_than(csv.data.len()));
+}
+
+#[test]
+fn text_parts_are_not_attachments() {
+    let email = Email::from_vec(b"To: me@example.com\n\nA long text body\n".to_vec()).unwrap();
+
+    assert!(!email.has_attachment_larger_than(0));
+}
+
+static TEST_EMAIL_NAMED_ATTACHMENTS: &str = r#"To: me@example.com
+MIME-Version: 1.0
+Content-Type: multipart/mixed; boundary="b"
+
+--b
+Content-Type: text/plain
+
+Body text
+--b
+Content-Type: image/png
+Content-Disposition: attachment;
+ filename*0*=utf-8''%CE%B1%CE%B2;
+ filename*1=".png"
+Content-Transfer-Encoding: base64
+
+iVBORw0KGgo=
+--b
+Content-Type: application/pdf; name="report.pdf"
+Content-Transfer-Encoding: quoted-printable
+
+%PDF=00
+--b
+Content-Type: text/plain; charset=iso-8859-1
+Content-Disposition: attachment; filename="notes.txt"
+
+caf=E9
+--b
+Content-Type: application/octet-stream
+
+raw
+--b--
+"#;
+
+#[test]
+fn attachments_have_filenames_and_decoded_data() {
+    let email = Email::from_vec(TEST_EMAIL_NAMED_ATTACHMENTS.to_string().into_bytes()).unwrap();
+    let attachments = email.attachments();
+
+    let summary: Vec<_> = attachments
+        .iter()
+        .map(|a| (a.filename.as_deref(), a.content_type.as_str(), a.data.as_slice()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (Some("αβ.png"), "image/png", &b"\x89PNG\r\n\x1a\n"[..]),
+            (Some("report.pdf"), "application/pdf", &b"%PDF\x00"[..]),
+            (Some("notes.txt"), "text/plain", &b"caf=E9\n"[..]),
+            (None, "application/octet-stream", &b"raw"[..]),
+        ]
+    );
+}
+
+#[test]
+fn email_without_attachments_has_no_attachments() {
+    let email = Email::from_vec(b"To: me@example.com\n\nA long text body\n".to_vec()).unwrap();
+
+    assert_eq!(email.attachments(), Vec::<Attachment>::new());
+}
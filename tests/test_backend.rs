@@ -0,0 +1,188 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{BorrowedEmail, Compression, Email, FilesystemMaildirBackend, MaildirBackend, MaildirFlag, MdaError, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Subject: [URGENT] Status
+
+Body body body
+";
+
+struct RecordingBackend {
+    maildirs: Mutex<Vec<PathBuf>>,
+}
+
+impl MaildirBackend for RecordingBackend {
+    fn deliver(&self, _email: &BorrowedEmail, maildir: &Path) -> Result<PathBuf> {
+        self.maildirs.lock().unwrap().push(maildir.to_path_buf());
+        Ok(maildir.join("new/recorded"))
+    }
+}
+
+#[test]
+fn delivers_through_custom_backend() {
+    let backend = Arc::new(RecordingBackend{maildirs: Mutex::new(Vec::new())});
+    let mut email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    email.set_maildir_backend(backend.clone());
+
+    let result = email.deliver_to_maildir_detailed("/nonexistent/maildir").unwrap();
+
+    assert_eq!(result.path, Path::new("/nonexistent/maildir/new/recorded"));
+    assert_eq!(result.size, TEST_EMAIL.len() as u64);
+    assert!(!result.file_synced && !result.dirs_synced);
+    assert_eq!(*backend.maildirs.lock().unwrap(), [Path::new("/nonexistent/maildir")]);
+}
+
+#[test]
+fn custom_backend_fails_unsupported_deliveries() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let backend = Arc::new(RecordingBackend{maildirs: Mutex::new(Vec::new())});
+    let mut email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    email.set_maildir_backend(backend.clone());
+
+    let err = email.deliver_to_maildir_with_flags(tmpdir.path(), &[MaildirFlag::Seen]).err().unwrap();
+    assert!(matches!(err, MdaError::Io(ref err) if err.kind() == io::ErrorKind::Unsupported));
+    assert!(email.deliver_to_maildir_named(tmpdir.path(), "name").is_err());
+    assert!(email.deliver_to_maildir_once(tmpdir.path(), "<id@example.com>").is_err());
+    assert!(email.deliver_to_maildir_compressed(tmpdir.path(), Compression::None).is_err());
+    assert!(email.deliver_to_maildir_with_keywords(tmpdir.path(), &["work"]).is_err());
+
+    assert!(backend.maildirs.lock().unwrap().is_empty());
+    assert_eq!(fs::read_dir(tmpdir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn filesystem_backend_delivers_to_maildir() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let mut email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    email.set_maildir_backend(Arc::new(FilesystemMaildirBackend));
+
+    let path = email.deliver_to_maildir(tmpdir.path()).unwrap();
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("new"));
+    assert_eq!(fs::read(&path).unwrap(), TEST_EMAIL.as_bytes());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn in_memory_backend_records_routed_deliveries() {
+    use mda::{InMemoryDelivery, InMemoryMaildirBackend};
+
+    let backend = Arc::new(InMemoryMaildirBackend::new());
+    let mut email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    email.set_maildir_backend(backend.clone());
+
+    let route = email
+        .when(|e| e.header_field("Subject").unwrap().contains("[LOW]"))
+        .deliver("/maildir/low").unwrap()
+        .when(|e| e.header_field("Subject").unwrap().contains("[URGENT]"))
+        .deliver("/maildir/urgent").unwrap()
+        .otherwise("/maildir/inbox").unwrap();
+    email.deliver_to_maildir("/maildir/archive").unwrap();
+
+    assert_eq!(route.delivered_path(), Some(Path::new("/maildir/urgent/new/1")));
+    assert_eq!(backend.delivered_to("/maildir/urgent"), [TEST_EMAIL.as_bytes()]);
+    assert!(backend.delivered_to("/maildir/low").is_empty());
+    assert!(backend.delivered_to("/maildir/inbox").is_empty());
+    assert_eq!(
+        backend.deliveries(),
+        [
+            InMemoryDelivery{
+                maildir: "/maildir/urgent".into(),
+                path: "/maildir/urgent/new/1".into(),
+                data: TEST_EMAIL.into(),
+                keywords: Vec::new(),
+                sidecar: None,
+            },
+            InMemoryDelivery{
+                maildir: "/maildir/archive".into(),
+                path: "/maildir/archive/new/2".into(),
+                data: TEST_EMAIL.into(),
+                keywords: Vec::new(),
+                sidecar: None,
+            },
+        ]
+    );
+    assert!(!Path::new("/maildir").exists());
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn in_memory_backend_records_all_kinds_of_deliveries() {
+    use mda::InMemoryMaildirBackend;
+
+    let backend = Arc::new(InMemoryMaildirBackend::new());
+    let mut email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    email.set_maildir_backend(backend.clone());
+    let maildir = Path::new("/maildir");
+
+    assert_eq!(
+        email.deliver_to_maildir_with_flags(maildir, &[MaildirFlag::Seen, MaildirFlag::Flagged]).unwrap(),
+        Path::new("/maildir/cur/1:2,FS")
+    );
+    assert_eq!(
+        email.deliver_to_maildir_with_keywords(maildir, &["work"]).unwrap(),
+        Path::new("/maildir/cur/2:2,")
+    );
+    assert_eq!(
+        email.deliver_to_maildir_named(maildir, "ticket:1").unwrap(),
+        Path::new(r"/maildir/new/ticket\0721")
+    );
+    assert!(email.deliver_to_maildir_named(maildir, "ticket:1").is_err());
+    assert_eq!(
+        email.deliver_to_maildir_compressed(maildir, Compression::None).unwrap(),
+        Path::new("/maildir/new/4")
+    );
+    assert!(email.deliver_to_maildir_once(maildir, "<id@example.com>").unwrap().is_some());
+    assert!(email.deliver_to_maildir_once(maildir, "<id@example.com>").unwrap().is_none());
+
+    let deliveries = backend.deliveries();
+    assert_eq!(deliveries.len(), 5);
+    assert_eq!(deliveries[1].keywords, ["work"]);
+    assert!(deliveries.iter().all(|delivery| delivery.data == TEST_EMAIL.as_bytes()));
+    assert!(!maildir.exists());
+}
+
+#[cfg(all(feature = "test-util", feature = "serde"))]
+#[test]
+fn in_memory_backend_records_sidecar() {
+    use mda::InMemoryMaildirBackend;
+
+    let backend = Arc::new(InMemoryMaildirBackend::new());
+    let mut email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    email.set_maildir_backend(backend.clone());
+
+    let (path, sidecar) = email.deliver_to_maildir_with_sidecar("/maildir").unwrap();
+
+    let deliveries = backend.deliveries();
+    assert_eq!(deliveries[0].path, path);
+    assert!(sidecar.is_ok());
+    let sidecar = String::from_utf8(deliveries[0].sidecar.clone().unwrap()).unwrap();
+    assert!(sidecar.contains("[URGENT] Status"));
+    assert!(!Path::new("/maildir").exists());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn sidecar_failure_still_reports_delivered_email() {
+    let backend = Arc::new(RecordingBackend{maildirs: Mutex::new(Vec::new())});
+    let mut email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    email.set_maildir_backend(backend.clone());
+
+    let (path, sidecar) = email.deliver_to_maildir_with_sidecar("/maildir").unwrap();
+
+    assert_eq!(path, Path::new("/maildir/new/recorded"));
+    assert!(sidecar.is_err());
+    assert_eq!(*backend.maildirs.lock().unwrap(), [PathBuf::from("/maildir")]);
+}
@@ -0,0 +1,179 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+
+static TEST_EMAIL_MULTIPART: &str = r#"Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Content-type: multipart/mixed; boundary="XtT01VFrJIenjlg+ZCXSSWq4"
+
+This is a multi-part message in MIME format.
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: text/plain; charset="utf-8"
+Content-Transfer-Encoding: base64
+
+zpHOks6TCg==
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: image/png
+Content-Transfer-Encoding: base64
+
+iVBORw0KGgo=
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: text/plain
+
+Second text part
+--XtT01VFrJIenjlg+ZCXSSWq4--
+"#;
+
+static TEST_EMAIL_ALTERNATIVE: &str = r#"To: me@example.com
+Content-Type: multipart/mixed; boundary="outer"
+
+--outer
+Content-Type: multipart/alternative; boundary="inner"
+
+--inner
+Content-Type: text/plain; charset=utf-8
+
+Plain text
+--inner
+Content-Type: text/html; charset=utf-8
+
+<p>HTML text</p>
+--inner--
+
+--outer
+Content-Type: text/html; charset=utf-8
+
+<html><head><title>Title</title><style>p { color: red; }</style></head>
+<body>
+<p>Tom &amp; Jerry&nbsp;&#x2764;</p>
+<script>alert("hi");</script>
+<ul><li>One</li><li>Two</li></ul>
+</body></html>
+--outer--
+"#;
+
+static TEST_EMAIL_REPLY: &str = r#"Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Subject: Re: Status
+
+Thanks, I will have a look.
+
+On Monday, someone wrote:
+> This is URGENT, please reply.
+>> Earlier URGENT request.
+
+-- 
+Me, URGENT signatures are not new content
+"#;
+
+#[test]
+fn body_text_contains_only_text_parts() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.body_text(), "ΑΒΓ\nSecond text part\n");
+}
+
+#[test]
+fn body_text_prefers_plain_text_alternative() {
+    let email = Email::from_vec(TEST_EMAIL_ALTERNATIVE.to_string().into_bytes()).unwrap();
+
+    let text = email.body_text();
+    assert!(text.starts_with("Plain text\n"));
+    assert!(!text.contains("HTML text"));
+}
+
+#[test]
+fn body_text_uses_html_alternative_without_plain_text() {
+    let data = TEST_EMAIL_ALTERNATIVE.replace("text/plain", "application/pdf");
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert!(email.body_text().starts_with("HTML text\n"));
+}
+
+#[test]
+fn body_text_strips_html() {
+    let email = Email::from_vec(TEST_EMAIL_ALTERNATIVE.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.body_text(), "Plain text\nTom & Jerry \u{2764}\nOne\nTwo\n");
+}
+
+#[test]
+fn body_text_of_single_part_email_is_body() {
+    let email = Email::from_vec(TEST_EMAIL_REPLY.to_string().into_bytes()).unwrap();
+
+    assert!(email.body_text().starts_with("Thanks, I will have a look."));
+    assert!(email.body_text().ends_with("are not new content\n"));
+}
+
+#[test]
+fn new_content_search_ignores_quoted_text() {
+    let email = Email::from_vec(TEST_EMAIL_REPLY.to_string().into_bytes()).unwrap();
+
+    assert!(email.search_new_content(r"will have a look").unwrap());
+    assert!(email.search_new_content(r"^On Monday").unwrap());
+    assert!(!email.search_new_content(r"URGENT").unwrap());
+}
+
+#[test]
+fn new_content_search_ignores_signature() {
+    let email = Email::from_vec(TEST_EMAIL_REPLY.to_string().into_bytes()).unwrap();
+
+    assert!(!email.search_new_content(r"signatures").unwrap());
+}
+
+#[test]
+fn body_with_only_whitespace_text_is_empty() {
+    let data = TEST_EMAIL_MULTIPART
+        .replace("zpHOks6TCg==", "wqAgCg==")
+        .replace("Second text part", "\t");
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert!(!email.body().is_empty());
+    assert!(email.body_is_empty());
+}
+
+#[test]
+fn body_with_text_is_not_empty() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART.to_string().into_bytes()).unwrap();
+    assert!(!email.body_is_empty());
+
+    let email = Email::from_vec(b"To: me@example.com\n".to_vec()).unwrap();
+    assert!(email.body_is_empty());
+}
+
+#[test]
+fn quote_ratio_counts_nested_quotes_and_ignores_blank_lines() {
+    let email = Email::from_vec(TEST_EMAIL_REPLY.to_string().into_bytes()).unwrap();
+
+    // 2 quoted lines out of 6 non-blank lines.
+    assert!((email.quote_ratio() - 2.0 / 6.0).abs() < 1e-6);
+}
+
+#[test]
+fn quote_ratio_of_empty_body_is_zero() {
+    let email = Email::from_vec(b"To: me@example.com\n\n\n".to_vec()).unwrap();
+
+    assert_eq!(email.quote_ratio(), 0.0);
+}
+
+#[test]
+fn html_with_many_references_and_skipped_elements_is_stripped() {
+    let html = format!(
+        "<p>{}</p>{}<p>End</p>",
+        "&amp &".repeat(100_000),
+        "<SCRIPT>x</Script><style>y</style>".repeat(20_000)
+    );
+    let data = format!("Content-Type: text/html\n\n{}\n", html);
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    let text = email.body_text();
+    assert!(text.starts_with("&amp &&amp &"));
+    assert!(text.ends_with("&amp &\nEnd\n"));
+    assert!(!text.contains('x') && !text.contains('y'));
+}
@@ -0,0 +1,38 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+
+static TEST_EMAIL: &[u8] = b"Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Subject: =?utf-8?q?Hello_=CE=B1?=
+Content-Transfer-Encoding: base64
+
+SGVsbG8gd29ybGQK
+";
+
+#[test]
+fn borrowed_email_does_not_copy_raw_data() {
+    let email = Email::from_slice(TEST_EMAIL).unwrap();
+
+    assert_eq!(email.raw_data().as_ptr(), TEST_EMAIL.as_ptr());
+    assert_eq!(email.raw_data(), TEST_EMAIL);
+}
+
+#[test]
+fn borrowed_email_has_same_contents_as_owned_email() {
+    let borrowed = Email::from_slice(TEST_EMAIL).unwrap();
+    let owned = Email::from_vec(TEST_EMAIL.to_vec()).unwrap();
+
+    assert_eq!(borrowed.data(), owned.data());
+    assert_eq!(borrowed.header(), owned.header());
+    assert_eq!(borrowed.body(), owned.body());
+    assert_eq!(borrowed.header_field("Subject"), owned.header_field("Subject"));
+    assert!(borrowed.semantically_equal(&owned, &[]));
+    assert_eq!(borrowed.into_raw(), owned.into_raw());
+}
@@ -6,9 +6,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use mda::{Email, EmailRegex};
+use mda::{Email, EmailRegex, NormalizationOptions, ValidationIssue};
 
-static TEST_EMAIL_FAKE_BOUNDARY: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_FAKE_BOUNDARY: &str = r#"Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Content-type: multipart/alternative; boundary="QWFCYkN"
 
@@ -20,7 +20,8 @@ Content-transfer-encoding: base64
 --QWFCYkN
 "#;
 
-static TEST_EMAIL_BOUNDARY_BEGIN_AFTER_END: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_BOUNDARY_BEGIN_AFTER_END: &str = r#"Return-Path: <me@source.com>
+MIME-Version: 1.0
 To: Destination <someone.else@destination.com>
 Content-type: multipart/alternative; boundary="XtT01VFrJIenjlg+ZCXSSWq4"
 
@@ -47,3 +48,155 @@ fn boundary_begin_after_end_is_parsed() {
         ).is_ok()
     );
 }
+
+fn nested_multipart_email(depth: usize) -> Vec<u8> {
+    let mut data = String::from("Return-Path: <me@source.com>\nMIME-Version: 1.0\n");
+
+    for i in 0..depth {
+        data += &format!("Content-Type: multipart/mixed; boundary=\"b{}\"\n\n--b{}\n", i, i);
+    }
+    data += "Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: base64\n\nzpHOks6TCg==\n";
+    for i in (0..depth).rev() {
+        data += &format!("--b{}--\n", i);
+    }
+
+    data.into_bytes()
+}
+
+#[test]
+fn nested_parts_within_depth_limit_are_parsed() {
+    let email = Email::from_vec(nested_multipart_email(50)).unwrap();
+
+    assert!(email.validate().is_empty());
+    assert!(email.body().search("ΑΒΓ").unwrap());
+}
+
+#[test]
+fn nested_parts_beyond_depth_limit_are_body_data() {
+    let email = Email::from_vec(nested_multipart_email(500)).unwrap();
+
+    assert_eq!(email.validate(), [ValidationIssue::PartDepthLimitReached]);
+    assert!(email.body().search("^--b499--$").unwrap());
+    assert!(email.body().search("zpHOks6TCg==").unwrap());
+}
+
+#[test]
+fn part_depth_limit_is_configurable() {
+    let options = NormalizationOptions{
+        max_part_depth: 2,
+        ..Default::default()
+    };
+    let email = Email::from_vec_with_options(nested_multipart_email(2), options).unwrap();
+
+    assert_eq!(email.validate(), [ValidationIssue::PartDepthLimitReached]);
+    assert!(!email.body().search("ΑΒΓ").unwrap());
+}
+
+static TEST_EMAIL_MISSING_INNER_END: &str = r#"Return-Path: <me@source.com>
+MIME-Version: 1.0
+Content-type: multipart/mixed; boundary="outer"
+
+--outer
+Content-type: multipart/alternative; boundary="inner"
+
+--inner
+Content-Type: text/plain
+
+Inner text
+--outer--
+"#;
+
+#[test]
+fn balanced_boundaries_are_valid() {
+    let email = Email::from_vec(nested_multipart_email(3)).unwrap();
+
+    assert!(!email.validate().contains(&ValidationIssue::UnbalancedBoundaries));
+}
+
+#[test]
+fn missing_inner_boundary_end_is_unbalanced() {
+    let email = Email::from_vec(TEST_EMAIL_MISSING_INNER_END.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.validate(), [ValidationIssue::UnbalancedBoundaries]);
+    assert!(email.body().search("^Inner text$").unwrap());
+}
+
+#[test]
+fn boundary_begin_after_end_is_balanced() {
+    let email = Email::from_vec(
+        TEST_EMAIL_BOUNDARY_BEGIN_AFTER_END.to_string().into_bytes()
+    ).unwrap();
+
+    assert!(email.validate().is_empty());
+}
+
+#[test]
+fn multipart_email_without_mime_version_is_flagged() {
+    let data = String::from_utf8(nested_multipart_email(1)).unwrap();
+    let email = Email::from_vec(data.replace("MIME-Version: 1.0\n", "").into_bytes()).unwrap();
+
+    assert_eq!(email.mime_version(), None);
+    assert_eq!(email.validate(), [ValidationIssue::MissingMimeVersion]);
+}
+
+#[test]
+fn mime_version_with_comment_is_accepted() {
+    let data = String::from_utf8(nested_multipart_email(1)).unwrap();
+    let data = data.replace("MIME-Version: 1.0\n", "MIME-Version: 1.0 (Generated)\n");
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert_eq!(email.mime_version(), Some("1.0 (Generated)"));
+    assert!(email.validate().is_empty());
+}
+
+#[test]
+fn non_mime_email_without_mime_version_is_valid() {
+    let email = Email::from_vec(b"Subject: Hi\n\nBody\n".to_vec()).unwrap();
+
+    assert_eq!(email.mime_version(), None);
+    assert!(email.validate().is_empty());
+}
+
+static TEST_EMAIL_ENCODED_MULTIPART: &str = r#"Return-Path: <me@source.com>
+MIME-Version: 1.0
+Content-Transfer-Encoding: base64
+Content-Type: multipart/mixed; boundary="outer"
+
+Preamble text
+--outer
+Content-Type: multipart/alternative; boundary="inner"
+Content-Transfer-Encoding: quoted-printable
+
+--inner
+Content-Type: text/plain
+Content-Transfer-Encoding: base64
+
+SGVsbG8gd29ybGQ=
+--inner
+Content-Type: text/html; charset=utf-8
+Content-Transfer-Encoding: quoted-printable
+
+<p>Caf=C3=A9</p>
+--inner--
+--outer--
+"#;
+
+#[test]
+fn encoding_of_multipart_containers_is_ignored() {
+    let email = Email::from_vec(TEST_EMAIL_ENCODED_MULTIPART.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.validate(), [ValidationIssue::InvalidMultipartEncoding]);
+    assert!(email.body().search("^Preamble text$").unwrap());
+    assert!(email.body().search("^Hello world$").unwrap());
+    assert!(email.body().search("^<p>Café</p>$").unwrap());
+}
+
+#[test]
+fn unencoded_multipart_containers_are_valid() {
+    let data = TEST_EMAIL_ENCODED_MULTIPART
+        .replacen("Content-Transfer-Encoding: base64", "Content-Transfer-Encoding: 7bit", 1)
+        .replace("Content-Transfer-Encoding: quoted-printable\n\n--inner", "\n--inner");
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert!(email.validate().is_empty());
+}
@@ -6,9 +6,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use mda::{Email, EmailRegex};
+use mda::{Email, EmailRegex, NormalizationOptions};
 
-static TEST_EMAIL_ISO_BASE64: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_ISO_BASE64: &str = r#"Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Content-Type: text/plain; charset="iso-8859-7"
 Content-Transfer-Encoding: base64
@@ -18,7 +18,7 @@ tuvr4SDm5/Tl3yDnIPj1994g8+/1LCDj6Scg3Ovr4SDq6+Hf5em3CvTv7SDd8OHp7e8g9O/1IMTe
 9OftIMHj7/HcLCD07yDI3eH08e8sIOrh6SD07/XyINP05fbc7e/18i4=
 "#;
 
-static TEST_EMAIL_ISO_8BIT: &'static [u8] = &[
+static TEST_EMAIL_ISO_8BIT: &[u8] = &[
     b'C', b'o', b'n', b't', b'e', b'n', b't', b'-', b'T', b'y', b'p', b'e',
     b':', b' ', b't', b'e', b'x', b't', b'/', b'p', b'l', b'a', b'i', b'n',
     b';', b' ', b'c', b'h', b'a', b'r', b's', b'e', b't', b'=', b'"', b'i',
@@ -42,7 +42,7 @@ static TEST_EMAIL_ISO_8BIT: &'static [u8] = &[
     0xf4, 0xe5, 0xf6, 0xdc, 0xed, 0xef, 0xf5, 0xf2, 0x2e
 ];
 
-static TEST_EMAIL_MULTIPART_ISO: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_MULTIPART_ISO: &str = r#"Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Content-type: multipart/alternative; boundary="XtT01VFrJIenjlg+ZCXSSWq4"
 
@@ -102,3 +102,93 @@ fn email_part_with_charset_is_decoded() {
     assert!(email.body().search(r"Sample US-ASCII text.").unwrap());
     assert!(email.body().search(r"τα δύσκολα και τ' ανεκτίμητα Εύγε·").unwrap());
 }
+
+#[test]
+fn primary_charset_is_charset_of_first_text_part() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART_ISO.as_bytes().to_vec()).unwrap();
+
+    assert_eq!(email.primary_charset(), Some("us-ascii"));
+}
+
+#[test]
+fn primary_charset_is_lowercase() {
+    let data = TEST_EMAIL_ISO_BASE64.replace("iso-8859-7", "ISO-8859-7");
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert_eq!(email.primary_charset(), Some("iso-8859-7"));
+}
+
+#[test]
+fn primary_charset_is_none_if_not_declared() {
+    let email = Email::from_vec(b"Subject: hi\n\nBody\n".to_vec()).unwrap();
+
+    assert_eq!(email.primary_charset(), None);
+}
+
+static TEST_EMAIL_LATIN1_CP1252_8BIT: &[u8] =
+    b"Subject: =?iso-8859-1?q?It=92s_here?=\r\n\
+Content-Type: text/plain; charset=\"iso-8859-1\"\r\n\
+Content-Transfer-Encoding: 8bit\r\n\
+\r\n\
+It\x92s \x93quoted\x94\r\n";
+
+#[test]
+fn iso_8859_1_is_decoded_as_windows_1252() {
+    let email = Email::from_vec(TEST_EMAIL_LATIN1_CP1252_8BIT.to_vec()).unwrap();
+
+    assert_eq!(email.header_field("Subject").unwrap().trim(), "It\u{2019}s here");
+    assert!(email.body().search("It\u{2019}s \u{201c}quoted\u{201d}").unwrap());
+    assert!(!email.body_text().contains('\u{92}'));
+}
+
+#[test]
+fn nonstandard_windows_charset_label_is_decoded() {
+    let mut data = b"Content-Type: text/plain; charset=cp-1256\n\n".to_vec();
+    data.extend([0xd3, 0xe1, 0xc7, 0xe3, b'\n']);
+    let email = Email::from_vec(data).unwrap();
+
+    assert!(email.body().search("سلام").unwrap());
+}
+
+#[test]
+fn nonstandard_charset_label_in_encoded_word_is_decoded() {
+    let email = Email::from_vec(b"Subject: =?ks_c_5601?B?x9E=?=\n\nBody\n".to_vec()).unwrap();
+
+    assert_eq!(email.header_field("Subject"), Some(" 한"));
+}
+
+#[test]
+fn user_charset_alias_is_used() {
+    let mut data = b"Content-Type: text/plain; charset=X-Custom\n\n".to_vec();
+    data.extend([0xe1, 0xe2, b'\n']);
+    let mut options = NormalizationOptions::default();
+    options.charset_aliases.insert("x-custom".to_string(), "iso-8859-7".to_string());
+    let email = Email::from_vec_with_options(data, options).unwrap();
+
+    assert!(email.body().search("αβ").unwrap());
+}
+
+fn text_email(charset: &str, encoding: &str, body: &str) -> Email {
+    Email::from_vec(
+        format!(
+            "Content-Type: text/plain; charset={}\nContent-Transfer-Encoding: {}\n\n{}",
+            charset, encoding, body
+        ).into_bytes()
+    ).unwrap()
+}
+
+#[test]
+fn utf8_bom_is_stripped() {
+    assert_eq!(text_email("utf-8", "8bit", "\u{feff}Héllo\n").body_text(), "Héllo\n");
+    assert_eq!(text_email("x-unknown", "8bit", "\u{feff}Héllo\n").body_text(), "Héllo\n");
+}
+
+#[test]
+fn utf16le_is_detected_from_bom() {
+    // "\u{feff}Héllo\n" in UTF-16LE.
+    let body = "//5IAOkAbABsAG8ACgA=\n";
+
+    assert_eq!(text_email("utf-16le", "base64", body).body_text(), "Héllo\n");
+    assert_eq!(text_email("iso-8859-1", "base64", body).body_text(), "Héllo\n");
+    assert_eq!(text_email("x-unknown", "base64", body).body_text(), "Héllo\n");
+}
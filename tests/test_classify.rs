@@ -0,0 +1,175 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+mod common;
+
+use common::email_with_header;
+use mda::{Email, Priority};
+
+#[test]
+fn spam_score_is_parsed_from_spam_status() {
+    let email = email_with_header(
+        "X-Spam-Status: Yes, score=7.3 required=5.0 tests=BAYES_99,\n\tHTML_MESSAGE autolearn=no"
+    );
+
+    assert_eq!(email.spam_score(), Some(7.3));
+}
+
+#[test]
+fn negative_spam_score_is_parsed() {
+    let email = email_with_header("X-Spam-Status: No, score=-1.9 required=5.0");
+
+    assert_eq!(email.spam_score(), Some(-1.9));
+}
+
+#[test]
+fn spam_score_is_parsed_from_spam_score() {
+    assert_eq!(email_with_header("X-Spam-Score: 4.2").spam_score(), Some(4.2));
+    assert_eq!(email_with_header("X-Spam-Score: 12 (++++++++++++)").spam_score(), Some(12.0));
+}
+
+#[test]
+fn spam_status_takes_precedence_over_spam_score() {
+    let email = email_with_header("X-Spam-Score: 1.0\nX-Spam-Status: Yes, score=9.0");
+
+    assert_eq!(email.spam_score(), Some(9.0));
+}
+
+#[test]
+fn spam_score_is_none_if_missing_or_invalid() {
+    assert_eq!(email_with_header("Subject: hi").spam_score(), None);
+    assert_eq!(email_with_header("X-Spam-Status: Yes").spam_score(), None);
+    assert_eq!(email_with_header("X-Spam-Score: high").spam_score(), None);
+}
+
+#[test]
+fn email_with_null_return_path_is_bounce() {
+    for return_path in &["<>", "< >"] {
+        let data = format!("Return-Path: {}\nSubject: Failure\n\nBody\n", return_path);
+        assert!(Email::from_vec(data.into_bytes()).unwrap().is_bounce());
+    }
+}
+
+#[test]
+fn delivery_status_report_is_bounce() {
+    let email = email_with_header(
+        "Content-Type: multipart/report; report-type=\"Delivery-Status\";\n boundary=\"abc\""
+    );
+
+    assert!(email.is_bounce());
+}
+
+#[test]
+fn email_from_mailer_daemon_is_bounce() {
+    let email = email_with_header("From: Mail Delivery System <MAILER-DAEMON@example.com>");
+
+    assert!(email.is_bounce());
+}
+
+#[test]
+fn normal_email_is_not_bounce() {
+    let email = email_with_header(
+        "From: Someone <someone@example.com>\n\
+         Content-Type: multipart/report; report-type=disposition-notification"
+    );
+
+    assert!(!email.is_bounce());
+}
+
+#[test]
+fn subject_reply_prefixes_are_replies() {
+    for subject in &["Re: Hello", "RE: Hello", "Aw: Hallo", "SV: Hej", "Re[2]: Hello", "[list] Re: Hello"] {
+        let email = email_with_header(&format!("Subject: {}", subject));
+        assert!(email.is_reply(), "{}", subject);
+        assert!(!email.is_forward(), "{}", subject);
+    }
+}
+
+#[test]
+fn subject_forward_prefixes_are_forwards() {
+    for subject in &["Fwd: Hello", "FW: Hello", "WG: Hallo", "[list] Fwd: Hello"] {
+        let email = email_with_header(&format!("Subject: {}", subject));
+        assert!(email.is_forward(), "{}", subject);
+        assert!(!email.is_reply(), "{}", subject);
+    }
+}
+
+#[test]
+fn email_with_in_reply_to_or_references_is_reply() {
+    for header in &["In-Reply-To: <a@example.com>", "References: <a@example.com>"] {
+        let email = email_with_header(&format!("Subject: Hello\n{}", header));
+        assert!(email.is_reply(), "{}", header);
+    }
+}
+
+#[test]
+fn email_with_rfc822_part_is_forward() {
+    let email = email_with_header(
+        "Subject: Have a look\n\
+         MIME-Version: 1.0\n\
+         Content-Type: multipart/mixed; boundary=\"abc\"\n\
+         \n\
+         --abc\n\
+         Content-Type: text/plain\n\
+         \n\
+         See below\n\
+         --abc\n\
+         Content-Type: message/rfc822\n\
+         \n\
+         Subject: Original\n\
+         \n\
+         Original body\n\
+         --abc--"
+    );
+
+    assert!(email.is_forward());
+    assert!(!email.is_reply());
+}
+
+#[test]
+fn plain_subjects_are_neither_replies_nor_forwards() {
+    for subject in &["Hello", "Meeting notes: agenda", "Rest: of the day"] {
+        let email = email_with_header(&format!("Subject: {}", subject));
+        assert!(!email.is_reply(), "{}", subject);
+        assert!(!email.is_forward(), "{}", subject);
+    }
+}
+
+#[test]
+fn priority_is_parsed_from_x_priority() {
+    assert_eq!(email_with_header("X-Priority: 1 (Highest)").priority(), Priority::High);
+    assert_eq!(email_with_header("X-Priority: 2").priority(), Priority::High);
+    assert_eq!(email_with_header("X-Priority: 3 (Normal)").priority(), Priority::Normal);
+    assert_eq!(email_with_header("X-Priority: 5 (Lowest)").priority(), Priority::Low);
+}
+
+#[test]
+fn priority_is_parsed_from_importance_and_priority() {
+    assert_eq!(email_with_header("Importance: High").priority(), Priority::High);
+    assert_eq!(email_with_header("Importance: low").priority(), Priority::Low);
+    assert_eq!(email_with_header("Priority: urgent").priority(), Priority::High);
+    assert_eq!(email_with_header("Priority: non-urgent").priority(), Priority::Low);
+}
+
+#[test]
+fn x_priority_takes_precedence_over_importance_and_priority() {
+    let email = email_with_header("Priority: urgent\nImportance: high\nX-Priority: 5");
+    assert_eq!(email.priority(), Priority::Low);
+
+    let email = email_with_header("Priority: urgent\nImportance: low");
+    assert_eq!(email.priority(), Priority::Low);
+}
+
+#[test]
+fn unrecognized_priorities_are_ignored() {
+    let email = email_with_header("X-Priority: urgent\nImportance: high");
+    assert_eq!(email.priority(), Priority::High);
+
+    assert_eq!(email_with_header("Importance: whatever").priority(), Priority::Normal);
+    assert_eq!(email_with_header("Subject: Hi").priority(), Priority::Normal);
+}
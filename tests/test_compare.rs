@@ -0,0 +1,45 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+
+static TEST_EMAIL: &str = "Received: from a.example.com by b.example.com
+Delivered-To: me@example.com
+To: me@example.com
+Subject: =?utf-8?b?zpHOks6T?=
+Message-ID: <1234@example.com>
+
+Body body body
+";
+
+static TEST_EMAIL_OTHER_PATH: &str = "Message-ID: <1234@example.com>
+Received: from c.example.com by d.example.com
+Received: from d.example.com by b.example.com
+Subject: ΑΒΓ
+To: me@example.com
+
+Body body body
+";
+
+#[test]
+fn emails_with_different_trace_fields_are_semantically_equal() {
+    let email1 = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let email2 = Email::from_vec(TEST_EMAIL_OTHER_PATH.to_string().into_bytes()).unwrap();
+
+    assert!(email1.semantically_equal(&email2, &["received", "Delivered-To"]));
+    assert!(!email1.semantically_equal(&email2, &["Received"]));
+}
+
+#[test]
+fn emails_with_different_bodies_are_not_semantically_equal() {
+    let email1 = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let email2 = Email::from_vec(TEST_EMAIL.replace("Body body", "Body").into_bytes()).unwrap();
+
+    assert!(email1.semantically_equal(&email1, &[]));
+    assert!(!email1.semantically_equal(&email2, &[]));
+}
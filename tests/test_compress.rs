@@ -0,0 +1,73 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Compression, Email};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+
+Body body body
+";
+
+fn deliver_compressed(compression: Compression) -> (tempfile::TempDir, PathBuf) {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let path = email.deliver_to_maildir_compressed(tmpdir.path(), compression).unwrap();
+    (tmpdir, path)
+}
+
+#[test]
+fn uncompressed_delivery_stores_raw_data() {
+    let (tmpdir, path) = deliver_compressed(Compression::None);
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("new"));
+    assert!(!path.to_str().unwrap().contains(".gz"));
+    assert!(path.to_str().unwrap().ends_with(&format!(",S={}", TEST_EMAIL.len())));
+    assert_eq!(fs::read(&path).unwrap(), TEST_EMAIL.as_bytes());
+    assert_eq!(fs::read_dir(tmpdir.path().join("tmp")).unwrap().count(), 0);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_delivery_stores_gzip_data() {
+    use std::io::Read;
+
+    let (_tmpdir, path) = deliver_compressed(Compression::Gzip);
+    assert!(path.to_str().unwrap().ends_with(&format!(".gz,S={}", TEST_EMAIL.len())));
+
+    let mut decompressed = Vec::new();
+    flate2::read::GzDecoder::new(fs::File::open(&path).unwrap())
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, TEST_EMAIL.as_bytes());
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_delivery_stores_zstd_data() {
+    let (_tmpdir, path) = deliver_compressed(Compression::Zstd);
+    assert!(path.to_str().unwrap().ends_with(&format!(".zst,S={}", TEST_EMAIL.len())));
+
+    let decompressed = zstd::decode_all(fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(decompressed, TEST_EMAIL.as_bytes());
+}
+
+#[test]
+fn compressed_delivery_is_never_hard_linked() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    let plain = email.deliver_to_maildir(tmpdir.path().join("plain")).unwrap();
+    let compressed = email.deliver_to_maildir_compressed(tmpdir.path().join("archive"), Compression::None).unwrap();
+
+    assert_eq!(fs::metadata(&plain).unwrap().nlink(), 1);
+    assert_eq!(fs::metadata(&compressed).unwrap().nlink(), 1);
+}
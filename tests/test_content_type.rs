@@ -0,0 +1,86 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+
+fn email_with_content_type(content_type: &str) -> Email {
+    let data = format!(
+        "Return-Path: <me@source.com>\nContent-Type: {}\n\nBody\n",
+        content_type
+    );
+    Email::from_vec(data.into_bytes()).unwrap()
+}
+
+#[test]
+fn content_type_params_are_parsed() {
+    let email = email_with_content_type(
+        r#"multipart/signed; protocol="application/pgp-signature"; micalg=pgp-sha256;
+ boundary="abc;def""#
+    );
+    let params = email.content_type_params();
+
+    assert_eq!(params.len(), 3);
+    assert_eq!(params["protocol"], "application/pgp-signature");
+    assert_eq!(params["micalg"], "pgp-sha256");
+    assert_eq!(params["boundary"], "abc;def");
+}
+
+#[test]
+fn content_type_param_names_are_lowercase() {
+    let email = email_with_content_type("text/plain; CharSet=UTF-8");
+
+    assert_eq!(email.content_type_params()["charset"], "UTF-8");
+}
+
+#[test]
+fn content_type_param_quoted_values_are_unescaped() {
+    let email = email_with_content_type(r#"text/plain; name="a \"quoted\" name""#);
+
+    assert_eq!(email.content_type_params()["name"], r#"a "quoted" name"#);
+}
+
+#[test]
+fn content_type_param_continuations_are_joined_and_decoded() {
+    let email = email_with_content_type(
+        "application/pdf; name*0*=utf-8''%CE%91%CE%92; name*1=\" and more\""
+    );
+
+    assert_eq!(email.content_type_params()["name"], "ΑΒ and more");
+}
+
+#[test]
+fn content_type_params_are_empty_without_content_type() {
+    let email = Email::from_vec(b"Subject: hi\n\nBody\n".to_vec()).unwrap();
+
+    assert!(email.content_type_params().is_empty());
+}
+
+#[test]
+fn format_flowed_is_detected() {
+    let flowed = email_with_content_type("text/plain; charset=utf-8; format=Flowed");
+    let delsp = email_with_content_type("text/plain; format=flowed; DelSp=yes");
+    let fixed = email_with_content_type("text/plain; charset=utf-8");
+
+    assert!(flowed.is_format_flowed());
+    assert!(!flowed.is_delsp());
+    assert!(delsp.is_format_flowed());
+    assert!(delsp.is_delsp());
+    assert!(!fixed.is_format_flowed());
+    assert!(!fixed.is_delsp());
+}
+
+#[test]
+fn format_flowed_is_detected_in_first_text_part() {
+    let email = Email::from_vec(
+        b"Content-Type: multipart/alternative; boundary=\"b\"\n\n\
+          --b\nContent-Type: text/plain; format=flowed; delsp=yes\n\nText\n\
+          --b\nContent-Type: text/html\n\n<p>Text</p>\n--b--\n".to_vec()).unwrap();
+
+    assert!(email.is_format_flowed());
+    assert!(email.is_delsp());
+}
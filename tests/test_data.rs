@@ -0,0 +1,240 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{BorrowedEmail, Email, NormalizationOptions};
+use sha2::{Digest, Sha256};
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Subject: =?utf-8?b?zpHOks6T?=
+
+Body body body
+";
+
+#[test]
+fn from_reader_reads_all_data() {
+    let email = Email::from_reader(TEST_EMAIL.as_bytes()).unwrap();
+
+    assert_eq!(email.raw_data(), TEST_EMAIL.as_bytes());
+    assert_eq!(email.header_field("Subject"), Some(" ΑΒΓ"));
+}
+
+#[test]
+fn raw_hash_is_sha256_of_raw_data() {
+    let expected = Sha256::digest(TEST_EMAIL.as_bytes());
+
+    let email = Email::from_reader_with_raw_hash(TEST_EMAIL.as_bytes()).unwrap();
+    assert_eq!(email.raw_data(), TEST_EMAIL.as_bytes());
+    assert_eq!(email.raw_hash(), &expected[..]);
+
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    assert_eq!(email.raw_hash(), &expected[..]);
+}
+
+#[test]
+fn raw_hash_reflects_edited_raw_data() {
+    let mut email = Email::from_reader_with_raw_hash(TEST_EMAIL.as_bytes()).unwrap();
+    email.set_header_field("X-Spam", "yes").unwrap();
+
+    assert_eq!(email.raw_hash(), &Sha256::digest(email.raw_data())[..]);
+}
+
+#[test]
+fn from_file_reads_file_data() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let path = tmpdir.path().join("email");
+    std::fs::write(&path, TEST_EMAIL).unwrap();
+
+    let email = Email::from_file(&path).unwrap();
+
+    assert_eq!(email.raw_data(), TEST_EMAIL.as_bytes());
+}
+
+#[test]
+fn from_file_fails_for_missing_file() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    assert!(Email::from_file(tmpdir.path().join("missing")).is_err());
+}
+
+#[test]
+fn into_raw_returns_raw_data() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.into_raw(), TEST_EMAIL.as_bytes());
+}
+
+#[test]
+fn into_normalized_returns_normalized_data() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let normalized_data = email.data().to_vec();
+
+    let into_normalized_data = email.into_normalized();
+
+    assert_eq!(into_normalized_data, normalized_data);
+    assert!(String::from_utf8(into_normalized_data).unwrap().contains("Subject: ΑΒΓ"));
+}
+
+#[test]
+fn smtp_size_counts_lf_as_crlf() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.smtp_size(), TEST_EMAIL.len() + TEST_EMAIL.matches('\n').count());
+}
+
+#[test]
+fn smtp_size_of_crlf_email_is_raw_size() {
+    let data = TEST_EMAIL.replace('\n', "\r\n");
+    let email = Email::from_vec(data.clone().into_bytes()).unwrap();
+
+    assert_eq!(email.smtp_size(), data.len());
+}
+
+#[test]
+fn empty_input_has_empty_header_and_body() {
+    let email = Email::from_vec(Vec::new()).unwrap();
+
+    assert!(email.header().is_empty());
+    assert!(email.body().is_empty());
+    assert!(email.data().is_empty());
+    assert!(email.raw_data().is_empty());
+    assert_eq!(email.header_field("Subject"), None);
+    assert_eq!(email.raw_header_lines().count(), 0);
+    assert!(email.validate().is_empty());
+    assert_eq!(email.body_text(), "");
+}
+
+#[test]
+fn single_newline_input_has_empty_header() {
+    let email = Email::from_vec(b"\n".to_vec()).unwrap();
+
+    assert!(email.header().is_empty());
+    assert_eq!(email.body(), b"\n");
+    assert_eq!(email.data(), b"\n");
+    assert_eq!(email.raw_header_lines().count(), 0);
+    assert!(email.validate().is_empty());
+}
+
+#[test]
+fn input_starting_with_empty_line_is_all_body() {
+    let email = Email::from_vec(b"\r\nSubject: Not a field\r\n".to_vec()).unwrap();
+
+    assert!(email.header().is_empty());
+    assert_eq!(email.body(), b"\r\nSubject: Not a field\r\n");
+    assert_eq!(email.header_field("Subject"), None);
+}
+
+#[test]
+fn header_only_copy_has_header_and_no_body() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    let copy = email.header_only_copy();
+
+    assert_eq!(copy.raw_data(), TEST_EMAIL.replace("Body body body\n", "").as_bytes());
+    assert_eq!(copy.header(), email.header());
+    assert_eq!(copy.header_field("Subject"), Some(" ΑΒΓ"));
+    assert!(copy.body().iter().all(|c| c.is_ascii_whitespace()));
+    assert_eq!(copy.body_text(), "");
+}
+
+#[test]
+fn header_only_copy_adds_missing_separator() {
+    let email = Email::from_vec(b"Subject: No body\r\nTo: me@example.com".to_vec()).unwrap();
+
+    let copy = email.header_only_copy();
+
+    assert_eq!(copy.raw_data(), b"Subject: No body\r\nTo: me@example.com\n\n");
+    assert_eq!(copy.header_field("To"), Some(" me@example.com"));
+}
+
+static TEST_EMAIL_CONTENT_LENGTH: &str = "Subject: Length delimited
+Content-Length: 10
+
+Body body
+Subject: Next email
+
+Next body
+";
+
+#[test]
+fn content_length_is_ignored_by_default() {
+    let email = Email::from_vec(TEST_EMAIL_CONTENT_LENGTH.to_string().into_bytes()).unwrap();
+
+    assert!(String::from_utf8_lossy(email.body()).contains("Next body"));
+}
+
+#[test]
+fn content_length_delimits_body_if_enabled() {
+    let options = NormalizationOptions{
+        use_content_length: true,
+        ..Default::default()
+    };
+    let email = Email::from_vec_with_options(
+        TEST_EMAIL_CONTENT_LENGTH.to_string().into_bytes(),
+        options
+    ).unwrap();
+
+    assert_eq!(email.body(), b"\n\nBody body\n");
+    assert_eq!(email.body_text(), "Body body\n");
+    assert_eq!(email.raw_data(), TEST_EMAIL_CONTENT_LENGTH.as_bytes());
+}
+
+static TEST_EMAIL_ENCODED_PARTS: &str = "To: me@example.com
+Subject: =?utf-8?b?zpHOks6T?=
+ folded
+MIME-Version: 1.0
+Content-Type: multipart/mixed; boundary=\"b\"
+
+--b
+Content-Type: text/plain; charset=iso-8859-7
+Content-Transfer-Encoding: base64
+Content-Description: =?utf-8?b?zpHOks6T?=
+ folded
+
+wcLD
+--b
+Content-Type: text/plain; charset=iso-8859-1
+Content-Transfer-Encoding: quoted-printable
+
+caf=E9
+--b--
+";
+
+#[test]
+fn body_passthrough_keeps_raw_body() {
+    let options = NormalizationOptions{
+        body_passthrough: true,
+        ..Default::default()
+    };
+    let email = Email::from_vec_with_options(
+        TEST_EMAIL_ENCODED_PARTS.to_string().into_bytes(), options).unwrap();
+
+    // The body starts with the line terminator of the last header field
+    // and the empty separator line.
+    assert_eq!(&email.body()[2..], email.raw_body());
+    assert_eq!(email.header_field("Subject"), Some(" ΑΒΓ folded"));
+    assert_eq!(email.header_field("Content-Description"), Some(" ΑΒΓ folded"));
+    assert!(!email.body_text().contains("ΑΒΓ"));
+}
+
+#[test]
+fn body_is_decoded_without_body_passthrough() {
+    let email = Email::from_vec(TEST_EMAIL_ENCODED_PARTS.to_string().into_bytes()).unwrap();
+
+    assert_ne!(&email.body()[2..], email.raw_body());
+    assert!(email.body_text().contains("ΑΒΓ"));
+    assert!(email.body_text().contains("café"));
+}
+
+#[test]
+fn emails_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    assert_send_sync::<Email>();
+    assert_send_sync::<BorrowedEmail>();
+}
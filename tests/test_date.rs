@@ -0,0 +1,50 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+
+fn email_with_date(date: &str) -> Email {
+    Email::from_vec(format!("Date: {}\nTo: me@example.com\n\nBody\n", date).into_bytes()).unwrap()
+}
+
+#[test]
+fn numeric_date_offset_is_parsed() {
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 10:00:00 +0230").date_offset(), Some(150));
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 10:00:00 -0500 (EST)").date_offset(), Some(-300));
+}
+
+#[test]
+fn named_date_offset_is_parsed() {
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 10:00:00 GMT").date_offset(), Some(0));
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 10:00:00 pdt").date_offset(), Some(-420));
+}
+
+#[test]
+fn missing_or_invalid_date_offset_is_none() {
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 10:00:00").date_offset(), None);
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 10:00:00 +02").date_offset(), None);
+    let email = Email::from_vec(b"To: me@example.com\n\nBody\n".to_vec()).unwrap();
+    assert_eq!(email.date_offset(), None);
+}
+
+#[test]
+fn date_is_parsed_to_unix_time() {
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 10:00:00 +0000").date(), Some(1546855200));
+    assert_eq!(email_with_date("Mon, 07 Jan 2019 12:30:00 +0230").date(), Some(1546855200));
+    assert_eq!(email_with_date("Mon, 7 Jan 2019 05:00:00 EST").date(), Some(1546855200));
+    assert_eq!(email_with_date("7 Jan 19 10:00 UT").date(), Some(1546855200));
+    assert_eq!(email_with_date("Thu, 29 Feb 2024 23:59:59 GMT (Leap day)").date(), Some(1709251199));
+}
+
+#[test]
+fn missing_or_invalid_date_is_none() {
+    assert_eq!(email_with_date("Mon, 7 Foo 2019 10:00:00 +0000").date(), None);
+    assert_eq!(email_with_date("yesterday").date(), None);
+    let email = Email::from_vec(b"To: me@example.com\n\nBody\n".to_vec()).unwrap();
+    assert_eq!(email.date(), None);
+}
@@ -0,0 +1,44 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Email, MdaError, NormalizationOptions};
+use std::io;
+use std::time::{Duration, Instant};
+
+fn many_parts_email(parts: usize) -> Vec<u8> {
+    let mut data = String::from("Content-Type: multipart/mixed; boundary=\"b\"\n\n");
+    for i in 0..parts {
+        data += &format!("--b\nContent-Type: text/plain\n\nPart {}\n", i);
+    }
+    data += "--b--\n";
+    data.into_bytes()
+}
+
+#[test]
+fn exceeded_deadline_fails_with_timeout() {
+    let options = NormalizationOptions{
+        deadline: Some(Instant::now() - Duration::from_secs(1)),
+        ..Default::default()
+    };
+
+    let err = Email::from_vec_with_options(many_parts_email(1000), options).err().unwrap();
+
+    assert!(matches!(err, MdaError::Io(ref err) if err.kind() == io::ErrorKind::TimedOut));
+}
+
+#[test]
+fn normalization_within_deadline_succeeds() {
+    let options = NormalizationOptions{
+        deadline: Some(Instant::now() + Duration::from_secs(3600)),
+        ..Default::default()
+    };
+
+    let email = Email::from_vec_with_options(many_parts_email(1000), options).unwrap();
+
+    assert_eq!(email.body_text().lines().count(), 1000);
+}
@@ -6,10 +6,14 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use mda::Email;
-use tempfile;
+use mda::{
+    sanitize_maildir_component, DeliveryDurability, Email, EmailFilenameGenerator, Maildir, MaildirFlag,
+    NormalizationOptions,
+};
 use std::fs;
 use std::os::unix::fs as unix_fs;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
 #[test]
 fn creates_maildir_dir_structure() {
@@ -84,3 +88,444 @@ fn deals_with_soft_link_path() {
     let email = Email::from_vec(Vec::new()).unwrap();
     email.deliver_to_maildir(&symlink).unwrap();
 }
+
+#[test]
+fn delivers_data_directly_with_maildir() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    let inbox = Maildir::open_or_create(&tmpdir.path().join("inbox"), gen.clone()).unwrap();
+    let archive = Maildir::open_or_create(&tmpdir.path().join("archive"), gen).unwrap();
+    let data = [1, 3, 5, 7, 11];
+
+    let path = inbox.deliver(&data, DeliveryDurability::FileSyncOnly).unwrap();
+    let linked = archive.deliver_with_hard_link(&path, DeliveryDurability::FileSyncOnly).unwrap();
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("inbox/new"));
+    assert_eq!(linked.parent().unwrap(), tmpdir.path().join("archive/new"));
+    assert_eq!(fs::read(&path).unwrap(), &data);
+    assert_eq!(fs::read(&linked).unwrap(), &data);
+}
+
+#[test]
+fn cleans_stale_tmp_files() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    let maildir = Maildir::open_or_create(tmpdir.path(), gen).unwrap();
+
+    let stale = tmpdir.path().join("tmp/stale");
+    let fresh = tmpdir.path().join("tmp/fresh");
+    let two_days_ago = SystemTime::now() - Duration::from_secs(48 * 60 * 60);
+    fs::File::create(&stale).unwrap().set_modified(two_days_ago).unwrap();
+    fs::File::create(&fresh).unwrap();
+
+    let removed = maildir.clean_tmp(Duration::from_secs(36 * 60 * 60)).unwrap();
+
+    assert_eq!(removed, 1);
+    assert!(!stale.exists());
+    assert!(fresh.exists());
+}
+
+#[test]
+fn cleans_once_markers_of_removed_emails() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    let maildir = Maildir::open_or_create(tmpdir.path(), gen).unwrap();
+
+    let kept = maildir.deliver_once(b"data", "<kept@example.com>", DeliveryDurability::FileSyncOnly).unwrap();
+    let removed = maildir.deliver_once(b"data", "<removed@example.com>", DeliveryDurability::FileSyncOnly).unwrap();
+    fs::remove_file(removed.unwrap()).unwrap();
+
+    assert_eq!(maildir.clean_tmp(Duration::from_secs(36 * 60 * 60)).unwrap(), 1);
+    assert!(tmpdir.path().join("mda-once/<kept@example.com>.once").exists());
+    assert!(!tmpdir.path().join("mda-once/<removed@example.com>.once").exists());
+    assert!(kept.unwrap().exists());
+}
+
+#[test]
+fn moves_message_preserving_flags() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let src_root = tmpdir.path().join("src");
+    let dest_root = tmpdir.path().join("dest");
+    let data = [1, 3, 5, 7, 11];
+
+    fs::create_dir_all(src_root.join("cur")).unwrap();
+    let src = src_root.join("cur/1546300800.1234_0.host:2,FS");
+    fs::write(&src, data).unwrap();
+
+    let moved = Maildir::move_message(&src, &dest_root).unwrap();
+
+    assert_eq!(moved, dest_root.join("cur/1546300800.1234_0.host:2,FS"));
+    assert_eq!(fs::read(&moved).unwrap(), &data);
+    assert!(!src.exists());
+}
+
+#[test]
+fn moves_message_without_info_to_cur() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let dest_root = tmpdir.path().join("dest");
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let src = email.deliver_to_maildir(tmpdir.path().join("src")).unwrap();
+
+    let moved = Maildir::move_message(&src, &dest_root).unwrap();
+
+    assert_eq!(moved.parent().unwrap(), dest_root.join("cur"));
+    assert!(moved.to_str().unwrap().ends_with(":2,"));
+    assert!(!src.exists());
+}
+
+#[test]
+fn moves_message_with_new_name_on_collision() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let dest_root = tmpdir.path().join("dest");
+
+    fs::create_dir_all(tmpdir.path().join("src/cur")).unwrap();
+    fs::create_dir_all(dest_root.join("cur")).unwrap();
+    let src = tmpdir.path().join("src/cur/1546300800.1234_0.host:2,S");
+    let existing = dest_root.join("cur/1546300800.1234_0.host:2,S");
+    fs::write(&src, [1]).unwrap();
+    fs::write(&existing, [2]).unwrap();
+
+    let moved = Maildir::move_message(&src, &dest_root).unwrap();
+
+    assert_ne!(moved, existing);
+    assert!(moved.to_str().unwrap().ends_with(":2,S"));
+    assert_eq!(fs::read(&moved).unwrap(), &[1]);
+    assert_eq!(fs::read(&existing).unwrap(), &[2]);
+}
+
+#[test]
+fn delivers_with_sorted_flags_to_cur() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let flags = [MaildirFlag::Seen, MaildirFlag::Flagged, MaildirFlag::Seen, MaildirFlag::Draft];
+    let path = email.deliver_to_maildir_with_flags(tmpdir.path(), &flags).unwrap();
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("cur"));
+    assert!(path.to_str().unwrap().ends_with(":2,DFS"));
+    assert_eq!(fs::read(&path).unwrap(), [1, 2, 3]);
+    assert_eq!(fs::read_dir(tmpdir.path().join("new")).unwrap().count(), 0);
+}
+
+#[test]
+fn delivers_without_flags_to_new() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let path = email.deliver_to_maildir_with_flags(tmpdir.path(), &[]).unwrap();
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("new"));
+    assert!(!path.to_str().unwrap().contains(":2,"));
+}
+
+#[test]
+fn delivers_with_dovecot_keywords() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(b"Subject: Hi\n\nBody\n".to_vec()).unwrap();
+    let path = email.deliver_to_maildir_with_keywords(tmpdir.path(), &["work", "$Label1"]).unwrap();
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("cur"));
+    assert!(path.to_str().unwrap().ends_with(":2,ab"));
+    assert_eq!(fs::read(&path).unwrap(), b"Subject: Hi\n\nBody\n");
+    assert_eq!(
+        fs::read_to_string(tmpdir.path().join("dovecot-keywords")).unwrap(),
+        "0 work\n1 $Label1\n"
+    );
+    assert!(!tmpdir.path().join("dovecot-keywords.lock").exists());
+}
+
+#[test]
+fn reuses_registered_dovecot_keywords() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    fs::write(tmpdir.path().join("dovecot-keywords"), "0 junk\n2 work\n").unwrap();
+
+    let email = Email::from_vec(Vec::new()).unwrap();
+    let path = email.deliver_to_maildir_with_keywords(tmpdir.path(), &["work", "new"]).unwrap();
+
+    assert!(path.to_str().unwrap().ends_with(":2,bc"));
+    assert_eq!(
+        fs::read_to_string(tmpdir.path().join("dovecot-keywords")).unwrap(),
+        "0 junk\n1 new\n2 work\n"
+    );
+}
+
+#[test]
+fn rejects_invalid_dovecot_keywords() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(Vec::new()).unwrap();
+
+    assert!(email.deliver_to_maildir_with_keywords(tmpdir.path(), &["two words"]).is_err());
+    assert!(email.deliver_to_maildir_with_keywords(tmpdir.path(), &[""]).is_err());
+}
+
+#[test]
+fn detailed_delivery_reports_syncs() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let first = email.deliver_to_maildir_detailed(tmpdir.path().join("first")).unwrap();
+    let second = email.deliver_to_maildir_detailed(tmpdir.path().join("second")).unwrap();
+
+    assert_eq!(first.path.parent().unwrap(), tmpdir.path().join("first/new"));
+    assert_eq!(first.size, 3);
+    assert_eq!(second.size, 3);
+    assert_eq!(first.durability, DeliveryDurability::FileAndDirSync);
+    assert!(first.file_synced);
+    assert!(first.dirs_synced);
+    assert_eq!(fs::read(&second.path).unwrap(), [1, 2, 3]);
+    assert!(!second.file_synced);
+    assert!(second.dirs_synced);
+}
+
+#[test]
+fn detailed_delivery_reports_durability_used() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let mut email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    email.set_delivery_durability(DeliveryDurability::FileSyncOnly);
+    let result = email.deliver_to_maildir_detailed(tmpdir.path()).unwrap();
+
+    assert_eq!(result.durability, DeliveryDurability::FileSyncOnly);
+    assert!(result.file_synced);
+    assert!(!result.dirs_synced);
+}
+
+#[test]
+fn delivers_once_per_identity() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let path = email.deliver_to_maildir_once(tmpdir.path(), "<1234/5678@example.com>").unwrap().unwrap();
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("new"));
+    assert_eq!(fs::read(&path).unwrap(), [1, 2, 3]);
+    assert_eq!(email.deliver_to_maildir_once(tmpdir.path(), "<1234/5678@example.com>").unwrap(), None);
+    assert!(email.deliver_to_maildir_once(tmpdir.path(), "<other@example.com>").unwrap().is_some());
+    assert_eq!(fs::read_dir(tmpdir.path().join("new")).unwrap().count(), 2);
+    assert_eq!(fs::read_dir(tmpdir.path().join("tmp")).unwrap().count(), 0);
+}
+
+#[test]
+fn delivers_once_after_move_to_cur() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let path = email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap().unwrap();
+    let cur = tmpdir.path().join("cur").join(format!("{}:2,S", path.file_name().unwrap().to_str().unwrap()));
+    fs::rename(&path, &cur).unwrap();
+
+    assert_eq!(email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap(), None);
+}
+
+#[test]
+fn delivers_once_if_marker_has_no_email() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    fs::create_dir_all(tmpdir.path().join("mda-once")).unwrap();
+    fs::write(tmpdir.path().join("mda-once/<1234@example.com>.once"), b"").unwrap();
+
+    let path = email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap().unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), [1, 2, 3]);
+    assert_eq!(email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap(), None);
+}
+
+#[test]
+fn delivers_once_if_email_has_no_marker() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let path = email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap().unwrap();
+    fs::remove_file(tmpdir.path().join("mda-once/<1234@example.com>.once")).unwrap();
+    let cur = tmpdir.path().join("cur").join(format!("{}:2,S", path.file_name().unwrap().to_str().unwrap()));
+    fs::rename(&path, &cur).unwrap();
+
+    assert_eq!(email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap(), None);
+}
+
+#[test]
+fn delivers_once_again_after_removal() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let path = email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(email.deliver_to_maildir_once(tmpdir.path(), "<1234@example.com>").unwrap(), Some(path));
+}
+
+#[test]
+fn delivers_once_with_long_identity() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let identity1 = format!("<{}1@example.com>", "/".repeat(500));
+    let identity2 = format!("<{}2@example.com>", "/".repeat(500));
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+
+    assert!(email.deliver_to_maildir_once(tmpdir.path(), &identity1).unwrap().is_some());
+    assert!(email.deliver_to_maildir_once(tmpdir.path(), &identity2).unwrap().is_some());
+    assert_eq!(email.deliver_to_maildir_once(tmpdir.path(), &identity1).unwrap(), None);
+}
+
+#[test]
+fn delivers_with_named_filename() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let path = email.deliver_to_maildir_named(tmpdir.path(), "ticket/12:34").unwrap();
+
+    assert_eq!(path, tmpdir.path().join("new").join(r"ticket\05712\07234"));
+    assert_eq!(fs::read(&path).unwrap(), [1, 2, 3]);
+    assert_eq!(fs::read_dir(tmpdir.path().join("tmp")).unwrap().count(), 0);
+}
+
+#[test]
+fn named_delivery_fails_if_name_exists() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let path = email.deliver_to_maildir_named(tmpdir.path(), "ticket-1234").unwrap();
+    let cur = tmpdir.path().join("cur").join("ticket-1234:2,S");
+    fs::rename(&path, &cur).unwrap();
+
+    assert!(email.deliver_to_maildir_named(tmpdir.path(), "ticket-1234").is_err());
+    assert_eq!(fs::read_dir(tmpdir.path().join("new")).unwrap().count(), 0);
+    assert_eq!(fs::read_dir(tmpdir.path().join("tmp")).unwrap().count(), 0);
+}
+
+#[test]
+fn named_delivery_fails_for_invalid_name() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+
+    assert!(email.deliver_to_maildir_named(tmpdir.path(), "").is_err());
+    assert!(email.deliver_to_maildir_named(tmpdir.path(), "..").is_err());
+}
+
+#[test]
+fn sanitized_maildir_component_is_single_safe_component() {
+    assert_eq!(sanitize_maildir_component("work.lists"), "work.lists");
+    assert_eq!(sanitize_maildir_component(".."), r"\056.");
+    assert_eq!(sanitize_maildir_component(".hidden"), r"\056hidden");
+    assert_eq!(sanitize_maildir_component("a/../b"), r"a\057..\057b");
+    assert_eq!(sanitize_maildir_component("a\\b\n"), r"a\134b\012");
+    assert_eq!(sanitize_maildir_component(""), "_");
+}
+
+#[test]
+fn sanitized_maildir_component_stays_within_parent() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let email = Email::from_vec(vec![1, 2, 3]).unwrap();
+    let folder = tmpdir.path().join(sanitize_maildir_component("../escaped"));
+    email.deliver_to_maildir(&folder).unwrap();
+
+    assert_eq!(folder.parent().unwrap(), tmpdir.path());
+    assert_eq!(fs::read_dir(folder.join("new")).unwrap().count(), 1);
+}
+
+#[test]
+fn delivers_with_writer() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    let maildir = Maildir::open_or_create(tmpdir.path(), gen).unwrap();
+
+    let path = maildir.deliver_with_writer(
+        |writer| {
+            writer.write_all(b"Subject: Hi\n")?;
+            writer.write_all(b"\nBody\n")?;
+            Ok(())
+        },
+        DeliveryDurability::FileSyncOnly
+    ).unwrap();
+
+    assert_eq!(path.parent().unwrap(), tmpdir.path().join("new"));
+    assert_eq!(fs::read(&path).unwrap(), b"Subject: Hi\n\nBody\n");
+    assert_eq!(fs::read_dir(tmpdir.path().join("tmp")).unwrap().count(), 0);
+}
+
+#[test]
+fn failed_writer_delivery_leaves_no_files() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    let maildir = Maildir::open_or_create(tmpdir.path(), gen).unwrap();
+
+    let result = maildir.deliver_with_writer(
+        |writer| {
+            writer.write_all(b"Subject: Hi\n")?;
+            Err("source failed".into())
+        },
+        DeliveryDurability::FileSyncOnly
+    );
+
+    assert_eq!(result.unwrap_err().to_string(), "source failed");
+    for dir in &["tmp", "new", "cur"] {
+        assert_eq!(fs::read_dir(tmpdir.path().join(dir)).unwrap().count(), 0);
+    }
+}
+
+#[test]
+fn delivers_normalized_data_while_normalizing() {
+    let emails = [
+        "Subject: =?utf-8?q?Caf=C3=A9?=\nTo: a@example.com,\n b@example.com\n\nPlain body\n",
+        "Subject: Parts\n\
+         MIME-Version: 1.0\n\
+         Content-Type: multipart/mixed; boundary=\"abc\"\n\
+         \n\
+         Preamble\n\
+         --abc\n\
+         Content-Type: text/plain; charset=iso-8859-1\n\
+         Content-Transfer-Encoding: quoted-printable\n\
+         \n\
+         Caf=E9\n\
+         --abc\n\
+         Content-Type: application/octet-stream\n\
+         Content-Transfer-Encoding: base64\n\
+         \n\
+         AQIDBA==\n\
+         --abc--\n",
+    ];
+
+    for data in &emails {
+        for rewrite_encoding_fields in &[false, true] {
+            let tmpdir = tempfile::tempdir().unwrap();
+            let options = || NormalizationOptions{
+                rewrite_encoding_fields: *rewrite_encoding_fields,
+                ..Default::default()
+            };
+
+            let (email, path) = Email::from_vec_delivering_normalized(
+                data.as_bytes().to_vec(),
+                options(),
+                tmpdir.path()
+            ).unwrap();
+
+            let expected = Email::from_vec_with_options(data.as_bytes().to_vec(), options()).unwrap();
+            assert_eq!(path.parent().unwrap(), tmpdir.path().join("new"));
+            assert_eq!(fs::read(&path).unwrap(), expected.data());
+            assert_eq!(email.header_field("Subject"), expected.header_field("Subject"));
+            assert_eq!(email.raw_data(), data.as_bytes());
+            assert!(email.data().is_empty());
+            assert!(email.parts().is_empty());
+        }
+    }
+}
+
+#[test]
+fn permanent_delivery_errors_are_not_retried() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    let mut maildir = Maildir::open_or_create(tmpdir.path(), gen).unwrap();
+    maildir.set_delivery_retries(5, Duration::from_secs(10));
+    fs::remove_dir(tmpdir.path().join("new")).unwrap();
+
+    let start = std::time::Instant::now();
+    assert!(maildir.deliver(b"data", DeliveryDurability::FileSyncOnly).is_err());
+
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert_eq!(fs::read_dir(tmpdir.path().join("tmp")).unwrap().count(), 0);
+}
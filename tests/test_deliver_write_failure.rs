@@ -0,0 +1,38 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+// The file size limit used to simulate write failures applies to the whole
+// process, so this is kept in its own test binary with a single test.
+
+use mda::Email;
+use std::fs;
+
+fn limit_file_size(size: u64) {
+    unsafe {
+        // Get EFBIG errors instead of being killed by SIGXFSZ.
+        libc::signal(libc::SIGXFSZ, libc::SIG_IGN);
+        let limit = libc::rlimit{rlim_cur: size, rlim_max: libc::RLIM_INFINITY};
+        assert_eq!(libc::setrlimit(libc::RLIMIT_FSIZE, &limit), 0);
+    }
+}
+
+#[test]
+fn failed_write_leaves_no_tmp_file() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(vec![b'a'; 64 * 1024]).unwrap();
+
+    limit_file_size(4096);
+
+    assert!(email.deliver_to_maildir(tmpdir.path()).is_err());
+
+    let tmp_entries: Vec<_> = fs::read_dir(tmpdir.path().join("tmp")).unwrap().collect();
+    let new_entries: Vec<_> = fs::read_dir(tmpdir.path().join("new")).unwrap().collect();
+
+    assert_eq!(tmp_entries.len(), 0);
+    assert_eq!(new_entries.len(), 0);
+}
@@ -0,0 +1,77 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Canon, Email, HashAlgo};
+
+static TEST_EMAIL: &str = "Subject: DKIM\r\nTo: me@example.com\r\n\r\nHello  world \r\n\tIndented\r\n\r\n\r\n";
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn simple_body_hash() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(
+        hex(&email.dkim_body_hash(Canon::Simple, None, HashAlgo::Sha256)),
+        "1618b0c6734d6017892711c9a36071a3c691ae63d1d9eab083d2eddc8d38aacd"
+    );
+    assert_eq!(
+        hex(&email.dkim_body_hash(Canon::Simple, None, HashAlgo::Sha1)),
+        "eb6fba16bf4bc59ae7906fd8a2a7a752955c1abe"
+    );
+}
+
+#[test]
+fn relaxed_body_hash() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(
+        hex(&email.dkim_body_hash(Canon::Relaxed, None, HashAlgo::Sha256)),
+        "64b7cef38a5b4cb61375fa45feec50c70dba5875ce9381f49707a51bffcb3707"
+    );
+}
+
+#[test]
+fn body_hash_honors_length_limit() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(
+        hex(&email.dkim_body_hash(Canon::Relaxed, Some(8), HashAlgo::Sha256)),
+        "00bf846eaf9c17aa019bdc9e5643a1775a270f60dd7eabf944d4e94b75c48989"
+    );
+}
+
+#[test]
+fn body_hash_treats_lf_as_crlf() {
+    let crlf_email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let lf_email = Email::from_vec(TEST_EMAIL.replace("\r\n", "\n").into_bytes()).unwrap();
+
+    for canon in [Canon::Simple, Canon::Relaxed] {
+        assert_eq!(
+            lf_email.dkim_body_hash(canon, None, HashAlgo::Sha256),
+            crlf_email.dkim_body_hash(canon, None, HashAlgo::Sha256)
+        );
+    }
+}
+
+#[test]
+fn empty_body_hash() {
+    let email = Email::from_vec(b"Subject: Empty\r\n\r\n".to_vec()).unwrap();
+
+    // The well-known hashes of an empty body.
+    assert_eq!(
+        hex(&email.dkim_body_hash(Canon::Simple, None, HashAlgo::Sha256)),
+        "7eb70257593da06f682a3ddda54a9d260d4fc514f645237f5ca74b08f8da61a6"
+    );
+    assert_eq!(
+        hex(&email.dkim_body_hash(Canon::Relaxed, None, HashAlgo::Sha256)),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+}
@@ -0,0 +1,161 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Email, EmailRegex, NormalizationOptions};
+use std::cell::Cell;
+use std::fs;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+X-Spam-Score: 1.0
+To: Destination <someone.else@destination.com>
+X-Spam-Score: 2.0
+ (folded)
+Content-Type: text/plain
+
+Body
+";
+
+fn test_email() -> Email {
+    Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap()
+}
+
+#[test]
+fn set_header_field_replaces_all_occurrences() {
+    let mut email = test_email();
+
+    email.set_header_field("x-spam-score", "7.5").unwrap();
+
+    assert_eq!(email.header_field_all_occurrences("X-Spam-Score").unwrap(), &vec![" 7.5".to_string()]);
+    assert_eq!(
+        email.raw_data(),
+        "Return-Path: <me@source.com>\n\
+         x-spam-score: 7.5\n\
+         To: Destination <someone.else@destination.com>\n\
+         Content-Type: text/plain\n\
+         \n\
+         Body\n".as_bytes()
+    );
+    assert!(email.header().search(r"^x-spam-score: 7\.5$").unwrap());
+    assert_eq!(email.body(), b"\n\nBody\n");
+}
+
+#[test]
+fn set_header_field_adds_missing_field() {
+    let mut email = test_email();
+
+    email.set_header_field("X-Bogosity", "Ham").unwrap();
+
+    assert_eq!(email.header_field("X-Bogosity"), Some(" Ham"));
+    assert!(email.raw_data().ends_with(b"Content-Type: text/plain\nX-Bogosity: Ham\n\nBody\n"));
+}
+
+#[test]
+fn add_header_field_keeps_existing_occurrences() {
+    let mut email = test_email();
+
+    email.add_header_field("X-Spam-Score", "3.0").unwrap();
+
+    assert_eq!(email.header_field_all_occurrences("X-Spam-Score").unwrap().len(), 3);
+    assert!(email.raw_data().ends_with(b"Content-Type: text/plain\nX-Spam-Score: 3.0\n\nBody\n"));
+    assert_eq!(email.body(), b"\n\nBody\n");
+}
+
+#[test]
+fn added_fields_use_crlf_in_crlf_emails() {
+    let mut email = Email::from_vec(b"Subject: Hi\r\n\r\nBody\r\n".to_vec()).unwrap();
+
+    email.add_header_field("X-Tag", "a").unwrap();
+
+    assert_eq!(email.raw_data(), b"Subject: Hi\r\nX-Tag: a\r\n\r\nBody\r\n");
+}
+
+#[test]
+fn fields_are_added_to_emails_without_header_or_body() {
+    let mut email = Email::from_vec(b"\nBody\n".to_vec()).unwrap();
+    email.add_header_field("X-Tag", "a").unwrap();
+    assert_eq!(email.raw_data(), b"X-Tag: a\n\nBody\n");
+    assert_eq!(email.header_field("X-Tag"), Some(" a"));
+
+    let mut email = Email::from_vec(b"Subject: Hi".to_vec()).unwrap();
+    email.add_header_field("X-Tag", "a").unwrap();
+    assert_eq!(email.raw_data(), b"Subject: Hi\nX-Tag: a\n");
+    assert_eq!(email.header_field("X-Tag"), Some(" a"));
+}
+
+#[test]
+fn non_ascii_values_are_encoded_and_long_values_folded() {
+    let mut email = test_email();
+    let value = format!("Καλημέρα {}", "word ".repeat(30));
+    let value = value.trim_end();
+
+    email.set_header_field("X-Note", value).unwrap();
+
+    let raw = std::str::from_utf8(email.raw_data()).unwrap();
+    assert!(raw.is_ascii());
+    assert!(raw.contains("X-Note: =?utf-8?b?"));
+    assert!(raw.lines().all(|line| line.len() <= 78));
+    assert_eq!(email.header_field("X-Note").unwrap().trim(), value);
+}
+
+#[test]
+fn invalid_fields_are_rejected() {
+    let mut email = test_email();
+
+    assert!(email.set_header_field("X-Bad", "a\r\nBcc: victim@example.com").is_err());
+    assert!(email.add_header_field("X Bad", "a").is_err());
+    assert!(email.add_header_field("X:Bad", "a").is_err());
+    assert!(email.add_header_field("", "a").is_err());
+    assert_eq!(email.raw_data(), TEST_EMAIL.as_bytes());
+}
+
+#[test]
+fn editing_uses_original_normalization_options() {
+    let options = NormalizationOptions{fold_whitespace_to_space: true, ..Default::default()};
+    let mut email = Email::from_vec_with_options(
+        b"Subject: a\n\tb\n\nBody\n".to_vec(),
+        options
+    ).unwrap();
+
+    email.add_header_field("X-Tag", "a").unwrap();
+
+    assert_eq!(email.header_field("Subject"), Some(" a b"));
+}
+
+#[test]
+fn editing_ignores_deadline_and_part_transform() {
+    let transforms = Rc::new(Cell::new(0));
+    let transforms_clone = transforms.clone();
+    let options = NormalizationOptions{
+        deadline: Some(Instant::now() + Duration::from_millis(100)),
+        part_transform: Some(Box::new(move |_, _| transforms_clone.set(transforms_clone.get() + 1))),
+        ..Default::default()
+    };
+    let mut email = Email::from_vec_with_options(TEST_EMAIL.to_string().into_bytes(), options).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    email.set_header_field("X-Tag", "a").unwrap();
+
+    assert_eq!(email.header_field("X-Tag"), Some(" a"));
+    assert_eq!(transforms.get(), 1);
+}
+
+#[test]
+fn edited_email_is_delivered_with_new_header() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let mut email = test_email();
+
+    let first = email.deliver_to_maildir(tmpdir.path()).unwrap();
+    email.set_header_field("X-Spam-Score", "7.5").unwrap();
+    let second = email.deliver_to_maildir(tmpdir.path()).unwrap();
+
+    assert_eq!(fs::read(first).unwrap(), TEST_EMAIL.as_bytes());
+    assert_eq!(fs::read(second).unwrap(), email.raw_data());
+}
@@ -8,7 +8,7 @@
 
 use mda::{Email, EmailRegex};
 
-static TEST_EMAIL_MULTIPART: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_MULTIPART: &str = r#"Return-Path: <me@source.com>
 To: =?iso-8859-1?q?=C0a_b=DF?= <someone.else1@destination.com>,
  =?utf-8?b?zqXOps6nzqjOqQo=?= <someone.else2@destination.com>,
 Cc: =?iso-8859-1?q?=C0 b?= <someone.else3@destination.com>
@@ -23,10 +23,10 @@ X-header-field: =?UTF-8?B?zpHOks6TCg==?=
 --XtT01VFrJIenjlg+ZCXSSWq4--
 "#;
 
-static TEST_EMAIL_INVALID_UTF8: &'static str =
+static TEST_EMAIL_INVALID_UTF8: &str =
     r#"Subject: =?utf-8?B?zojOus60zr/Pg863IGUtzrvOv86zzrHPgc65zrHPg868zr/P?="#;
 
-static TEST_EMAIL_MULTI_ENC_WORD: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_MULTI_ENC_WORD: &str = r#"Return-Path: <me@source.com>
 Subject: =?utf-8?b?TXkgbXVsdGkgZW5jb2RlZC0=?=
  =?utf-8?b?d29yZCBzdWJqZWN0IGw=?=
 	  =?utf-8?b?aW5l?=
@@ -76,3 +76,47 @@ fn multpile_encoded_words_are_concatenated() {
     assert!(email.data().search("My multi encoded-word subject line").unwrap());
     assert!(email.header_field("Subject").unwrap().contains("My multi encoded-word subject line"));
 }
+
+#[test]
+fn fields_with_undecodable_encoded_words_are_lossy() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.lossy_fields(), ["bcc"]);
+}
+
+#[test]
+fn fields_with_invalid_charset_encoding_are_lossy() {
+    let email = Email::from_vec(TEST_EMAIL_INVALID_UTF8.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.lossy_fields(), ["subject"]);
+}
+
+#[test]
+fn fields_with_invalid_utf8_are_lossy() {
+    let mut data = b"Subject: abc".to_vec();
+    data.extend(&[0xff, 0xfe, b'\n']);
+    data.extend(b"To: me@example.com\n");
+    let email = Email::from_vec(data).unwrap();
+
+    assert_eq!(email.lossy_fields(), ["subject"]);
+}
+
+#[test]
+fn properly_decoded_fields_are_not_lossy() {
+    let email = Email::from_vec(TEST_EMAIL_MULTI_ENC_WORD.to_string().into_bytes()).unwrap();
+
+    assert!(email.lossy_fields().is_empty());
+}
+
+#[test]
+fn encoded_word_with_invalid_encoding_is_left_literal() {
+    let email = Email::from_vec(
+        b"Subject: =?utf-8?BOGUS?zpHOks6T?= and =?utf-8?b?zpHOks6T?=\n\nBody\n".to_vec()
+    ).unwrap();
+
+    assert_eq!(
+        email.header_field("Subject"),
+        Some(" =?utf-8?BOGUS?zpHOks6T?= and ΑΒΓ")
+    );
+    assert!(email.lossy_fields().is_empty());
+}
@@ -6,9 +6,11 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use mda::{Email, EmailRegex};
+use mda::{normalize_body, Email, EmailRegex, NormalizationOptions, PartContext};
+use std::cell::RefCell;
+use std::rc::Rc;
 
-static TEST_EMAIL_BASE64: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_BASE64: &str = r#"Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Content-Type: text/plain; charset="utf-8"
 Content-Transfer-Encoding: base64
@@ -18,7 +20,7 @@ cHJpc20gYW5kIHNpbGVudCBmYWNlLApUaGUgbWFyYmxlIGluZGV4IG9mIGEgbWluZCBmb3IgZXZl
 cgpWb3lhZ2luZyB0aHJvdWdoIHN0cmFuZ2Ugc2VhcyBvZiBUaG91Z2h0LCBhbG9uZS4gCg==
 "#;
 
-static TEST_EMAIL_MULTIPART: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_MULTIPART: &str = r#"Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Content-type: multipart/alternative; boundary="XtT01VFrJIenjlg+ZCXSSWq4"
 
@@ -60,7 +62,7 @@ IGEgbG9uZWx5IHNwZWNrIGluIHRoZSBncmVhdCBlbnZlbG9waW5nIGNvc21pYyBkYXJrLg==
 --XtT01VFrJIenjlg+ZCXSSWq4--
 "#;
 
-static TEST_EMAIL_INVALID_BASE64: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_INVALID_BASE64: &str = r#"Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Content-Type: text/plain; charset="utf-8"
 Content-Transfer-Encoding: base64
@@ -70,7 +72,7 @@ cHJpc20gYW5kIHNpbGVudCBmYWNlLApUaGUgbWFyYmxlIGluZGV4IG9mIGEgbWluZCBmb3IgZXZl
 cgpWb3lhZ2luZyB0aHJvdWdoIHN0cmFuZ2Ugc2VhcyBvZiBUaG91Z2h0LCBhbG9uZS4gCg====
 "#;
 
-static TEST_EMAIL_QP: &'static str = r#"Return-Path: <me@source.com>
+static TEST_EMAIL_QP: &str = r#"Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Content-Type: text/plain; charset="utf-8"
 Content-Transfer-Encoding: quoted-printable
@@ -142,3 +144,219 @@ fn raw_data_is_not_decoded() {
     assert!(email.raw_data().search(r"vZiBUaG91Z2h0LCBhbG9uZS4gCg==").unwrap());
     assert!(!email.raw_data().search(r"ἤδη θὰ τὸ κατάλαβες ᾑ Ἰθάκες τί σημαίνουν").unwrap());
 }
+
+fn rewrite_encoding_fields_options() -> NormalizationOptions {
+    NormalizationOptions{
+        rewrite_encoding_fields: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn encoding_fields_are_not_rewritten_by_default() {
+    let email = Email::from_vec(TEST_EMAIL_BASE64.to_string().into_bytes()).unwrap();
+
+    assert!(email.header().search(r"^Content-Transfer-Encoding: base64$").unwrap());
+}
+
+#[test]
+fn encoding_fields_of_decoded_parts_are_rewritten() {
+    let email = Email::from_vec_with_options(
+        TEST_EMAIL_MULTIPART.to_string().into_bytes(),
+        rewrite_encoding_fields_options()
+    ).unwrap();
+    let data = String::from_utf8_lossy(email.data());
+
+    // Only the non-text image part is still base64 encoded.
+    assert_eq!(data.matches("Content-Transfer-Encoding: base64").count(), 1);
+    assert_eq!(data.matches("Content-Transfer-Encoding: 8bit").count(), 3);
+    assert_eq!(
+        String::from_utf8_lossy(email.raw_data()).matches("Content-Transfer-Encoding: base64").count(),
+        4
+    );
+}
+
+#[test]
+fn charset_of_decoded_parts_is_rewritten() {
+    let data = "Content-Type: text/plain; charset=\"iso-8859-7\"\n\
+                Content-Transfer-Encoding: base64\n\
+                \n\
+                wcLDCg==\n";
+    let email = Email::from_vec_with_options(
+        data.to_string().into_bytes(),
+        rewrite_encoding_fields_options()
+    ).unwrap();
+
+    assert_eq!(email.header_field("Content-Type"), Some(" text/plain; charset=utf-8"));
+    assert_eq!(email.header_field("Content-Transfer-Encoding"), Some(" 8bit"));
+    assert!(email.body().ends_with("\n\nΑΒΓ\n".as_bytes()));
+    assert_eq!(email.body_text(), "ΑΒΓ\n");
+}
+
+#[test]
+fn charset_of_7bit_parts_is_converted() {
+    let data = b"Content-Type: text/plain; charset=\"iso-8859-7\"\n\
+                 Content-Transfer-Encoding: 7bit\n\
+                 \n\
+                 \xc1\xc2\xc3\n";
+    let email = Email::from_vec(data.to_vec()).unwrap();
+
+    assert_eq!(email.body_text(), "ΑΒΓ\n");
+}
+
+#[test]
+fn rewriting_encoding_fields_preserves_body_text() {
+    let data = TEST_EMAIL_MULTIPART.to_string().into_bytes();
+    let email = Email::from_vec(data.clone()).unwrap();
+    let rewritten = Email::from_vec_with_options(data, rewrite_encoding_fields_options()).unwrap();
+
+    assert_eq!(rewritten.body_text(), email.body_text());
+}
+
+#[test]
+fn encoding_fields_of_invalid_parts_are_not_rewritten() {
+    let data = "Content-Type: text/plain\nContent-Transfer-Encoding: base64\n\n=zpHOks6TCg==\n";
+    let email = Email::from_vec_with_options(
+        data.to_string().into_bytes(),
+        rewrite_encoding_fields_options()
+    ).unwrap();
+
+    assert_eq!(email.header_field("Content-Transfer-Encoding"), Some(" base64"));
+}
+
+#[test]
+fn encoding_fields_of_parts_with_unknown_charset_are_not_rewritten() {
+    let data = "Content-Type: text/plain; charset=x-unknown\n\
+                Content-Transfer-Encoding: base64\n\
+                \n\
+                wcLDCg==\n";
+    let email = Email::from_vec_with_options(
+        data.to_string().into_bytes(),
+        rewrite_encoding_fields_options()
+    ).unwrap();
+
+    assert_eq!(email.header_field("Content-Type"), Some(" text/plain; charset=x-unknown"));
+    assert_eq!(email.header_field("Content-Transfer-Encoding"), Some(" base64"));
+}
+
+#[test]
+fn standalone_text_body_is_normalized() {
+    assert_eq!(
+        normalize_body(b"=CE=91=CE=92=\n=CE=93\n", "text/plain", Some("quoted-printable"), Some("utf-8")),
+        "ΑΒΓ\n".as_bytes()
+    );
+    assert_eq!(
+        normalize_body(b"\xc1\xc2\xc3\n", "Text/Plain", Some("8bit"), Some("ISO-8859-7")),
+        "ΑΒΓ\n".as_bytes()
+    );
+}
+
+#[test]
+fn standalone_non_text_body_is_not_normalized() {
+    let body = b"iVBORw0KGgo=\n";
+
+    assert_eq!(normalize_body(body, "image/png", Some("base64"), None), body);
+}
+
+#[test]
+fn part_transform_modifies_decoded_parts() {
+    let options = NormalizationOptions{
+        part_transform: Some(Box::new(|context: &PartContext, data: &mut Vec<u8>| {
+            if context.content_type == Some("text/plain") {
+                *data = String::from_utf8_lossy(data).replace("Newton", "[REDACTED]").into_bytes();
+            }
+        })),
+        ..Default::default()
+    };
+    let email = Email::from_vec_with_options(
+        TEST_EMAIL_MULTIPART.to_string().into_bytes(),
+        options
+    ).unwrap();
+
+    assert!(email.body().search(r"^Of \[REDACTED\] with his prism").unwrap());
+    assert!(!email.body().search(r"Newton").unwrap());
+    assert!(email.body().search(r"^SSBhbSBzb3JyeSBEYXZl").unwrap());
+}
+
+#[test]
+fn part_transform_receives_part_context() {
+    let contexts = Rc::new(RefCell::new(Vec::new()));
+    let contexts_clone = contexts.clone();
+    let options = NormalizationOptions{
+        part_transform: Some(Box::new(move |context: &PartContext, _: &mut Vec<u8>| {
+            contexts_clone.borrow_mut().push(
+                (context.content_type.map(String::from), context.charset.map(String::from)));
+        })),
+        ..Default::default()
+    };
+    Email::from_vec_with_options(TEST_EMAIL_BASE64.to_string().into_bytes(), options).unwrap();
+
+    assert_eq!(
+        *contexts.borrow(),
+        [(Some("text/plain".to_string()), Some("utf-8".to_string()))]
+    );
+}
+
+static TEST_EMAIL_BASE64_TRAILING_BLANK_LINES: &str = "Subject: Padded
+MIME-Version: 1.0
+Content-Type: text/plain; charset=utf-8
+Content-Transfer-Encoding: base64
+
+SGVsbG8KCgogCg==
+";
+
+fn trimming_options() -> NormalizationOptions {
+    NormalizationOptions{
+        trim_trailing_blank_lines: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn trailing_blank_lines_are_kept_by_default() {
+    let email = Email::from_vec(TEST_EMAIL_BASE64_TRAILING_BLANK_LINES.to_string().into_bytes()).unwrap();
+
+    assert!(email.body().ends_with(b"Hello\n\n\n \n"));
+}
+
+#[test]
+fn trailing_blank_lines_are_trimmed() {
+    let email = Email::from_vec_with_options(
+        TEST_EMAIL_BASE64_TRAILING_BLANK_LINES.to_string().into_bytes(),
+        trimming_options()
+    ).unwrap();
+
+    assert!(email.body().ends_with(b"\nHello\n"));
+    assert_eq!(email.body_text(), "Hello\n");
+    assert_eq!(email.raw_data(), TEST_EMAIL_BASE64_TRAILING_BLANK_LINES.as_bytes());
+}
+
+#[test]
+fn trailing_blank_lines_are_trimmed_in_text_parts() {
+    let data = "Subject: Parts\r\n\
+                MIME-Version: 1.0\r\n\
+                Content-Type: multipart/mixed; boundary=\"abc\"\r\n\
+                \r\n\
+                --abc\r\n\
+                Content-Transfer-Encoding: base64\r\n\
+                \r\n\
+                UGFydA0KDQoNCg==\r\n\
+                --abc\r\n\
+                Content-Type: text/plain\r\n\
+                \r\n\
+                \r\n\
+                \r\n\
+                --abc\r\n\
+                Content-Type: application/octet-stream\r\n\
+                \r\n\
+                data\r\n\
+                \r\n\
+                --abc--\r\n";
+
+    let email = Email::from_vec_with_options(data.as_bytes().to_vec(), trimming_options()).unwrap();
+
+    let body = String::from_utf8(email.body().to_vec()).unwrap();
+    assert!(body.contains("\r\nPart\r\n--abc\r\n"));
+    assert!(body.contains("text/plain\r\n\r\n\r\n--abc\r\n"));
+    assert!(body.contains("\r\ndata\r\n\r\n--abc--\r\n"));
+}
@@ -0,0 +1,47 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Email, EmailRegex, MdaError};
+use std::error::Error;
+use std::io;
+
+#[test]
+fn invalid_regex_fails_with_regex_error() {
+    let email = Email::from_vec(b"Subject: Hi\n\nBody\n".to_vec()).unwrap();
+
+    let err = email.header().search("(unclosed").unwrap_err();
+
+    assert!(matches!(err, MdaError::Regex(_)));
+    assert!(err.source().is_some());
+}
+
+#[test]
+fn missing_file_fails_with_io_error() {
+    let tmpdir = tempfile::tempdir().unwrap();
+
+    let err = Email::from_file(tmpdir.path().join("missing")).err().unwrap();
+
+    assert!(matches!(err, MdaError::Io(ref err) if err.kind() == io::ErrorKind::NotFound));
+}
+
+#[test]
+fn invalid_input_fails_with_parse_error() {
+    let mut email = Email::from_vec(b"Subject: Hi\n\nBody\n".to_vec()).unwrap();
+
+    let err = email.set_header_field("Bad Name", "value").unwrap_err();
+
+    assert!(matches!(err, MdaError::Parse(_)));
+    assert_eq!(err.to_string(), "Invalid header field name: \"Bad Name\"");
+}
+
+#[test]
+fn errors_convert_to_boxed_errors() {
+    let boxed: Box<dyn Error + Send + Sync> = MdaError::Decode("Invalid base64 padding".into()).into();
+
+    assert_eq!(boxed.to_string(), "Invalid base64 padding");
+}
@@ -6,9 +6,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use mda::Email;
+use mda::{Email, NormalizationOptions, ValidationIssue};
 
-static TEST_EMAIL: &'static str = "Return-Path: <me@source.com>
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
 Multi: multi1
 To: Destination <someone.else@destination.com>
 Cc: firstcc <firstcc@destination.com>,
@@ -24,7 +24,7 @@ BodyField: body
 Body body body
 ";
 
-static TEST_EMAIL_NO_BODY: &'static str = "Return-Path: <me@source.com>
+static TEST_EMAIL_NO_BODY: &str = "Return-Path: <me@source.com>
 Multi: multi1
 To: Destination <someone.else@destination.com>
 Cc: firstcc <firstcc@destination.com>,
@@ -32,7 +32,7 @@ Cc: firstcc <firstcc@destination.com>,
     thirsdcc <secondcc@destination.com>
 ";
 
-static TEST_EMAIL_CRLF: &'static str = "Return-Path: <me@source.com>\r
+static TEST_EMAIL_CRLF: &str = "Return-Path: <me@source.com>\r
 Multi: multi1\r
 To: Destination <someone.else@destination.com>\r
 Cc: firstcc <firstcc@destination.com>,\r
@@ -133,3 +133,98 @@ fn header_using_crlf() {
          thirsdcc <secondcc@destination.com>"
     );
 }
+
+#[test]
+fn whitespace_is_kept_by_default() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert!(email.header_field("Cc").unwrap().contains(",\tthirsdcc"));
+}
+
+#[test]
+fn whitespace_is_folded_to_space() {
+    // TEST_EMAIL continues the Cc field with a tab, TEST_EMAIL_NO_BODY
+    // with spaces.
+    for data in [TEST_EMAIL, TEST_EMAIL_NO_BODY] {
+        let options = NormalizationOptions{
+            fold_whitespace_to_space: true,
+            ..Default::default()
+        };
+        let email = Email::from_vec_with_options(data.to_string().into_bytes(), options).unwrap();
+
+        assert_eq!(
+            email.header_field("Cc").unwrap().trim(),
+            "firstcc <firstcc@destination.com>, secondcc <secondcc@destination.com>, \
+             thirsdcc <secondcc@destination.com>"
+        );
+    }
+}
+
+#[test]
+fn header_field_canonical_names_are_title_case() {
+    assert_eq!(Email::header_field_canonical_name("return-path"), "Return-Path");
+    assert_eq!(Email::header_field_canonical_name("X-MAILER"), "X-Mailer");
+    assert_eq!(Email::header_field_canonical_name("to"), "To");
+}
+
+#[test]
+fn header_field_canonical_names_keep_acronyms_uppercase() {
+    assert_eq!(Email::header_field_canonical_name("mime-version"), "MIME-Version");
+    assert_eq!(Email::header_field_canonical_name("message-id"), "Message-ID");
+    assert_eq!(Email::header_field_canonical_name("dkim-signature"), "DKIM-Signature");
+    assert_eq!(Email::header_field_canonical_name("received-spf"), "Received-SPF");
+}
+
+#[test]
+fn longest_header_field_is_measured_before_decoding() {
+    let email = Email::from_vec(
+        b"To: someone@example.com\nSubject: =?utf-8?q?=CE=B1?=\n long\nX-Short: a\n\nBody\n".to_vec()).unwrap();
+
+    assert_eq!(email.longest_header_field(), Some(("subject", " =?utf-8?q?=CE=B1?= long".len())));
+    assert_eq!(Email::from_vec(b"\nBody\n".to_vec()).unwrap().longest_header_field(), None);
+}
+
+#[test]
+fn long_header_fields_are_truncated() {
+    let data = format!("Subject: {}\r\n{}\r\nTo: someone@example.com\r\n\r\nBody\r\n",
+                       "a".repeat(50), " b".repeat(1000));
+    // A non-ASCII body disables the plain email fast path.
+    let non_ascii_data = data.replace("Body", "Bödy");
+
+    for data in [data, non_ascii_data] {
+        let options = NormalizationOptions{
+            max_header_field_len: Some(20),
+            ..Default::default()
+        };
+        let email = Email::from_vec_with_options(data.into_bytes(), options).unwrap();
+
+        assert_eq!(email.header_field("Subject"), Some(" aaaaaaaaaaaaaaaaaaa"));
+        assert_eq!(email.header_field("To"), Some(" someone@example.com"));
+        assert!(email.header().starts_with(b"Subject: aaaaaaaaaaaaaaaaaaa\r\nTo:"));
+        assert_eq!(email.longest_header_field(), Some(("subject", 50 + 2000 + 1)));
+        assert_eq!(email.validate(), vec![ValidationIssue::HeaderFieldTruncated]);
+    }
+}
+
+#[test]
+fn whitespace_only_separator_line_is_not_canonical() {
+    let data = "To: someone@example.com\r\nSubject: hello\r\n \r\nX-Smuggled: yes\r\n\r\nBody\r\n";
+    let non_ascii_data = data.replace("Body", "Bödy");
+
+    for data in [data.to_string(), non_ascii_data] {
+        let email = Email::from_vec(data.into_bytes()).unwrap();
+
+        assert!(!email.separator_is_canonical());
+        assert_eq!(email.header_field("X-Smuggled"), Some(" yes"));
+        assert_eq!(email.validate(), vec![ValidationIssue::WhitespaceOnlyHeaderLine]);
+    }
+}
+
+#[test]
+fn empty_separator_line_is_canonical() {
+    let email = Email::from_vec(b"To: someone@example.com\r\n\r\n \r\nBody\r\n".to_vec()).unwrap();
+
+    assert!(email.separator_is_canonical());
+    assert!(email.body().ends_with(b"\r\n \r\nBody\r\n"));
+    assert!(email.validate().is_empty());
+}
@@ -0,0 +1,106 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+#![cfg(feature = "imap")]
+
+use mda::{Email, ImapConfig};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+Date: Mon, 7 Jan 2019 10:00:00 +0200 (EET)
+Subject: Test
+
+Body
+";
+
+/// Runs a minimal IMAP server that accepts a single connection, replying
+/// to APPEND with the specified status, and returns what it received.
+fn run_server(append_status: &'static str) -> (u16, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut received = Vec::new();
+
+        writer.write_all(b"* OK IMAP4rev1 ready\r\n").unwrap();
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 {
+                break;
+            }
+            let tag = line.split(' ').next().unwrap().to_string();
+            received.push(line.clone());
+
+            if line.contains(" APPEND ") {
+                let size: usize = line.rsplit('{').next().unwrap()
+                    .trim_end().trim_end_matches('}').parse().unwrap();
+                writer.write_all(b"+ Ready for literal data\r\n").unwrap();
+                let mut literal = vec![0; size + 2];
+                reader.read_exact(&mut literal).unwrap();
+                received.push(String::from_utf8(literal).unwrap());
+                writer.write_all(format!("{} {}\r\n", tag, append_status).as_bytes()).unwrap();
+            } else if line.contains(" LOGOUT") {
+                writer.write_all(b"* BYE\r\n").unwrap();
+                writer.write_all(format!("{} OK LOGOUT completed\r\n", tag).as_bytes()).unwrap();
+                break;
+            } else {
+                writer.write_all(format!("{} OK completed\r\n", tag).as_bytes()).unwrap();
+            }
+        }
+
+        received
+    });
+
+    (port, handle)
+}
+
+fn test_config(port: u16) -> ImapConfig {
+    let mut config = ImapConfig::new("127.0.0.1", "me", "se\"cret");
+    config.port = port;
+    config.tls = false;
+    config
+}
+
+#[test]
+fn appends_email_to_mailbox() {
+    let (port, server) = run_server("OK APPEND completed");
+    let mut config = test_config(port);
+    config.flags = vec![r"\Seen".to_string()];
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    email.deliver_to_imap(&config, "Archive").unwrap();
+
+    let received = server.join().unwrap();
+    let crlf_email = TEST_EMAIL.replace('\n', "\r\n");
+    assert_eq!(received[0], "a1 LOGIN \"me\" \"se\\\"cret\"\r\n");
+    assert_eq!(
+        received[1],
+        format!(
+            "a2 APPEND \"Archive\" (\\Seen) \"07-Jan-2019 10:00:00 +0200\" {{{}}}\r\n",
+            crlf_email.len()
+        )
+    );
+    assert_eq!(received[2], crlf_email + "\r\n");
+    assert_eq!(received[3], "a3 LOGOUT\r\n");
+}
+
+#[test]
+fn reports_append_failure() {
+    let (port, _server) = run_server("NO [TRYCREATE] No such mailbox");
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    let err = email.deliver_to_imap(&test_config(port), "Missing").unwrap_err();
+
+    assert!(err.to_string().contains("NO [TRYCREATE] No such mailbox"));
+}
@@ -0,0 +1,85 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Email, SliceLines};
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Cc: firstcc <firstcc@destination.com>,
+ secondcc <secondcc@destination.com>
+
+Body body body
+";
+
+#[test]
+fn slice_lines_include_terminators() {
+    let lines: Vec<_> = SliceLines::new(b"a\nbb\r\n\nc\n").collect();
+
+    assert_eq!(lines, [&b"a\n"[..], b"bb\r\n", b"\n", b"c\n"]);
+}
+
+#[test]
+fn slice_lines_yield_final_unterminated_line() {
+    let lines: Vec<_> = SliceLines::new(b"a\nb").collect();
+
+    assert_eq!(lines, [&b"a\n"[..], b"b"]);
+}
+
+#[test]
+fn slice_lines_of_empty_data_is_empty() {
+    assert_eq!(SliceLines::new(b"").count(), 0);
+}
+
+#[test]
+fn data_lines_reproduce_data() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.data_lines().count(), 5);
+    assert_eq!(email.data_lines().collect::<Vec<_>>().concat(), email.data());
+}
+
+#[test]
+fn header_lines_are_unfolded() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let lines: Vec<_> = email.header_lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[2],
+        &b"Cc: firstcc <firstcc@destination.com>, secondcc <secondcc@destination.com>"[..]
+    );
+}
+
+#[test]
+fn raw_header_lines_preserve_folding() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let lines: Vec<_> = email.raw_header_lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], &b"Return-Path: <me@source.com>\n"[..]);
+    assert_eq!(
+        lines[2],
+        &b"Cc: firstcc <firstcc@destination.com>,\n secondcc <secondcc@destination.com>\n"[..]
+    );
+}
+
+#[test]
+fn raw_header_lines_are_not_decoded() {
+    let data = b"Subject: =?utf-8?b?zpHOks6T?=\r\n\tmore\r\n\r\nBody\r\n".to_vec();
+    let email = Email::from_vec(data).unwrap();
+    let lines: Vec<_> = email.raw_header_lines().collect();
+
+    assert_eq!(lines, [&b"Subject: =?utf-8?b?zpHOks6T?=\r\n\tmore\r\n"[..]]);
+}
+
+#[test]
+fn raw_header_lines_of_email_without_header_are_empty() {
+    let email = Email::from_vec(b"\nBody\n".to_vec()).unwrap();
+
+    assert_eq!(email.raw_header_lines().count(), 0);
+}
@@ -0,0 +1,207 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{read_mbox, split_mbox, Email, MboxDateFormat, MboxFormat, MboxOptions};
+use regex::Regex;
+use std::fs;
+
+static TEST_MBOX: &str = "From me@source.com Mon Jan  7 10:00:00 2019
+Return-Path: <me@source.com>
+Subject: First
+
+>From the start
+>>From the quoted start
+
+From someone@source.com Mon Jan  7 11:00:00 2019
+Return-Path: <someone@source.com>
+Subject: Second
+
+Body
+From here, not a separator
+
+";
+
+#[test]
+fn mbox_is_split_on_separator_lines() {
+    let emails = split_mbox(TEST_MBOX.as_bytes(), MboxFormat::Mboxrd);
+
+    assert_eq!(emails.len(), 2);
+    assert!(emails[0].starts_with(b"Return-Path: <me@source.com>\n"));
+    assert!(emails[1].starts_with(b"Return-Path: <someone@source.com>\n"));
+}
+
+#[test]
+fn mbox_separator_padding_is_removed() {
+    let emails = split_mbox(TEST_MBOX.as_bytes(), MboxFormat::Mboxrd);
+
+    assert!(emails[0].ends_with(b"start\n"));
+    assert!(emails[1].ends_with(b"not a separator\n"));
+}
+
+#[test]
+fn mbox_from_line_not_after_empty_line_is_not_separator() {
+    let emails = split_mbox(TEST_MBOX.as_bytes(), MboxFormat::Mboxrd);
+
+    assert!(emails[1].ends_with(b"Body\nFrom here, not a separator\n"));
+}
+
+#[test]
+fn mboxrd_unescapes_all_quoted_from_lines() {
+    let emails = split_mbox(TEST_MBOX.as_bytes(), MboxFormat::Mboxrd);
+
+    assert!(emails[0].ends_with(b"\n\nFrom the start\n>From the quoted start\n"));
+}
+
+#[test]
+fn mboxo_unescapes_only_single_quoted_from_lines() {
+    let emails = split_mbox(TEST_MBOX.as_bytes(), MboxFormat::Mboxo);
+
+    assert!(emails[0].ends_with(b"\n\nFrom the start\n>>From the quoted start\n"));
+}
+
+#[test]
+fn mbox_data_before_first_separator_is_ignored() {
+    let mbox = format!("garbage\n\n{}", TEST_MBOX);
+    let emails = split_mbox(mbox.as_bytes(), MboxFormat::Mboxrd);
+
+    assert_eq!(emails.len(), 2);
+    assert!(emails[0].starts_with(b"Return-Path: <me@source.com>\n"));
+}
+
+#[test]
+fn mbox_file_is_read_into_emails() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let path = tmpdir.path().join("mbox");
+    fs::write(&path, TEST_MBOX).unwrap();
+
+    let emails = read_mbox(&path, MboxFormat::Mboxrd).unwrap();
+
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[0].header_field("Subject"), Some(" First"));
+    assert_eq!(emails[1].header_field("Subject"), Some(" Second"));
+}
+
+static TEST_EMAIL_WITH_FROM_LINES: &str = "Return-Path: <me@source.com>
+Subject: Escaping
+
+From the start
+>From the quoted start
+";
+
+#[test]
+fn delivered_emails_are_read_back() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let mbox = tmpdir.path().join("mbox");
+    let email1 = Email::from_vec(TEST_EMAIL_WITH_FROM_LINES.to_string().into_bytes()).unwrap();
+    let email2 = Email::from_vec(b"Subject: Second\n\nNo trailing newline".to_vec()).unwrap();
+
+    email1.deliver_to_mbox(&mbox).unwrap();
+    email2.deliver_to_mbox(&mbox).unwrap();
+
+    let emails = read_mbox(&mbox, MboxFormat::Mboxrd).unwrap();
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[0].raw_data(), TEST_EMAIL_WITH_FROM_LINES.as_bytes());
+    assert_eq!(emails[1].raw_data(), b"Subject: Second\n\nNo trailing newline\n");
+}
+
+#[test]
+fn delivered_from_line_uses_return_path_sender() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let mbox = tmpdir.path().join("mbox");
+    let email1 = Email::from_vec(TEST_EMAIL_WITH_FROM_LINES.to_string().into_bytes()).unwrap();
+    let email2 = Email::from_vec(b"Return-Path: <>\n\nBody\n".to_vec()).unwrap();
+
+    email1.deliver_to_mbox(&mbox).unwrap();
+    email2.deliver_to_mbox(&mbox).unwrap();
+
+    let data = fs::read_to_string(&mbox).unwrap();
+    let from_lines: Vec<&str> = data.lines().filter(|l| l.starts_with("From ")).collect();
+    let asctime = r"(Mon|Tue|Wed|Thu|Fri|Sat|Sun) [A-Z][a-z]{2} [ \d]\d \d\d:\d\d:\d\d \d{4}$";
+    assert_eq!(from_lines.len(), 2);
+    assert!(Regex::new(&format!("^From me@source.com {}", asctime)).unwrap().is_match(from_lines[0]));
+    assert!(Regex::new(&format!("^From MAILER-DAEMON {}", asctime)).unwrap().is_match(from_lines[1]));
+}
+
+#[test]
+fn delivery_uses_mbox_options() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let mbox = tmpdir.path().join("mbox");
+    let email = Email::from_vec(TEST_EMAIL_WITH_FROM_LINES.to_string().into_bytes()).unwrap();
+    let options = MboxOptions{
+        sender: Some("other@source.com".to_string()),
+        date_format: MboxDateFormat::Rfc5322,
+        format: MboxFormat::Mboxo,
+    };
+
+    email.deliver_to_mbox_with_options(&mbox, &options).unwrap();
+
+    let data = fs::read_to_string(&mbox).unwrap();
+    let rfc5322 = r"(Mon|Tue|Wed|Thu|Fri|Sat|Sun), \d\d [A-Z][a-z]{2} \d{4} \d\d:\d\d:\d\d \+0000";
+    assert!(Regex::new(&format!("^From other@source.com {}\n", rfc5322)).unwrap().is_match(&data));
+    assert!(data.ends_with("\n>From the start\n>From the quoted start\n\n"));
+}
+
+#[test]
+fn delivery_separates_from_existing_data() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let mbox = tmpdir.path().join("mbox");
+    fs::write(&mbox, "From me@source.com Mon Jan  7 10:00:00 2019\nSubject: Old\n\nBody").unwrap();
+    let email = Email::from_vec(b"Subject: New\n\nBody\n".to_vec()).unwrap();
+
+    email.deliver_to_mbox(&mbox).unwrap();
+
+    let emails = read_mbox(&mbox, MboxFormat::Mboxrd).unwrap();
+    assert_eq!(emails.len(), 2);
+    assert_eq!(emails[0].raw_data(), b"Subject: Old\n\nBody\n");
+    assert_eq!(emails[1].raw_data(), b"Subject: New\n\nBody\n");
+}
+
+#[test]
+fn concurrent_deliveries_are_not_interleaved() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let mbox = tmpdir.path().join("mbox");
+    let body = "line\n".repeat(10_000);
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let mbox = mbox.clone();
+            let data = format!("Subject: {}\n\n{}", i, body);
+            std::thread::spawn(move || {
+                let email = Email::from_vec(data.into_bytes()).unwrap();
+                email.deliver_to_mbox(&mbox).unwrap();
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let emails = read_mbox(&mbox, MboxFormat::Mboxrd).unwrap();
+    assert_eq!(emails.len(), 8);
+    for email in &emails {
+        assert_eq!(email.raw_data().len(), "Subject: 0\n\n".len() + body.len());
+    }
+}
+
+#[test]
+fn leading_mbox_from_line_is_stripped() {
+    let data = b"From me@source.com Mon Jan  7 10:00:00 2019\r\nSubject: Hi\r\n\r\nFrom me\r\n".to_vec();
+
+    let email = Email::from_vec(Email::strip_mbox_from_line(data)).unwrap();
+
+    assert_eq!(email.raw_data(), b"Subject: Hi\r\n\r\nFrom me\r\n");
+    assert_eq!(email.header_field("Subject"), Some(" Hi"));
+}
+
+#[test]
+fn data_without_mbox_from_line_is_not_stripped() {
+    let data = b"From: me@source.com\nSubject: Hi\n\nBody\n".to_vec();
+
+    assert_eq!(Email::strip_mbox_from_line(data.clone()), data);
+    assert_eq!(Email::strip_mbox_from_line(Vec::new()), b"");
+}
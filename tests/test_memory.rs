@@ -0,0 +1,69 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+// The allocation counting allocator applies to the whole test binary, so
+// this is kept in its own test binary with a single test.
+
+use mda::{Email, EmailRegex};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK_ALLOCATED.fetch_max(allocated, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn large_base64_email(lines: usize) -> Vec<u8> {
+    let mut data = b"Return-Path: <me@source.com>\n\
+                     Content-Type: text/plain\n\
+                     Content-Transfer-Encoding: base64\n\n".to_vec();
+
+    // Each line decodes to "The quick brown fox jumps over the lazy dog, again!!!!!!\n".
+    for _ in 0..lines {
+        data.extend(&b"VGhlIHF1aWNrIGJyb3duIGZveCBqdW1wcyBvdmVyIHRoZSBsYXp5IGRvZywgYWdhaW4hISEhISEK\n"[..]);
+    }
+
+    data
+}
+
+#[test]
+fn large_part_is_decoded_without_copying_encoded_data() {
+    let data = large_base64_email(128 * 1024);
+    let data_len = data.len();
+
+    let baseline = ALLOCATED.load(Ordering::SeqCst);
+    PEAK_ALLOCATED.store(baseline, Ordering::SeqCst);
+
+    let email = Email::from_vec(data).unwrap();
+
+    let peak_extra = PEAK_ALLOCATED.load(Ordering::SeqCst) - baseline;
+
+    assert!(email.body().search("^The quick brown fox jumps over the lazy dog, again!+$").unwrap());
+    // The normalized data is the only large allocation, and its size is
+    // bounded by the size of the original data.
+    assert!(
+        peak_extra < data_len * 5 / 4,
+        "peak extra allocation {} for data size {}", peak_extra, data_len
+    );
+}
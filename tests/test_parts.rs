@@ -0,0 +1,290 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Descend, Email, PartInfo, TextPartStrategy};
+
+static TEST_EMAIL_NESTED: &str = r#"To: me@example.com
+Content-Type: multipart/mixed; boundary="outer"
+
+Preamble
+--outer
+Content-Type: multipart/alternative; boundary="inner"
+
+--inner
+Content-Type: text/plain; charset="iso-8859-7"
+Content-Transfer-Encoding: base64
+
+wcLD
+--inner
+Content-Type: text/html
+
+<p>html</p>
+--inner--
+--outer
+Content-Type: image/png
+Content-Transfer-Encoding: base64
+
+iVBORw0KGgo=
+--outer--
+Epilogue
+"#;
+
+fn visited_parts(email: &Email, skip: Option<&str>) -> Vec<(Option<String>, usize, Vec<Option<String>>)> {
+    let mut visited = Vec::new();
+    email.walk_parts(|part, ancestors| {
+        let ancestor_types = ancestors.iter().map(|a| a.content_type.clone()).collect();
+        visited.push((part.content_type.clone(), part.depth, ancestor_types));
+        if part.content_type.as_deref() == skip { Descend::Skip } else { Descend::Into }
+    });
+    visited
+}
+
+fn some(s: &str) -> Option<String> {
+    Some(s.to_string())
+}
+
+#[test]
+fn walk_parts_visits_all_parts_with_ancestors() {
+    let email = Email::from_vec(TEST_EMAIL_NESTED.to_string().into_bytes()).unwrap();
+
+    assert_eq!(
+        visited_parts(&email, None),
+        vec![
+            (some("multipart/mixed"), 0, vec![]),
+            (some("multipart/alternative"), 1, vec![some("multipart/mixed")]),
+            (some("text/plain"), 2, vec![some("multipart/mixed"), some("multipart/alternative")]),
+            (some("text/html"), 2, vec![some("multipart/mixed"), some("multipart/alternative")]),
+            (some("image/png"), 1, vec![some("multipart/mixed")]),
+        ]
+    );
+}
+
+#[test]
+fn walk_parts_skips_subparts() {
+    let email = Email::from_vec(TEST_EMAIL_NESTED.to_string().into_bytes()).unwrap();
+
+    let types: Vec<_> = visited_parts(&email, Some("multipart/alternative"))
+        .into_iter()
+        .map(|(t, _, _)| t)
+        .collect();
+    assert_eq!(types, vec![some("multipart/mixed"), some("multipart/alternative"), some("image/png")]);
+}
+
+#[test]
+fn walk_parts_provides_part_details_and_ranges() {
+    let email = Email::from_vec(TEST_EMAIL_NESTED.to_string().into_bytes()).unwrap();
+    let mut parts: Vec<PartInfo> = Vec::new();
+    email.walk_parts(|part, _| { parts.push(part.clone()); Descend::Into });
+
+    let data = email.data();
+    let text = &parts[2];
+    assert_eq!(text.charset.as_deref(), Some("iso-8859-7"));
+    assert_eq!(text.encoding.as_deref(), Some("base64"));
+    assert!(!text.multipart);
+    assert!(data[text.header.clone()].starts_with(b"Content-Type: text/plain"));
+    assert_eq!(&data[text.body.clone()], "ΑΒΓ\n".as_bytes());
+
+    let top = &parts[0];
+    assert!(top.multipart);
+    assert_eq!(top.header.start, 0);
+    assert_eq!(top.body.end, data.len());
+    assert!(data[top.body.clone()].ends_with(b"--outer--\nEpilogue\n"));
+
+    let image = &parts[4];
+    assert_eq!(&data[image.body.clone()], b"iVBORw0KGgo=\n");
+}
+
+#[test]
+fn walk_parts_of_single_part_email_visits_top_level() {
+    let email = Email::from_vec(b"To: me@example.com\n\nBody\n".to_vec()).unwrap();
+    let mut parts: Vec<PartInfo> = Vec::new();
+    email.walk_parts(|part, _| { parts.push(part.clone()); Descend::Into });
+
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].content_type, None);
+    assert_eq!(&email.data()[parts[0].body.clone()], b"Body\n");
+}
+
+#[test]
+fn into_parts_returns_fields_and_leaf_part_data() {
+    let email = Email::from_vec(TEST_EMAIL_NESTED.to_string().into_bytes()).unwrap();
+    let (fields, parts) = email.into_parts();
+
+    assert_eq!(fields["to"], vec![" me@example.com"]);
+
+    let summary: Vec<_> = parts
+        .iter()
+        .map(|p| (p.info.content_type.clone(), String::from_utf8_lossy(&p.data).into_owned()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (some("text/plain"), "ΑΒΓ\n".to_string()),
+            (some("text/html"), "<p>html</p>\n".to_string()),
+            (some("image/png"), "iVBORw0KGgo=\n".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn into_parts_of_single_part_email_returns_body() {
+    let email = Email::from_vec(b"Subject: Hi\n\nBody\n".to_vec()).unwrap();
+    let (_, parts) = email.into_parts();
+
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].info.depth, 0);
+    assert_eq!(parts[0].data, b"Body\n");
+}
+
+#[test]
+fn parts_returns_leaf_parts_with_decoded_data() {
+    let email = Email::from_vec(TEST_EMAIL_NESTED.to_string().into_bytes()).unwrap();
+    let parts = email.parts();
+
+    let summary: Vec<_> = parts
+        .iter()
+        .map(|p| (p.content_type(), p.charset(), p.encoding(), p.info().depth))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (Some("text/plain"), Some("iso-8859-7"), Some("base64"), 2),
+            (Some("text/html"), None, None, 2),
+            (Some("image/png"), None, Some("base64"), 1),
+        ]
+    );
+
+    assert_eq!(parts[0].decoded_bytes(), "ΑΒΓ\n".as_bytes());
+    assert_eq!(parts[1].decoded_bytes(), &b"<p>html</p>\n"[..]);
+    assert_eq!(parts[2].decoded_bytes(), &b"\x89PNG\r\n\x1a\n"[..]);
+}
+
+#[test]
+fn parts_decodes_quoted_printable_non_text_data() {
+    let email = Email::from_vec(
+        b"Content-Type: multipart/mixed; boundary=\"b\"\n\n\
+          --b\nContent-Type: application/octet-stream\n\
+          Content-Transfer-Encoding: quoted-printable\n\na=3Db=\nc\n--b--\n".to_vec()).unwrap();
+    let parts = email.parts();
+
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].decoded_bytes(), &b"a=bc"[..]);
+}
+
+#[test]
+fn parts_of_single_part_email_returns_body() {
+    let email = Email::from_vec(b"Subject: Hi\n\nBody\n".to_vec()).unwrap();
+    let parts = email.parts();
+
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].content_type(), None);
+    assert_eq!(parts[0].decoded_bytes(), &b"Body\n"[..]);
+}
+
+static TEST_EMAIL_MULTIPLE_TEXT: &str = r#"To: me@example.com
+MIME-Version: 1.0
+Content-Type: multipart/mixed; boundary="b"
+
+--b
+Content-Type: text/plain
+
+Cover note
+--b
+Content-Type: image/png
+Content-Transfer-Encoding: base64
+
+iVBORw0KGgo=
+--b
+Content-Type: text/plain
+
+Quoted block, which is longer
+--b--
+"#;
+
+#[test]
+fn primary_text_part_uses_strategy() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPLE_TEXT.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.primary_text_part(TextPartStrategy::First).unwrap(), "Cover note\n");
+    assert_eq!(
+        email.primary_text_part(TextPartStrategy::Largest).unwrap(),
+        "Quoted block, which is longer\n"
+    );
+    assert_eq!(
+        email.primary_text_part(TextPartStrategy::Concatenated).unwrap(),
+        "Cover note\nQuoted block, which is longer\n"
+    );
+}
+
+#[test]
+fn primary_text_part_of_email_without_text_is_none() {
+    let email = Email::from_vec(
+        b"Content-Type: image/png\nContent-Transfer-Encoding: base64\n\niVBORw0KGgo=\n".to_vec()).unwrap();
+
+    assert_eq!(email.primary_text_part(TextPartStrategy::First), None);
+    assert_eq!(email.primary_text_part(TextPartStrategy::Largest), None);
+    assert_eq!(email.primary_text_part(TextPartStrategy::Concatenated), None);
+}
+
+#[test]
+fn preamble_and_epilogue_are_separate_from_body_text() {
+    let email = Email::from_vec(TEST_EMAIL_NESTED.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.preamble(), Some(&b"Preamble\n"[..]));
+    assert_eq!(email.epilogue(), Some(&b"Epilogue\n"[..]));
+    assert!(!email.body_text().contains("Preamble"));
+    assert!(!email.body_text().contains("Epilogue"));
+}
+
+#[test]
+fn non_multipart_email_has_no_preamble_or_epilogue() {
+    let email = Email::from_vec(b"Subject: Hi\n\nBody\n".to_vec()).unwrap();
+
+    assert_eq!(email.preamble(), None);
+    assert_eq!(email.epilogue(), None);
+}
+
+#[test]
+fn multipart_email_without_preamble_or_epilogue() {
+    let email = Email::from_vec(
+        b"Content-Type: multipart/mixed; boundary=\"b\"\n\n--b\n\nText\n--b--\n".to_vec()).unwrap();
+
+    assert_eq!(email.preamble(), None);
+    assert_eq!(email.epilogue(), None);
+}
+
+#[test]
+fn parts_are_searched_individually() {
+    let email = Email::from_vec(
+        b"MIME-Version: 1.0\n\
+          Content-Type: multipart/alternative; boundary=\"abc\"\n\
+          \n\
+          --abc\n\
+          Content-Type: text/plain\n\
+          Content-Transfer-Encoding: quoted-printable\n\
+          \n\
+          Click to UNSUB=\n\
+          SCRIBE now\n\
+          --abc\n\
+          Content-Type: text/html\n\
+          \n\
+          <p>Hello</p>\n\
+          --abc\n\
+          Content-Type: application/octet-stream\n\
+          Content-Transfer-Encoding: base64\n\
+          \n\
+          dW5zdWJzY3JpYmU=\n\
+          --abc--\n".to_vec()
+    ).unwrap();
+
+    let parts = email.parts();
+    assert!(parts[0].search(r"unsubscribe now$").unwrap());
+    assert!(!parts[1].search(r"unsubscribe").unwrap());
+    assert!(parts[2].search(r"^unsubscribe$").unwrap());
+    assert!(parts[1].search(r"(invalid").is_err());
+}
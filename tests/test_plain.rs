@@ -0,0 +1,99 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Descend, Email, NormalizationOptions, PartInfo};
+
+static TEST_EMAIL_PLAIN: &str = "Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>,
+\tOther <other@destination.com>
+Subject: A plain
+ folded subject
+Content-Type: text/plain; charset=us-ascii
+
+Body body body
+More body
+";
+
+static TEST_EMAIL_PLAIN_CRLF_NO_CONTENT_TYPE: &str =
+    "Subject: Hello\r\nX-Folded: a\r\n b\r\n\r\nBody\r\n";
+
+static TEST_EMAIL_PLAIN_NON_TEXT: &str = "Subject: Data
+Content-Type: application/octet-stream
+
+AAAA
+";
+
+static TEST_EMAIL_PLAIN_UNKNOWN_CHARSET: &str = "Subject: Data
+Content-Type: text/plain; charset=x-unknown
+
+Body
+";
+
+static TEST_EMAIL_PLAIN_NO_BODY: &str = "Subject: Hello
+To: someone@destination.com
+";
+
+static TEST_EMAIL_PLAIN_EMPTY_BODY: &str = "Subject: Hello\n\n";
+
+/// Creates an email with a no-op part transform, which makes normalization
+/// use the full email parser.
+fn email_with_full_parser(data: &str) -> Email {
+    let options = NormalizationOptions{
+        part_transform: Some(Box::new(|_, _| {})),
+        ..Default::default()
+    };
+    Email::from_vec_with_options(data.as_bytes().to_vec(), options).unwrap()
+}
+
+fn parts(email: &Email) -> Vec<PartInfo> {
+    let mut parts = Vec::new();
+    email.walk_parts(|part, _| { parts.push(part.clone()); Descend::Into });
+    parts
+}
+
+fn assert_normalized_like_full_parser(data: &str) {
+    let email = Email::from_vec(data.as_bytes().to_vec()).unwrap();
+    let expected = email_with_full_parser(data);
+
+    assert_eq!(email.data(), expected.data());
+    assert_eq!(email.header(), expected.header());
+    assert_eq!(email.body(), expected.body());
+    assert_eq!(email.body_text(), expected.body_text());
+    assert_eq!(email.primary_charset(), expected.primary_charset());
+    assert_eq!(email.lossy_fields(), expected.lossy_fields());
+    assert_eq!(email.validate(), expected.validate());
+    assert_eq!(parts(&email), parts(&expected));
+    for name in &["Return-Path", "To", "Subject", "Content-Type", "X-Folded", "Missing"] {
+        assert_eq!(email.header_field_all_occurrences(name), expected.header_field_all_occurrences(name));
+    }
+}
+
+#[test]
+fn plain_email_is_normalized_like_full_parser() {
+    assert_normalized_like_full_parser(TEST_EMAIL_PLAIN);
+    assert_normalized_like_full_parser(TEST_EMAIL_PLAIN_CRLF_NO_CONTENT_TYPE);
+    assert_normalized_like_full_parser(TEST_EMAIL_PLAIN_NON_TEXT);
+    assert_normalized_like_full_parser(TEST_EMAIL_PLAIN_UNKNOWN_CHARSET);
+}
+
+#[test]
+fn plain_email_without_body_is_normalized_like_full_parser() {
+    assert_normalized_like_full_parser(TEST_EMAIL_PLAIN_NO_BODY);
+    assert_normalized_like_full_parser(TEST_EMAIL_PLAIN_EMPTY_BODY);
+    assert_normalized_like_full_parser("");
+    assert_normalized_like_full_parser("\nBody only\n");
+}
+
+#[test]
+fn plain_email_header_fields_are_unfolded() {
+    let email = Email::from_vec(TEST_EMAIL_PLAIN.as_bytes().to_vec()).unwrap();
+
+    assert_eq!(email.header_field("Subject"), Some(" A plain folded subject"));
+    assert!(email.header().starts_with(
+        b"Return-Path: <me@source.com>\nTo: Destination <someone.else@destination.com>,\tOther"));
+}
@@ -0,0 +1,60 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+
+static TEST_EMAIL: &str = "To: me@example.com
+Subject: Re: Printer
+
+Yes,   the printer
+is on fire.
+
+> Is the printer on fire?
+--\x20
+Someone
+";
+
+#[test]
+fn body_preview_collapses_new_content() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(email.body_preview().unwrap(), "Yes, the printer is on fire.");
+}
+
+#[test]
+fn body_preview_is_truncated_at_word_boundary() {
+    let data = format!("To: me@example.com\n\n{}\n", "word ".repeat(100));
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    let preview = email.body_preview().unwrap();
+    assert!(preview.ends_with("word…"));
+    assert!(preview.chars().count() <= 201);
+}
+
+#[test]
+fn body_preview_is_none_without_text() {
+    let email = Email::from_vec(b"To: me@example.com\n\n> quoted\n".to_vec()).unwrap();
+
+    assert_eq!(email.body_preview(), None);
+}
+
+#[test]
+fn snippet_prefers_summary_header() {
+    let data = TEST_EMAIL.replacen("To:", "X-Summary: =?utf-8?q?Printer_=CF=86ire?=\nTo:", 1);
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert_eq!(email.snippet().unwrap(), "Printer φire");
+}
+
+#[test]
+fn snippet_falls_back_to_body_preview() {
+    let data = TEST_EMAIL.replacen("To:", "X-Summary:  \nTo:", 1);
+    let email = Email::from_vec(data.into_bytes()).unwrap();
+
+    assert_eq!(email.snippet().unwrap(), "Yes, the printer is on fire.");
+}
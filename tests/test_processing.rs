@@ -8,7 +8,7 @@
 
 use mda::Email;
 
-static TEST_EMAIL: &'static str = "Return-Path: <me@source.com>
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Cc: firstcc <firstcc@destination.com>,
  secondcc <secondcc@destination.com>,
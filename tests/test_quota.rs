@@ -0,0 +1,210 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{DeliveryDurability, DeliveryError, EmailFilenameGenerator, Maildir, MdaError};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+fn open_maildir(path: &Path) -> Maildir {
+    let gen = Arc::new(Mutex::new(EmailFilenameGenerator::new()));
+    Maildir::open_or_create(path, gen).unwrap()
+}
+
+fn num_files(dir: &Path) -> usize {
+    fs::read_dir(dir).unwrap().count()
+}
+
+fn is_quota_exceeded(err: &MdaError) -> bool {
+    matches!(err, MdaError::Delivery(DeliveryError::QuotaExceeded))
+}
+
+#[test]
+fn delivers_without_quota_if_maildirsize_is_missing() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildir = open_maildir(tmpdir.path());
+
+    maildir.deliver(&[0; 10000], DeliveryDurability::FileSyncOnly).unwrap();
+
+    assert_eq!(num_files(&tmpdir.path().join("new")), 1);
+    assert!(!tmpdir.path().join("maildirsize").exists());
+}
+
+#[test]
+fn records_deliveries_within_quota() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    fs::write(&maildirsize, "1000S,10C\n100 2\n").unwrap();
+
+    maildir.deliver(&[0; 30], DeliveryDurability::FileSyncOnly).unwrap();
+    maildir.deliver_with_info(&[0; 20], "S", DeliveryDurability::FileSyncOnly).unwrap();
+
+    assert_eq!(fs::read_to_string(&maildirsize).unwrap(), "1000S,10C\n100 2\n30 1\n20 1\n");
+}
+
+#[test]
+fn fails_delivery_exceeding_size_quota() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    fs::write(&maildirsize, "1000S\n900 3\n50 1\n").unwrap();
+
+    let err = maildir.deliver(&[0; 51], DeliveryDurability::FileSyncOnly).unwrap_err();
+
+    assert!(is_quota_exceeded(&err));
+    assert_eq!(err.to_string(), "Maildir quota exceeded");
+    assert_eq!(num_files(&tmpdir.path().join("new")), 0);
+    assert_eq!(num_files(&tmpdir.path().join("tmp")), 0);
+    assert_eq!(fs::read_to_string(&maildirsize).unwrap(), "1000S\n900 3\n50 1\n");
+
+    maildir.deliver(&[0; 50], DeliveryDurability::FileSyncOnly).unwrap();
+}
+
+#[test]
+fn fails_delivery_exceeding_count_quota() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildir = open_maildir(tmpdir.path());
+    fs::write(tmpdir.path().join("maildirsize"), "0S,2C\n10 1\n").unwrap();
+
+    maildir.deliver(b"data", DeliveryDurability::FileSyncOnly).unwrap();
+    let err = maildir.deliver(b"data", DeliveryDurability::FileSyncOnly).unwrap_err();
+
+    assert!(is_quota_exceeded(&err));
+    assert_eq!(num_files(&tmpdir.path().join("new")), 1);
+}
+
+#[test]
+fn enforces_quota_for_all_delivery_methods() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let inbox = open_maildir(&tmpdir.path().join("inbox"));
+    let full = open_maildir(&tmpdir.path().join("full"));
+    fs::write(tmpdir.path().join("full/maildirsize"), "10S\n10 1\n").unwrap();
+    let src = inbox.deliver(b"data", DeliveryDurability::FileSyncOnly).unwrap();
+
+    let errors = [
+        full.deliver_with_hard_link(&src, DeliveryDurability::FileSyncOnly).unwrap_err(),
+        full.deliver_named(b"data", "name", DeliveryDurability::FileSyncOnly).unwrap_err(),
+        full.deliver_once(b"data", "<id@example.com>", DeliveryDurability::FileSyncOnly).unwrap_err(),
+        full.deliver_with_writer(
+            |writer| Ok(writer.write_all(b"data")?),
+            DeliveryDurability::FileSyncOnly
+        ).unwrap_err(),
+    ];
+
+    assert!(errors.iter().all(is_quota_exceeded));
+    assert_eq!(num_files(&tmpdir.path().join("full/new")), 0);
+    assert_eq!(num_files(&tmpdir.path().join("full/tmp")), 0);
+}
+
+#[test]
+fn once_delivery_is_retried_after_exceeding_quota() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    fs::write(&maildirsize, "10S\n10 1\n").unwrap();
+
+    let err = maildir.deliver_once(b"data", "<id@example.com>", DeliveryDurability::FileSyncOnly).unwrap_err();
+    assert!(is_quota_exceeded(&err));
+
+    fs::write(&maildirsize, "1000S\n10 1\n").unwrap();
+    let path = maildir.deliver_once(b"data", "<id@example.com>", DeliveryDurability::FileSyncOnly).unwrap();
+    assert!(path.is_some());
+}
+
+#[test]
+fn once_delivery_is_skipped_before_checking_quota() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    fs::write(&maildirsize, "1000S\n").unwrap();
+
+    maildir.deliver_once(b"data", "<id@example.com>", DeliveryDurability::FileSyncOnly).unwrap();
+    fs::write(&maildirsize, "10S\n10 1\n").unwrap();
+
+    let path = maildir.deliver_once(b"data", "<id@example.com>", DeliveryDurability::FileSyncOnly).unwrap();
+    assert_eq!(path, None);
+}
+
+#[test]
+fn records_size_written_by_writer() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    fs::write(&maildirsize, "1000S\n").unwrap();
+
+    maildir.deliver_with_writer(
+        |writer| Ok(writer.write_all(&[0; 123])?),
+        DeliveryDurability::FileSyncOnly
+    ).unwrap();
+
+    assert_eq!(fs::read_to_string(&maildirsize).unwrap(), "1000S\n123 1\n");
+}
+
+#[test]
+fn recalculates_stale_maildirsize_when_over_quota() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    maildir.deliver(&[0; 40], DeliveryDurability::FileSyncOnly).unwrap();
+    fs::write(tmpdir.path().join("cur/1234.host,S=100:2,S"), b"compressed").unwrap();
+
+    // The recorded usage includes emails that have since been removed.
+    fs::write(&maildirsize, "500S\n40 1\n100 1\n300 1\n").unwrap();
+    let an_hour_ago = SystemTime::now() - Duration::from_secs(60 * 60);
+    fs::File::options().append(true).open(&maildirsize).unwrap()
+        .set_modified(an_hour_ago).unwrap();
+
+    maildir.deliver(&[0; 70], DeliveryDurability::FileSyncOnly).unwrap();
+
+    assert_eq!(fs::read_to_string(&maildirsize).unwrap(), "500S\n140 2\n70 1\n");
+}
+
+#[test]
+fn does_not_recalculate_recent_maildirsize() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    fs::write(&maildirsize, "500S\n480 3\n").unwrap();
+
+    let err = maildir.deliver(&[0; 70], DeliveryDurability::FileSyncOnly).unwrap_err();
+
+    assert!(is_quota_exceeded(&err));
+    assert_eq!(fs::read_to_string(&maildirsize).unwrap(), "500S\n480 3\n");
+}
+
+#[test]
+fn recalculates_large_maildirsize() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    maildir.deliver(&[0; 10], DeliveryDurability::FileSyncOnly).unwrap();
+    fs::write(&maildirsize, format!("1000000S,1000C\n{}", "10 1\n".repeat(1100))).unwrap();
+
+    maildir.deliver(&[0; 20], DeliveryDurability::FileSyncOnly).unwrap();
+
+    assert_eq!(fs::read_to_string(&maildirsize).unwrap(), "1000000S,1000C\n10 1\n20 1\n");
+    assert_eq!(num_files(&tmpdir.path().join("tmp")), 0);
+}
+
+#[test]
+fn recalculation_includes_subfolders() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let maildirsize = tmpdir.path().join("maildirsize");
+    let maildir = open_maildir(tmpdir.path());
+    let archive = open_maildir(&tmpdir.path().join(".Archive"));
+    maildir.deliver(&[0; 10], DeliveryDurability::FileSyncOnly).unwrap();
+    archive.deliver_with_info(&[0; 30], "S", DeliveryDurability::FileSyncOnly).unwrap();
+    fs::create_dir(tmpdir.path().join(".Empty")).unwrap();
+    fs::write(&maildirsize, format!("1000000S,1000C\n{}", "10 1\n".repeat(1100))).unwrap();
+
+    maildir.deliver(&[0; 20], DeliveryDurability::FileSyncOnly).unwrap();
+
+    assert_eq!(fs::read_to_string(&maildirsize).unwrap(), "1000000S,1000C\n40 2\n20 1\n");
+}
@@ -0,0 +1,122 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Email, NormalizationOptions};
+
+static TEST_EMAIL_MULTIPART: &str = r#"Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Subject: =?iso-8859-7?q?=C1=C2=C3?= =?utf-8?b?zpTOlQ==?=
+Content-Type: multipart/mixed; boundary="XtT01VFrJIenjlg+ZCXSSWq4"
+
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: multipart/alternative; boundary="nested"
+
+--nested
+Content-Type: text/plain; charset="iso-8859-7"
+Content-Transfer-Encoding: base64
+
+wcLDxMXGx8jJyg==
+--nested
+Content-Type: text/html; charset=utf-8
+Content-Transfer-Encoding: quoted-printable
+
+<p>=CE=91=CE=92=CE=93</p>
+--nested--
+
+--XtT01VFrJIenjlg+ZCXSSWq4
+Content-Type: application/octet-stream
+Content-Transfer-Encoding: base64
+
+AAECAwQFBgcICQ==
+--XtT01VFrJIenjlg+ZCXSSWq4--
+"#;
+
+fn uppercase_transform() -> NormalizationOptions {
+    NormalizationOptions{
+        part_transform: Some(Box::new(|context, data| {
+            if context.content_type.is_some_and(|t| t.starts_with("text/")) {
+                *data = String::from_utf8_lossy(data).replace('Α', "ω").into_bytes();
+            }
+        })),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn reencoded_email_has_same_content() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART.to_string().into_bytes()).unwrap();
+    let reencoded = email.reencode();
+    let reparsed = Email::from_vec(reencoded.clone()).unwrap();
+
+    assert!(reencoded.is_ascii());
+    assert_eq!(reparsed.body_text(), email.body_text());
+    assert_eq!(reparsed.header_field("Subject"), email.header_field("Subject"));
+    assert_eq!(reparsed.header_field("To"), email.header_field("To"));
+}
+
+#[test]
+fn reencoded_email_keeps_transfer_encodings_and_declares_utf8() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART.to_string().into_bytes()).unwrap();
+    let reencoded = String::from_utf8(email.reencode()).unwrap();
+
+    assert!(reencoded.contains(
+        "Content-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: base64\n\nzpHOks6TzpTOlc6WzpfOmM6Zzpo=\n"));
+    assert!(reencoded.contains("<p>=CE=91=CE=92=CE=93</p>\n"));
+    assert!(!reencoded.contains("iso-8859-7"));
+}
+
+#[test]
+fn reencoded_email_includes_modified_text() {
+    let data = TEST_EMAIL_MULTIPART.to_string().into_bytes();
+    let email = Email::from_vec_with_options(data, uppercase_transform()).unwrap();
+    let reparsed = Email::from_vec(email.reencode()).unwrap();
+
+    assert!(reparsed.body_text().contains("ωΒΓΔΕΖΗΘΙΚ"));
+    assert_eq!(reparsed.parts()[1].decoded_bytes(), "<p>ωΒΓ</p>\n".as_bytes());
+}
+
+#[test]
+fn reencoded_email_has_regenerated_boundaries() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART.to_string().into_bytes()).unwrap();
+    let reencoded = String::from_utf8(email.reencode()).unwrap();
+
+    assert!(!reencoded.contains("XtT01VFrJIenjlg+ZCXSSWq4"));
+    assert!(!reencoded.contains("--nested"));
+    let boundaries: Vec<&str> = reencoded.lines().filter(|l| l.starts_with("--")).collect();
+    assert_eq!(boundaries.len(), 6);
+    assert!(boundaries.iter().all(|b| b.starts_with("--=_mda_")));
+}
+
+#[test]
+fn reencoded_email_keeps_undecoded_parts() {
+    let email = Email::from_vec(TEST_EMAIL_MULTIPART.to_string().into_bytes()).unwrap();
+    let reencoded = String::from_utf8(email.reencode()).unwrap();
+
+    assert!(reencoded.contains(
+        "Content-Type: application/octet-stream\nContent-Transfer-Encoding: base64\n\nAAECAwQFBgcICQ==\n"));
+}
+
+#[test]
+fn non_ascii_text_without_encoding_is_reencoded_as_quoted_printable() {
+    let data = b"To: me@example.com\r\n\r\nAbc\r\n".to_vec();
+    let options = NormalizationOptions{
+        part_transform: Some(Box::new(|_, data| data.extend("Ωmega\r\n".as_bytes()))),
+        ..Default::default()
+    };
+    let email = Email::from_vec_with_options(data, options).unwrap();
+
+    assert_eq!(
+        email.reencode(),
+        b"To: me@example.com\r\n\
+          MIME-Version: 1.0\r\n\
+          Content-Type: text/plain; charset=utf-8\r\n\
+          Content-Transfer-Encoding: quoted-printable\r\n\
+          \r\n\
+          Abc\r\n\
+          =CE=A9mega\r\n");
+}
@@ -6,9 +6,9 @@
 //
 // SPDX-License-Identifier: MPL-2.0
 
-use mda::{Email, EmailRegex};
+use mda::{CompiledMatcherSet, Email, EmailRegex, RegionMatch, SearchOptions};
 
-static TEST_EMAIL: &'static str = "Return-Path: <me@source.com>
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
 To: Destination <someone.else@destination.com>
 Cc: firstcc <firstcc@destination.com>,
  secondcc <secondcc@destination.com>,
@@ -183,3 +183,193 @@ fn multiline_headers() {
     assert_eq!(captures.name("name").map(|m| m.as_bytes()), Some("name123".as_bytes()));
     assert_eq!(captures.name("value").map(|m| m.as_bytes()), Some("value456".as_bytes()));
 }
+
+#[test]
+fn search_regions() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert_eq!(
+        email.search_regions(r"^To:").unwrap(),
+        RegionMatch{header: true, body: true}
+    );
+    assert_eq!(
+        email.search_regions(r"name123").unwrap(),
+        RegionMatch{header: true, body: false}
+    );
+    assert_eq!(
+        email.search_regions(r"^body BODY").unwrap(),
+        RegionMatch{header: false, body: true}
+    );
+    assert!(!email.search_regions(r"nomatch").unwrap().any());
+}
+
+#[test]
+fn search_regions_invalid() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert!(email.search_regions(r"(").is_err());
+}
+
+#[test]
+fn compiled_matcher_set() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let set = CompiledMatcherSet::new(
+        &[
+            r"^x-test-field: name123",
+            r"^To:.*body@destination\.com",
+            r"^Cc:.*secondcc@destination\.com",
+        ]
+    ).unwrap();
+
+    assert_eq!(set.matches(email.header()), [0, 2]);
+    assert_eq!(set.matches(email.body()), [1]);
+    assert!(set.is_match(email.data()));
+    assert!(!set.is_match(b"nothing"));
+}
+
+#[test]
+fn compiled_matcher_set_from_regex_set() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    // Without the standard flags, the search is case-sensitive.
+    let set = CompiledMatcherSet::from_regex_set(
+        regex::bytes::RegexSet::new([r"x-test-field", r"X-Test-Field"]).unwrap()
+    );
+
+    assert_eq!(set.matches(email.header()), [1]);
+}
+
+#[test]
+fn compiled_matcher_set_invalid() {
+    assert!(CompiledMatcherSet::new(&[r"valid", r"(invalid"]).is_err());
+}
+
+#[test]
+fn match_rules_returns_names_of_matching_rules() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    let matched = email.match_rules(
+        &[
+            ("ithaca", r"Ἰθάκη"),
+            ("missing", r"^X-Missing:"),
+            ("cc", r"^Cc:.*secondcc@destination\.com"),
+        ]
+    ).unwrap();
+
+    assert_eq!(matched, ["ithaca", "cc"]);
+}
+
+#[test]
+fn match_rules_invalid() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert!(email.match_rules(&[("valid", r"valid"), ("invalid", r"(invalid")]).is_err());
+}
+
+#[test]
+fn search_opts_default_matches_search() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let options = SearchOptions::default();
+
+    assert!(email.header().search_opts(r"^x-test-field: NAME123", &options).unwrap());
+    assert!(email.header().search_opts(r"^To:", &options).unwrap());
+}
+
+#[test]
+fn search_opts_case_sensitive() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let options = SearchOptions{case_insensitive: false, ..Default::default()};
+
+    assert!(email.header().search_opts(r"^X-Test-Field: name123", &options).unwrap());
+    assert!(!email.header().search_opts(r"^x-test-field: NAME123", &options).unwrap());
+
+    let header = email.header();
+    let captures = header.search_with_captures_opts(r"name(\d+)", &options).unwrap();
+    assert_eq!(captures.unwrap().get(1).unwrap().as_bytes(), b"123");
+
+    let matches = email.body().search_set_opts(&[r"Body body", r"BODY BODY"], &options).unwrap();
+    assert_eq!(matches.into_iter().collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn search_opts_single_line() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let options = SearchOptions{multi_line: false, ..Default::default()};
+
+    assert!(email.data().search_opts(r"^Return-Path:", &options).unwrap());
+    assert!(!email.data().search_opts(r"^To:", &options).unwrap());
+}
+
+#[test]
+fn search_opts_dot_matches_new_line() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let options = SearchOptions{dot_matches_new_line: true, ..Default::default()};
+
+    assert!(!email.body().search(r"Body body.*Ἰθάκη").unwrap());
+    assert!(email.body().search_opts(r"Body body.*Ἰθάκη", &options).unwrap());
+}
+
+#[test]
+fn search_find_returns_offsets_of_first_match() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let header = email.header();
+
+    let (start, end) = header.search_find(r"name\d+").unwrap().unwrap();
+    assert_eq!(&header[start..end], b"name123");
+    assert!(header[..start].ends_with(b"X-Test-Field: "));
+
+    let body = email.body();
+    let (start, end) = body.search_find(r"^to:").unwrap().unwrap();
+    assert_eq!(&body[start..end], b"To:");
+
+    assert_eq!(header.search_find(r"nomatch").unwrap(), None);
+}
+
+#[test]
+fn search_find_opts() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let options = SearchOptions{case_insensitive: false, ..Default::default()};
+
+    assert_eq!(email.header().search_find_opts(r"NAME123", &options).unwrap(), None);
+    assert!(email.header().search_find_opts(r"name123", &options).unwrap().is_some());
+}
+
+#[test]
+fn search_find_invalid() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    assert!(email.data().search_find(r"(unclosed").is_err());
+}
+
+/// Searchable data implementing only the required methods of EmailRegex.
+struct Text(Vec<u8>);
+
+impl AsRef<[u8]> for Text {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl EmailRegex for Text {
+    fn search(&self, regex: &str) -> mda::Result<bool> {
+        Ok(regex::bytes::Regex::new(regex)?.is_match(&self.0))
+    }
+
+    fn search_with_captures(&self, regex: &str) -> mda::Result<Option<regex::bytes::Captures<'_>>> {
+        Ok(regex::bytes::Regex::new(regex)?.captures(&self.0))
+    }
+
+    fn search_set(&self, regex_set: &[&str]) -> mda::Result<regex::bytes::SetMatches> {
+        Ok(regex::bytes::RegexSet::new(regex_set)?.matches(&self.0))
+    }
+}
+
+#[test]
+fn custom_implementation_gets_default_methods() {
+    let text = Text(b"Subject: Hello\nTo: me@example.com\n".to_vec());
+    let options = SearchOptions{case_insensitive: false, ..Default::default()};
+
+    assert_eq!(text.search_find(r"To:").unwrap(), Some((15, 18)));
+    assert!(text.search_opts(r"^to:", &SearchOptions::default()).unwrap());
+    assert!(!text.search_opts(r"^to:", &options).unwrap());
+    assert_eq!(text.search_find_opts(r"Hello", &options).unwrap(), Some((9, 14)));
+}
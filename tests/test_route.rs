@@ -0,0 +1,74 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+To: Destination <someone.else@destination.com>
+Subject: [URGENT] Status
+
+Body body body
+";
+
+fn count_new(path: &Path) -> usize {
+    fs::read_dir(path.join("new")).map_or(0, |entries| entries.count())
+}
+
+#[test]
+fn first_matching_rule_delivers() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    let route = email
+        .when(|e| e.header_field("Subject").unwrap().contains("[LOW]"))
+        .deliver(tmpdir.path().join("low")).unwrap()
+        .when(|e| e.header_field("Subject").unwrap().contains("[URGENT]"))
+        .deliver(tmpdir.path().join("urgent")).unwrap()
+        .when(|e| e.header_field("To").unwrap().contains("destination.com"))
+        .deliver(tmpdir.path().join("destination")).unwrap()
+        .otherwise(tmpdir.path().join("inbox")).unwrap();
+
+    assert_eq!(count_new(&tmpdir.path().join("low")), 0);
+    assert_eq!(count_new(&tmpdir.path().join("urgent")), 1);
+    assert_eq!(count_new(&tmpdir.path().join("destination")), 0);
+    assert_eq!(count_new(&tmpdir.path().join("inbox")), 0);
+    assert!(route.delivered_path().unwrap().starts_with(tmpdir.path().join("urgent")));
+}
+
+#[test]
+fn otherwise_delivers_if_no_rule_matched() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+
+    let route = email
+        .when(|_| false)
+        .deliver(tmpdir.path().join("never")).unwrap()
+        .otherwise(tmpdir.path().join("inbox")).unwrap();
+
+    assert_eq!(count_new(&tmpdir.path().join("never")), 0);
+    assert_eq!(count_new(&tmpdir.path().join("inbox")), 1);
+    assert!(route.delivered_path().is_some());
+}
+
+#[test]
+fn predicates_after_delivery_are_not_evaluated() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let evaluated = Cell::new(false);
+
+    email
+        .when(|_| true)
+        .deliver(tmpdir.path().join("first")).unwrap()
+        .when(|_| { evaluated.set(true); true })
+        .deliver(tmpdir.path().join("second")).unwrap();
+
+    assert!(!evaluated.get());
+}
@@ -0,0 +1,57 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+mod common;
+
+use common::email_with_header;
+use mda::SpfVerdict;
+use std::net::IpAddr;
+
+#[test]
+fn received_spf_is_parsed() {
+    let email = email_with_header(
+        "Received-SPF: Pass (mailfrom) identity=mailfrom; client-ip=192.0.2.1;\n helo=mail.example.com; envelope-from=me@source.com"
+    );
+    let spf = email.received_spf().unwrap();
+
+    assert_eq!(spf.verdict, SpfVerdict::Pass);
+    assert_eq!(spf.client_ip, Some("192.0.2.1".parse::<IpAddr>().unwrap()));
+}
+
+#[test]
+fn received_spf_comment_is_skipped() {
+    let email = email_with_header(
+        "Received-SPF: softfail (example.com: domain of transitioning me@source.com does not designate 2001:db8::1 as permitted sender; see (docs)) client-ip=2001:db8::1;"
+    );
+    let spf = email.received_spf().unwrap();
+
+    assert_eq!(spf.verdict, SpfVerdict::SoftFail);
+    assert_eq!(spf.client_ip, Some("2001:db8::1".parse::<IpAddr>().unwrap()));
+}
+
+#[test]
+fn received_spf_without_client_ip_has_no_client_ip() {
+    let email = email_with_header("Received-SPF: none");
+    let spf = email.received_spf().unwrap();
+
+    assert_eq!(spf.verdict, SpfVerdict::None);
+    assert_eq!(spf.client_ip, None);
+}
+
+#[test]
+fn received_spf_with_unknown_result_is_none() {
+    assert_eq!(email_with_header("Received-SPF: maybe client-ip=192.0.2.1").received_spf(), None);
+    assert_eq!(email_with_header("Subject: hi").received_spf(), None);
+}
+
+#[test]
+fn first_received_spf_is_used() {
+    let email = email_with_header("Received-SPF: fail client-ip=192.0.2.1\nReceived-SPF: pass");
+
+    assert_eq!(email.received_spf().unwrap().verdict, SpfVerdict::Fail);
+}
@@ -0,0 +1,76 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::{Email, Script};
+
+fn email_with_subject(subject: &str) -> Email {
+    Email::from_vec(format!("To: me@example.com\nSubject: {}\n\nBody\n", subject).into_bytes()).unwrap()
+}
+
+#[test]
+fn leading_tags_are_extracted() {
+    let email = email_with_subject("[list] [TICKET-123]Printer [on] fire");
+
+    assert_eq!(email.subject_tags(), ["list", "TICKET-123"]);
+    assert_eq!(email.subject_without_tags(), Some("Printer [on] fire"));
+}
+
+#[test]
+fn nested_tags_are_single_tag() {
+    let email = email_with_subject("[[list] outer] Hello");
+
+    assert_eq!(email.subject_tags(), ["[list] outer"]);
+    assert_eq!(email.subject_without_tags(), Some("Hello"));
+}
+
+#[test]
+fn tags_after_other_text_are_ignored() {
+    let email = email_with_subject("Re: [list] Hello");
+
+    assert!(email.subject_tags().is_empty());
+    assert_eq!(email.subject_without_tags(), Some("Re: [list] Hello"));
+}
+
+#[test]
+fn unterminated_tag_is_subject_text() {
+    let email = email_with_subject("[list] [unterminated Hello");
+
+    assert_eq!(email.subject_tags(), ["list"]);
+    assert_eq!(email.subject_without_tags(), Some("[unterminated Hello"));
+}
+
+#[test]
+fn encoded_subject_tags_are_decoded() {
+    let email = email_with_subject("=?utf-8?b?W86RzpLOk10gSGk=?=");
+
+    assert_eq!(email.subject_tags(), ["ΑΒΓ"]);
+    assert_eq!(email.subject_without_tags(), Some("Hi"));
+}
+
+#[test]
+fn missing_subject_has_no_tags() {
+    let email = Email::from_vec(b"To: me@example.com\n\nBody\n".to_vec()).unwrap();
+
+    assert!(email.subject_tags().is_empty());
+    assert_eq!(email.subject_without_tags(), None);
+}
+
+#[test]
+fn subject_script_is_majority_script() {
+    assert_eq!(email_with_subject("Hello world").subject_script(), Some(Script::Latin));
+    assert_eq!(email_with_subject("Re: Καλημέρα").subject_script(), Some(Script::Greek));
+    assert_eq!(email_with_subject("[support] 打印机坏了").subject_script(), Some(Script::Cjk));
+    assert_eq!(email_with_subject("Fwd: שלום עולם").subject_script(), Some(Script::Hebrew));
+    assert_eq!(email_with_subject("123 !?").subject_script(), None);
+}
+
+#[test]
+fn rtl_scripts_are_detected() {
+    assert!(email_with_subject("مرحبا").subject_script().unwrap().is_rtl());
+    assert!(!email_with_subject("Привет").subject_script().unwrap().is_rtl());
+}
@@ -0,0 +1,75 @@
+// Copyright 2019 Alexandros Frantzis
+//
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+//
+// SPDX-License-Identifier: MPL-2.0
+
+use mda::Email;
+
+static TEST_EMAIL: &str = "Return-Path: <me@source.com>
+Message-ID: <1234@source.com>
+From: Me <me@source.com>
+To: Destination <someone.else@destination.com>, other@destination.com
+Subject: =?utf-8?q?Hello_=CE=B1?=
+
+Body body body
+";
+
+#[test]
+fn summary_contains_parsed_metadata() {
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let summary = email.summary();
+
+    assert_eq!(summary.message_id.as_deref(), Some("<1234@source.com>"));
+    assert_eq!(summary.date, None);
+    assert_eq!(summary.from, vec!["me@source.com"]);
+    assert_eq!(summary.to, vec!["someone.else@destination.com", "other@destination.com"]);
+    assert!(summary.cc.is_empty());
+    assert_eq!(summary.subject.as_deref(), Some("Hello α"));
+    assert_eq!(summary.size, TEST_EMAIL.len());
+    assert!(!summary.has_attachments);
+    assert!(!summary.is_bounce);
+    assert_eq!(summary.snippet.as_deref(), Some("Body body body"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn sidecar_delivery_writes_summary_next_to_email() {
+    use std::fs;
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let (path, sidecar) = email.deliver_to_maildir_with_sidecar(tmpdir.path()).unwrap();
+    let sidecar = sidecar.unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), TEST_EMAIL.as_bytes());
+    assert_eq!(sidecar.to_str().unwrap(), format!("{}.meta", path.display()));
+
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&fs::read(sidecar).unwrap()).unwrap();
+    assert_eq!(metadata["message_id"], "<1234@source.com>");
+    assert_eq!(metadata["subject"], "Hello α");
+    assert_eq!(metadata["to"][1], "other@destination.com");
+
+    assert_eq!(fs::read_dir(tmpdir.path().join("new")).unwrap().count(), 2);
+    assert_eq!(fs::read_dir(tmpdir.path().join("tmp")).unwrap().count(), 0);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deliver_and_summarize_returns_path_and_json_summary() {
+    use std::fs;
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let email = Email::from_vec(TEST_EMAIL.to_string().into_bytes()).unwrap();
+    let (path, summary) = email.deliver_and_summarize(tmpdir.path()).unwrap();
+
+    assert_eq!(fs::read(&path).unwrap(), TEST_EMAIL.as_bytes());
+
+    let summary: serde_json::Value = serde_json::from_str(&summary).unwrap();
+    assert_eq!(summary["from"][0], "me@source.com");
+    assert_eq!(summary["size"], TEST_EMAIL.len());
+    assert_eq!(summary["has_attachments"], false);
+}